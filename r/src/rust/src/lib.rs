@@ -0,0 +1,90 @@
+use extendr_api::prelude::*;
+use r_sysdeps::{api, os, repository};
+
+fn other<E: std::fmt::Display>(err: E) -> Error {
+    Error::Other(format!("{:#}", err))
+}
+
+/// System requirements of R packages.
+/// @export
+#[extendr]
+fn sysdeps(
+    packages: Vec<String>,
+    server: &str,
+    repository: Nullable<String>,
+    os_name: Nullable<String>,
+    os_version: Nullable<String>,
+) -> Result<List> {
+    let server = api::normalize_server(server).map_err(other)?;
+    let server = server.as_str();
+    let (distribution, release) =
+        os::detect(os_name.into_option(), os_version.into_option()).map_err(other)?;
+    let repository_name = match repository.into_option() {
+        Some(name) => name,
+        None => api::status(server).map_err(other)?.cran_repo().to_string(),
+    };
+    let repositories = api::repositories(server).map_err(other)?;
+    let repository = repository::find(&repositories, &repository_name, None).map_err(other)?;
+
+    let response = api::sysreqs(server, &distribution, &release, repository.id, &packages)
+        .map_err(other)?;
+
+    let names: Vec<String> = response.requirements.iter().map(|req| req.name.clone()).collect();
+    let values = response.requirements.into_iter().map(|req| {
+        let scripts = |steps: Option<Vec<r_sysdeps::models::APIPrePost>>| -> Vec<String> {
+            steps
+                .unwrap_or_default()
+                .into_iter()
+                .map(|step| step.script)
+                .collect()
+        };
+        let reqs = req.requirements;
+        Robj::from(list!(
+            packages = reqs.packages,
+            pre_install = scripts(reqs.pre_install),
+            install_scripts = reqs.install_scripts,
+            post_install = scripts(reqs.post_install)
+        ))
+    });
+
+    List::from_names_and_values(names, values)
+}
+
+/// Package repository URL.
+/// @export
+#[extendr]
+fn repo_url(
+    binary: bool,
+    server: &str,
+    repository: Nullable<String>,
+    os_name: Nullable<String>,
+    os_version: Nullable<String>,
+) -> Result<String> {
+    let server = api::normalize_server(server).map_err(other)?;
+    let server = server.as_str();
+    let status = api::status(server).map_err(other)?;
+    let repository_name = repository.into_option().unwrap_or_else(|| status.cran_repo().to_string());
+
+    if !binary {
+        return Ok(repository::source_url(server, &repository_name));
+    }
+
+    let (distribution, release) =
+        os::detect(os_name.into_option(), os_version.into_option()).map_err(other)?;
+    repository::binary_url(
+        server,
+        &repository_name,
+        &status,
+        &distribution,
+        &release,
+        &os::detect_arch(),
+        repository::LATEST,
+    )
+    .map_err(other)
+}
+
+extendr_module! {
+    mod rsysdeps;
+    fn sysdeps;
+    fn repo_url;
+}
@@ -0,0 +1,20 @@
+#!/bin/sh
+# Regenerate data/sysreqs.json.gz, the rules database bundled into the
+# binary, as `r-sysdeps db update` builds it: from the archive of
+# rstudio/r-system-requirements and CRAN's PACKAGES index.
+#
+# RULES_URL and PACKAGES_URL replace the upstream sources, e.g. with a mirror.
+set -eu
+
+cd "$(dirname "$0")/.."
+output=data/sysreqs.json.gz
+
+set -- --output "$output" \
+    --packages-url "${PACKAGES_URL:-https://cloud.r-project.org/src/contrib/PACKAGES}"
+# db update defaults to the upstream archive
+if [ -n "${RULES_URL:-}" ]; then
+    set -- "$@" --rules-url "$RULES_URL"
+fi
+cargo run --quiet -- --no-cache db update "$@"
+# the binary checks its snapshot by being built with it, not by a checksum
+rm -f "$output.sha256"
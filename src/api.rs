@@ -0,0 +1,1304 @@
+#[cfg(feature = "http")]
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::Read;
+#[cfg(feature = "http")]
+use std::path::Path;
+#[cfg(feature = "http")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "http")]
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+
+#[cfg(feature = "http")]
+use crate::cache::{self, Cache, EntryWriter};
+#[cfg(feature = "http")]
+use crate::models::{APIRepository, APIStatusResponse, APISysReqs};
+#[cfg(feature = "http")]
+use crate::{recording, timings};
+
+/// Longest excerpt of an error response body included in messages.
+const EXCERPT_LENGTH: usize = 200;
+
+/// A non-2xx response, with the start of its body.
+#[derive(Debug, Clone)]
+pub struct HttpError {
+    pub url: String,
+    pub status: u16,
+    pub reason: String,
+    pub excerpt: String,
+}
+
+impl HttpError {
+    pub fn new(url: &str, status: u16, reason: &str, body: &[u8]) -> Self {
+        HttpError {
+            url: url.to_string(),
+            status,
+            reason: reason.to_string(),
+            excerpt: excerpt(body),
+        }
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} responded with HTTP {}", self.url, self.status)?;
+        if !self.reason.is_empty() {
+            write!(f, " {}", self.reason)?;
+        }
+        if !self.excerpt.is_empty() {
+            write!(f, ": {}", self.excerpt)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+/// The start of `body`, on one line.
+fn excerpt(body: &[u8]) -> String {
+    let body = String::from_utf8_lossy(body);
+    let collapsed = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut excerpt: String = collapsed.chars().take(EXCERPT_LENGTH).collect();
+    if excerpt.len() < collapsed.len() {
+        excerpt.push('…');
+    }
+
+    excerpt
+}
+
+/// Parse a JSON response body of `url` as it is read, without buffering it.
+///
+/// Anything that isn't JSON at all, typically the HTML of a proxy or login
+/// page, is reported as such along with the start of the body.
+pub fn read_json<T: DeserializeOwned, R: Read>(url: &str, mut body: R) -> Result<T> {
+    // skip to the first significant byte, which tells JSON from anything else
+    let mut start = Vec::new();
+    let mut byte = [0u8];
+    loop {
+        if body
+            .read(&mut byte)
+            .with_context(|| format!("failed to read response from {}", url))?
+            == 0
+        {
+            break;
+        }
+        start.push(byte[0]);
+        // whitespace, or a byte of the UTF-8 byte order mark
+        if !byte[0].is_ascii_whitespace() && ![0xef, 0xbb, 0xbf].contains(&byte[0]) {
+            break;
+        }
+    }
+    if !matches!(start.last(), Some(b'{') | Some(b'[')) {
+        body.take(EXCERPT_LENGTH as u64 * 4)
+            .read_to_end(&mut start)
+            .with_context(|| format!("failed to read response from {}", url))?;
+        bail!(
+            "received non-JSON response from {} (are you behind a proxy/login page?): {}",
+            url,
+            excerpt(&start)
+        );
+    }
+
+    let significant = &start[start.len() - 1..];
+    serde_json::from_reader(significant.chain(body))
+        .with_context(|| format!("failed to parse JSON response from {}", url))
+}
+
+/// Normalize a server given by the user to `scheme://host[:port][/path]`.
+///
+/// The scheme defaults to https, and trailing slashes or `__api__` suffixes
+/// are dropped, so that endpoint URLs can be appended to it.
+pub fn normalize_server(server: &str) -> Result<String> {
+    let server = server.trim();
+    if server.is_empty() {
+        bail!("server URL is empty");
+    }
+    let with_scheme = if server.contains("://") {
+        server.to_string()
+    } else {
+        format!("https://{}", server)
+    };
+
+    let mut u = url::Url::parse(&with_scheme)
+        .with_context(|| format!("invalid server URL '{}'", server))?;
+    if u.scheme() != "http" && u.scheme() != "https" {
+        bail!(
+            "invalid server URL '{}': scheme must be http or https",
+            server
+        );
+    }
+    if u.host_str().is_none() {
+        bail!("invalid server URL '{}': missing host", server);
+    }
+    if u.query().is_some() || u.fragment().is_some() {
+        bail!(
+            "invalid server URL '{}': unexpected query or fragment",
+            server
+        );
+    }
+
+    let mut path = u.path().trim_end_matches('/');
+    while let Some(prefix) = path.strip_suffix("/__api__") {
+        path = prefix.trim_end_matches('/');
+    }
+    let path = path.to_string();
+    u.set_path(&path);
+
+    Ok(u.as_str().trim_end_matches('/').to_string())
+}
+
+/// URL of the server status endpoint.
+pub fn status_url(server: &str) -> String {
+    format!("{}/__api__/status", server)
+}
+
+/// URL of the repository listing endpoint.
+pub fn repositories_url(server: &str) -> String {
+    format!("{}/__api__/repos", server)
+}
+
+/// URL of the system requirements endpoint for `packages`.
+///
+/// Package names are validated, and percent-encoded as query parameters.
+pub fn sysreqs_url(
+    server: &str,
+    distribution: &str,
+    release: &str,
+    repo_id: u64,
+    packages: &[String],
+) -> Result<url::Url> {
+    let mut u = url::Url::parse_with_params(
+        format!("{}/__api__/repos/{}/sysreqs", server, repo_id).as_str(),
+        &[("distribution", distribution), ("release", release)],
+    )
+    .with_context(|| "failed to construct server URL")?;
+    for pkgname in packages {
+        crate::packages::validate(pkgname)?;
+        u.query_pairs_mut().append_pair("pkgname", pkgname);
+    }
+
+    Ok(u)
+}
+
+/// Fetch the system requirements of `packages` for a distribution release.
+#[cfg(feature = "http")]
+pub fn sysreqs(
+    server: &str,
+    distribution: &str,
+    release: &str,
+    repo_id: u64,
+    packages: &[String],
+) -> Result<APISysReqs> {
+    sysreqs_with(
+        &settings(),
+        server,
+        distribution,
+        release,
+        repo_id,
+        packages,
+    )
+}
+
+/// Fetch the system requirements of `packages` as [`sysreqs`] does, with
+/// `settings` instead of the process-wide ones.
+#[cfg(feature = "http")]
+#[tracing::instrument(level = "debug", skip(settings, server, packages), fields(packages = packages.len()))]
+pub fn sysreqs_with(
+    settings: &Settings,
+    server: &str,
+    distribution: &str,
+    release: &str,
+    repo_id: u64,
+    packages: &[String],
+) -> Result<APISysReqs> {
+    let u = sysreqs_url(server, distribution, release, repo_id, packages)?;
+    let _timing = timings::start("request", || u.to_string());
+
+    read_json(u.as_str(), send_cached(settings, u.as_str(), 60)?)
+}
+
+/// List the repositories available on the server.
+#[cfg(feature = "http")]
+pub fn repositories(server: &str) -> Result<Vec<APIRepository>> {
+    repositories_with(&settings(), server)
+}
+
+/// List the repositories as [`repositories`] does, with `settings`.
+#[cfg(feature = "http")]
+#[tracing::instrument(level = "debug", skip(settings))]
+pub fn repositories_with(settings: &Settings, server: &str) -> Result<Vec<APIRepository>> {
+    let url = repositories_url(server);
+    let _timing = timings::start("request", || url.clone());
+    read_json(&url, send_cached(settings, &url, 10)?)
+}
+
+/// Fetch the server status.
+#[cfg(feature = "http")]
+pub fn status(server: &str) -> Result<APIStatusResponse> {
+    status_with(&settings(), server)
+}
+
+/// Fetch the server status as [`status`] does, with `settings`.
+#[cfg(feature = "http")]
+#[tracing::instrument(level = "debug", skip(settings))]
+pub fn status_with(settings: &Settings, server: &str) -> Result<APIStatusResponse> {
+    let url = status_url(server);
+    let _timing = timings::start("request", || url.clone());
+    read_json(&url, send_cached(settings, &url, 10)?)
+}
+
+/// Names of the packages in a repository, from its `PACKAGES` index, cached
+/// as other responses are.
+#[cfg(feature = "http")]
+pub fn package_index(server: &str, repository: &str) -> Result<BTreeSet<String>> {
+    let url = crate::repository::packages_url(server, repository);
+    let _timing = timings::start("request", || url.clone());
+    let mut body = Vec::new();
+    send_cached(&settings(), &url, 60)?
+        .read_to_end(&mut body)
+        .with_context(|| format!("failed to read response from {}", url))?;
+    if String::from_utf8_lossy(&body).trim_start().starts_with('<') {
+        bail!(
+            "received HTML instead of a package index from {} (are you behind a proxy/login page?): {}",
+            url,
+            excerpt(&body)
+        );
+    }
+
+    Ok(crate::dcf::parse(&String::from_utf8_lossy(&body))
+        .into_iter()
+        .filter_map(|mut record| record.remove("Package"))
+        .collect())
+}
+
+/// Check that the server has a snapshot of a repository, as
+/// [`crate::repository::check_snapshot`] accepts, by asking for the
+/// `PACKAGES` index of the snapshot without receiving it.
+#[cfg(feature = "http")]
+pub fn check_snapshot(server: &str, repository: &str, snapshot: &str) -> Result<()> {
+    crate::repository::check_snapshot(snapshot)?;
+    let url = format!(
+        "{}/src/contrib/PACKAGES",
+        crate::repository::snapshot_url(server, repository, snapshot)
+    );
+    let _timing = timings::start("request", || format!("HEAD {}", url));
+    let settings = settings();
+    match send_with(&settings, "HEAD", &url, b"", 10, || {
+        request(&settings, "HEAD", &url)
+    }) {
+        Ok(_) => Ok(()),
+        Err(err)
+            if matches!(
+                err.downcast_ref::<HttpError>(),
+                Some(HttpError { status: 404, .. })
+            ) =>
+        {
+            Err(err.context(format!(
+                "server has no snapshot {} of repository {}",
+                snapshot, repository
+            )))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// GET `url`, failing on non-2xx responses.
+#[cfg(feature = "http")]
+#[tracing::instrument(level = "debug", skip(timeout))]
+pub fn get_bytes(url: &str, timeout: u64) -> Result<Vec<u8>> {
+    let _timing = timings::start("request", || url.to_string());
+    let mut body = Vec::new();
+    send(&settings(), url, timeout)?
+        .read_to_end(&mut body)
+        .with_context(|| format!("failed to read response from {}", url))?;
+
+    Ok(body)
+}
+
+/// GET `url` and parse the JSON response.
+#[cfg(feature = "http")]
+#[tracing::instrument(level = "debug", skip(timeout))]
+pub fn get_json<T: DeserializeOwned>(url: &str, timeout: u64) -> Result<T> {
+    let _timing = timings::start("request", || url.to_string());
+    read_json(url, send(&settings(), url, timeout)?)
+}
+
+/// Settings of requests: credentials and extra headers for a server,
+/// retries, timeouts, TLS and offline mode.
+///
+/// The functions of this module use the process-wide settings, changed by
+/// [`set_bearer_token`] and the like; each [`crate::client::Client`] keeps
+/// its own.
+#[cfg(feature = "http")]
+#[derive(Clone)]
+pub struct Settings {
+    /// Origin of the server and the `Authorization` header sent to it.
+    credentials: Option<(url::Origin, String)>,
+    /// Extra headers sent with requests to the origin they were added for.
+    headers: Vec<(url::Origin, String, String)>,
+    /// Times a request is retried, and the delay before the first retry.
+    retries: (u32, Duration),
+    /// Timeout of each request attempt, instead of the one of the endpoint.
+    timeout: Option<Duration>,
+    /// Instead of verifying servers against the bundled root certificates.
+    tls: Option<Arc<rustls::ClientConfig>>,
+    /// Whether requests are refused, leaving cached and recorded responses only.
+    offline: bool,
+    /// End of the time budget for all requests, and the budget itself.
+    deadline: Option<(Instant, Duration)>,
+}
+
+#[cfg(feature = "http")]
+impl Settings {
+    /// No credentials or extra headers, [`DEFAULT_RETRIES`], the timeouts
+    /// of the endpoints, the bundled root certificates, and no deadline.
+    pub const fn new() -> Self {
+        Settings {
+            credentials: None,
+            headers: Vec::new(),
+            retries: (DEFAULT_RETRIES, DEFAULT_BACKOFF),
+            timeout: None,
+            tls: None,
+            offline: false,
+            deadline: None,
+        }
+    }
+
+    /// Authenticate requests to `server`, and to it only, with HTTP Basic
+    /// authentication.
+    pub fn set_basic_auth(&mut self, server: &str, user: &str, password: &str) -> Result<()> {
+        use base64::Engine;
+
+        let token =
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, password));
+        self.credentials = Some((origin(server)?, format!("Basic {}", token)));
+        Ok(())
+    }
+
+    /// Authenticate requests to `server`, and to it only, with an API token,
+    /// replacing any Basic authentication.
+    pub fn set_bearer_token(&mut self, server: &str, token: &str) -> Result<()> {
+        self.credentials = Some((origin(server)?, format!("Bearer {}", token)));
+        Ok(())
+    }
+
+    /// Send a header with requests to `server`, and to it only.
+    pub fn add_header(&mut self, server: &str, name: &str, value: &str) -> Result<()> {
+        self.headers
+            .push((origin(server)?, name.to_string(), value.to_string()));
+        Ok(())
+    }
+
+    /// Retry requests up to `retries` times, waiting `backoff` before the
+    /// first retry and twice as long before each next one.
+    pub fn set_retries(&mut self, retries: u32, backoff: Duration) {
+        self.retries = (retries, backoff);
+    }
+
+    /// Time out each request attempt after `timeout`, in whole seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    /// Verify servers against the certificates of the PEM file `cacert`
+    /// too, or not at all when `insecure`.
+    pub fn set_tls(&mut self, cacert: Option<&Path>, insecure: bool) -> Result<()> {
+        self.tls = Some(tls_config(cacert, insecure)?);
+        Ok(())
+    }
+
+    /// Refuse requests; cached and recorded responses are still used.
+    pub fn set_offline(&mut self) {
+        self.offline = true;
+    }
+
+    /// Fail requests, and stop retrying them, once `budget` has passed from
+    /// now.
+    pub fn set_deadline(&mut self, budget: Duration) {
+        self.deadline = Some((Instant::now() + budget, budget));
+    }
+
+    /// The extra headers to send with a request to `url`.
+    fn headers(&self, url: &str) -> Vec<(String, String)> {
+        let target = match url::Url::parse(url) {
+            Ok(u) => u.origin(),
+            Err(_) => return Vec::new(),
+        };
+        self.headers
+            .iter()
+            .filter(|(origin, _, _)| *origin == target)
+            .map(|(_, name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    /// The `Authorization` header to send with a request to `url`, if any.
+    fn authorization(&self, url: &str) -> Option<String> {
+        let (origin, header) = self.credentials.as_ref()?;
+        let target = url::Url::parse(url).ok()?.origin();
+        Some(header.clone()).filter(|_| &target == origin)
+    }
+
+    /// Time left before the deadline, if one is set, failing once it has
+    /// passed.
+    fn remaining(&self) -> Result<Option<Duration>> {
+        let (deadline, budget) = match self.deadline {
+            Some(deadline) => deadline,
+            None => return Ok(None),
+        };
+        match deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => Ok(Some(remaining)),
+            _ => bail!(
+                "deadline of {} exceeded",
+                humantime::format_duration(budget)
+            ),
+        }
+    }
+
+    /// Sleep for `delay` before a retry, unless that would run past the
+    /// deadline.
+    fn wait(&self, delay: Duration) -> Result<()> {
+        if let Some(remaining) = self.remaining()? {
+            if delay >= remaining {
+                bail!(
+                    "deadline would be exceeded by retrying in {}",
+                    humantime::format_duration(delay)
+                );
+            }
+        }
+        std::thread::sleep(delay);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "http")]
+impl Default for Settings {
+    fn default() -> Self {
+        Settings::new()
+    }
+}
+
+// credentials and header values are left out, as they may be secret
+#[cfg(feature = "http")]
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Settings")
+            .field("authenticated", &self.credentials.is_some())
+            .field("headers", &self.headers.len())
+            .field("retries", &self.retries)
+            .field("timeout", &self.timeout)
+            .field("tls", &self.tls.is_some())
+            .field("offline", &self.offline)
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+/// The origin of `server`, which credentials and headers are sent to.
+#[cfg(feature = "http")]
+fn origin(server: &str) -> Result<url::Origin> {
+    Ok(url::Url::parse(server)
+        .with_context(|| format!("invalid server URL '{}'", server))?
+        .origin())
+}
+
+/// The process-wide settings.
+#[cfg(feature = "http")]
+static SETTINGS: Mutex<Settings> = Mutex::new(Settings::new());
+
+#[cfg(feature = "http")]
+fn global() -> std::sync::MutexGuard<'static, Settings> {
+    SETTINGS.lock().expect("settings lock poisoned")
+}
+
+/// A copy of the process-wide settings, as they are now.
+#[cfg(feature = "http")]
+pub fn settings() -> Settings {
+    global().clone()
+}
+
+/// Authenticate all further requests to `server`, and to it only, with HTTP
+/// Basic authentication.
+#[cfg(feature = "http")]
+pub fn set_basic_auth(server: &str, user: &str, password: &str) -> Result<()> {
+    global().set_basic_auth(server, user, password)
+}
+
+/// Authenticate all further requests to `server`, and to it only, with an
+/// API token, replacing any Basic authentication.
+#[cfg(feature = "http")]
+pub fn set_bearer_token(server: &str, token: &str) -> Result<()> {
+    global().set_bearer_token(server, token)
+}
+
+/// Send a header with all further requests to `server`, and to it only,
+/// such as one required by a proxy in front of it.
+#[cfg(feature = "http")]
+pub fn add_header(server: &str, name: &str, value: &str) -> Result<()> {
+    global().add_header(server, name, value)
+}
+
+/// Time out each further request attempt after `timeout`, in whole seconds,
+/// instead of after the default of its endpoint.
+#[cfg(feature = "http")]
+pub fn set_timeout(timeout: Duration) {
+    global().set_timeout(timeout);
+}
+
+/// Verify servers of all further requests against the certificates of the
+/// PEM file `cacert` too, e.g. of a private CA, or not at all when
+/// `insecure`.
+#[cfg(feature = "http")]
+pub fn set_tls(cacert: Option<&Path>, insecure: bool) -> Result<()> {
+    global().set_tls(cacert, insecure)
+}
+
+/// Refuse all requests from now on; cached and recorded responses are still
+/// used.
+#[cfg(feature = "http")]
+pub fn set_offline() {
+    global().set_offline();
+}
+
+/// Whether requests are refused.
+#[cfg(feature = "http")]
+pub fn is_offline() -> bool {
+    global().offline
+}
+
+/// Fail requests, and stop retrying them, once `budget` has passed from now.
+///
+/// Request timeouts are shortened to the time left.
+#[cfg(feature = "http")]
+pub fn set_deadline(budget: Duration) {
+    global().set_deadline(budget);
+}
+
+/// `server` without the user and password of its URL, and these, decoded.
+#[cfg(feature = "http")]
+pub fn split_userinfo(server: &str) -> Result<(String, Option<(String, String)>)> {
+    let mut u =
+        url::Url::parse(server).with_context(|| format!("invalid server URL '{}'", server))?;
+    if u.username().is_empty() && u.password().is_none() {
+        return Ok((server.to_string(), None));
+    }
+
+    let decode = |text: &str| {
+        percent_encoding::percent_decode_str(text)
+            .decode_utf8()
+            .map(|text| text.into_owned())
+            .context("invalid user or password in server URL")
+    };
+    let user = decode(u.username())?;
+    let password = decode(u.password().unwrap_or_default())?;
+    // only fails for URLs that can't have a user, which have none to remove
+    let _ = u.set_username("");
+    let _ = u.set_password(None);
+
+    Ok((
+        u.as_str().trim_end_matches('/').to_string(),
+        Some((user, password)),
+    ))
+}
+
+/// Longest `Retry-After` delay that is waited out rather than reported.
+#[cfg(feature = "http")]
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Times a rate-limited, failing (5xx) or interrupted request is retried
+/// unless set otherwise.
+#[cfg(feature = "http")]
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Delay before the first retry unless set otherwise, doubled for each
+/// further one.
+#[cfg(feature = "http")]
+pub const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Retry each further request up to `retries` times, waiting `backoff`
+/// before the first retry and twice as long before each next one.
+#[cfg(feature = "http")]
+pub fn set_retries(retries: u32, backoff: Duration) {
+    global().set_retries(retries, backoff);
+}
+
+/// `delay` shortened by up to half at random, so that concurrent requests
+/// failing together are not retried together.
+#[cfg(feature = "http")]
+fn jitter(delay: Duration) -> Duration {
+    use std::hash::{BuildHasher, Hasher};
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    delay.mul_f64(1.0 - (random % 1000) as f64 / 2000.0)
+}
+
+/// Sent with every request, as some services require one.
+#[cfg(feature = "http")]
+const USER_AGENT: &str = concat!("r-sysdeps/", env!("CARGO_PKG_VERSION"));
+
+/// Earliest time of the next request, and the interval between requests.
+#[cfg(feature = "http")]
+static PACING: Mutex<Option<(Instant, Duration)>> = Mutex::new(None);
+
+/// Space out all further requests so that at most `max_rps` are sent per second.
+#[cfg(feature = "http")]
+pub fn set_max_rps(max_rps: f64) {
+    let interval = Duration::from_secs_f64(1.0 / max_rps);
+    *PACING.lock().expect("pacing lock poisoned") = Some((Instant::now(), interval));
+}
+
+/// Lower the request rate to at most `max_rps`, keeping a lower one.
+#[cfg(feature = "http")]
+pub fn limit_rps(max_rps: f64) {
+    let interval = Duration::from_secs_f64(1.0 / max_rps);
+    let mut pacing = PACING.lock().expect("pacing lock poisoned");
+    match pacing.as_mut() {
+        Some((_, current)) => *current = (*current).max(interval),
+        None => *pacing = Some((Instant::now(), interval)),
+    }
+}
+
+/// Wait for the next request slot, if requests are paced.
+#[cfg(feature = "http")]
+fn pace() {
+    let wait = match PACING.lock().expect("pacing lock poisoned").as_mut() {
+        Some((next, interval)) => {
+            let now = Instant::now();
+            let slot = (*next).max(now);
+            *next = slot + *interval;
+            slot - now
+        }
+        None => return,
+    };
+    if !wait.is_zero() {
+        std::thread::sleep(wait);
+    }
+}
+
+/// Agents sending requests, by the proxy they send them through and the TLS
+/// settings they use.
+#[cfg(feature = "http")]
+#[allow(clippy::type_complexity)]
+static AGENTS: Mutex<
+    Vec<(
+        Option<String>,
+        Option<Arc<rustls::ClientConfig>>,
+        ureq::Agent,
+    )>,
+> = Mutex::new(Vec::new());
+
+/// TLS settings verifying servers against the bundled root certificates and
+/// those of the PEM file `cacert`, or not at all when `insecure`.
+#[cfg(feature = "http")]
+fn tls_config(cacert: Option<&Path>, insecure: bool) -> Result<Arc<rustls::ClientConfig>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .context("failed to set up TLS")?;
+    let config = if insecure {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(Unverified(provider)))
+            .with_no_client_auth()
+    } else {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        if let Some(path) = cacert {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let certs = rustls_pemfile::certs(&mut pem.as_slice())
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(|| format!("failed to parse {}", path.display()))?;
+            if certs.is_empty() {
+                bail!("no PEM certificates in {}", path.display());
+            }
+            for cert in certs {
+                roots
+                    .add(cert)
+                    .with_context(|| format!("invalid certificate in {}", path.display()))?;
+            }
+        }
+        builder.with_root_certificates(roots).with_no_client_auth()
+    };
+    Ok(Arc::new(config))
+}
+
+/// Accepts any server certificate, for `--insecure`; signatures are still
+/// checked, so that the handshake completes as usual.
+#[cfg(feature = "http")]
+#[derive(Debug)]
+struct Unverified(Arc<rustls::crypto::CryptoProvider>);
+
+#[cfg(feature = "http")]
+impl rustls::client::danger::ServerCertVerifier for Unverified {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::pki_types::CertificateDer<'_>,
+        _intermediates: &[rustls::pki_types::CertificateDer<'_>],
+        _server_name: &rustls::pki_types::ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: rustls::pki_types::UnixTime,
+    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::danger::ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// The proxy to send requests for `url` through, as curl picks it from the
+/// environment: `https_proxy` or `http_proxy` by the scheme of `url`, else
+/// `all_proxy`, in lower or upper case, unless `no_proxy` lists its host or
+/// a domain of it.
+#[cfg(feature = "http")]
+fn proxy(url: &str) -> Option<String> {
+    let var = |name: &str| {
+        std::env::var(name)
+            .or_else(|_| std::env::var(name.to_uppercase()))
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+    };
+    let url = url::Url::parse(url).ok()?;
+    let host = url
+        .host_str()?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_ascii_lowercase();
+    let bypassed = var("no_proxy").is_some_and(|no_proxy| {
+        no_proxy
+            .split(',')
+            .map(|entry| entry.trim().trim_start_matches('.').to_ascii_lowercase())
+            .any(|domain| {
+                domain == "*"
+                    || (!domain.is_empty()
+                        && (host == domain || host.ends_with(&format!(".{}", domain))))
+            })
+    });
+    if bypassed {
+        return None;
+    }
+    match url.scheme() {
+        "https" => var("https_proxy"),
+        // curl ignores HTTP_PROXY, which CGI programs may be handed by clients
+        _ => std::env::var("http_proxy")
+            .ok()
+            .filter(|value| !value.trim().is_empty()),
+    }
+    .or_else(|| var("all_proxy"))
+}
+
+/// A request to `url`, through the proxy of [`proxy`] and with the TLS
+/// settings of `settings`.
+#[cfg(feature = "http")]
+fn request(settings: &Settings, method: &str, url: &str) -> Result<ureq::Request> {
+    let proxy = proxy(url);
+    let same_tls = |tls: &Option<Arc<rustls::ClientConfig>>| match (tls, &settings.tls) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (a, b) => a.is_none() && b.is_none(),
+    };
+    let mut agents = AGENTS.lock().expect("agents lock poisoned");
+    if let Some((_, _, agent)) = agents
+        .iter()
+        .find(|(used, tls, _)| *used == proxy && same_tls(tls))
+    {
+        return Ok(agent.request(method, url));
+    }
+
+    let mut builder = ureq::AgentBuilder::new();
+    if let Some(proxy) = &proxy {
+        tracing::debug!(url, proxy, "using proxy");
+        let parsed =
+            ureq::Proxy::new(proxy).with_context(|| format!("invalid proxy '{}'", proxy))?;
+        builder = builder.proxy(parsed);
+    }
+    if let Some(tls) = settings.tls.clone() {
+        builder = builder.tls_config(tls);
+    }
+    let agent = builder.build();
+    let request = agent.request(method, url);
+    agents.push((proxy, settings.tls.clone(), agent));
+    Ok(request)
+}
+
+/// GET `url`, pacing requests and retrying those that may succeed later.
+///
+/// Rate-limited requests wait out `Retry-After`; server errors and transport
+/// errors are retried with exponential backoff and jitter, as many times as
+/// [`set_retries`] allows; other non-2xx responses fail right away with an
+/// [`HttpError`].
+#[cfg(feature = "http")]
+fn send(settings: &Settings, url: &str, timeout: u64) -> Result<Body> {
+    Ok(send_with(settings, "GET", url, b"", timeout, || {
+        request(settings, "GET", url)
+    })?
+    .body)
+}
+
+/// Cache key of the ETag of the response cached for `url`.
+#[cfg(feature = "http")]
+fn etag_key(url: &str) -> String {
+    format!("{}#etag", url)
+}
+
+/// GET `url` as [`send`] does, answering from the cache while its entry is
+/// younger than [`cache::ttl`]. Older entries with an ETag are revalidated
+/// with the server, which spares sending them again when unchanged.
+///
+/// Requests are neither answered from nor stored in the cache while
+/// recording or replaying.
+#[cfg(feature = "http")]
+fn send_cached(settings: &Settings, url: &str, timeout: u64) -> Result<Body> {
+    match Cache::open(cache::ttl()) {
+        Ok(cache) => send_cached_in(settings, &cache, url, timeout),
+        Err(_) => send(settings, url, timeout),
+    }
+}
+
+/// GET `url` through `cache`, as the other requests of this module go
+/// through the user's cache: fresh entries are used as they are, and stale
+/// ones are revalidated by their ETag.
+#[cfg(feature = "http")]
+pub fn get_cached(cache: &Cache, url: &str, timeout: u64) -> Result<Vec<u8>> {
+    let _timing = timings::start("request", || url.to_string());
+    let mut body = Vec::new();
+    send_cached_in(&settings(), cache, url, timeout)?
+        .read_to_end(&mut body)
+        .with_context(|| format!("failed to read response from {}", url))?;
+
+    Ok(body)
+}
+
+/// [`send_cached`] with `cache` instead of the user's cache.
+#[cfg(feature = "http")]
+fn send_cached_in(settings: &Settings, cache: &Cache, url: &str, timeout: u64) -> Result<Body> {
+    if recording::mode().is_some() {
+        return send(settings, url, timeout);
+    }
+    if let Some(body) = cache.reader(url) {
+        tracing::debug!(url, "using cached response");
+        return Ok(Body(Box::new(body)));
+    }
+    // offline, an entry however old beats no answer at all
+    if settings.offline {
+        if let Some(body) = cache.stale_reader(url) {
+            tracing::debug!(url, "using expired cached response while offline");
+            return Ok(Body(Box::new(body)));
+        }
+    }
+
+    let stale = cache.stale_reader(url).zip(cache.get_stale(&etag_key(url)));
+    let response = send_with(settings, "GET", url, b"", timeout, || {
+        let request = request(settings, "GET", url)?;
+        Ok(match &stale {
+            Some((_, etag)) => request.set("If-None-Match", &String::from_utf8_lossy(etag)),
+            None => request,
+        })
+    })?;
+    match (response.status, stale) {
+        (304, Some((body, _))) => {
+            tracing::debug!(url, "cached response is still current");
+            if let Err(err) = cache.touch(url) {
+                tracing::debug!(url, "failed to refresh cached response: {:#}", err);
+            }
+            Ok(Body(Box::new(body)))
+        }
+        (304, None) => bail!(
+            "{} responded with HTTP 304 to an unconditional request",
+            url
+        ),
+        (_, _) => {
+            let entry = cache.writer(url).unwrap_or_else(|err| {
+                tracing::debug!(url, "failed to cache response: {:#}", err);
+                None
+            });
+            Ok(Body(Box::new(Tee {
+                url: url.to_string(),
+                body: response.body,
+                entry,
+                etag: response.etag.map(|etag| (cache.clone(), etag)),
+            })))
+        }
+    }
+}
+
+/// A response body stored in the cache as it is read, so that it is never
+/// held in memory whole. The entry is only kept once the body was read to
+/// its end.
+#[cfg(feature = "http")]
+struct Tee {
+    url: String,
+    body: Body,
+    entry: Option<EntryWriter>,
+    etag: Option<(Cache, String)>,
+}
+
+#[cfg(feature = "http")]
+impl Read for Tee {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.body.read(buf)?;
+        let url = self.url.as_str();
+        if read > 0 {
+            if let Some(entry) = &mut self.entry {
+                if let Err(err) = std::io::Write::write_all(entry, &buf[..read]) {
+                    tracing::debug!(url, "failed to cache response: {}", err);
+                    self.entry = None;
+                }
+            }
+        } else if !buf.is_empty() {
+            if let Some(entry) = self.entry.take() {
+                match entry.finish() {
+                    Ok(()) => {
+                        if let Some((cache, etag)) = self.etag.take() {
+                            if let Err(err) = cache.put(&etag_key(url), etag.as_bytes()) {
+                                tracing::debug!(url, "failed to cache ETag: {:#}", err);
+                            }
+                        }
+                    }
+                    Err(err) => tracing::debug!(url, "failed to cache response: {:#}", err),
+                }
+            }
+        }
+
+        Ok(read)
+    }
+}
+
+/// POST `body` as JSON to `url` and parse the JSON response, with the pacing
+/// and retries of GET requests.
+#[cfg(feature = "http")]
+pub fn post_json<B: serde::Serialize, T: DeserializeOwned>(
+    url: &str,
+    body: &B,
+    timeout: u64,
+) -> Result<T> {
+    let _timing = timings::start("request", || format!("POST {}", url));
+    let payload = serde_json::to_vec(body)?;
+    let settings = settings();
+    let response = send_with(&settings, "POST", url, &payload, timeout, || {
+        Ok(request(&settings, "POST", url)?.set("Content-Type", "application/json"))
+    })?;
+    read_json(url, response.body)
+}
+
+/// A successful or `304 Not Modified` response.
+#[cfg(feature = "http")]
+struct Response {
+    status: u16,
+    etag: Option<String>,
+    body: Body,
+}
+
+#[cfg(feature = "http")]
+impl Response {
+    fn new(status: u16, etag: Option<String>, body: Body) -> Self {
+        Response { status, etag, body }
+    }
+
+    fn buffered(body: Vec<u8>) -> Self {
+        Response::new(200, None, Body(Box::new(std::io::Cursor::new(body))))
+    }
+}
+
+/// Send the request built by `request`, anew for each attempt, as [`send`]
+/// does with `settings`, or answer it from the recording being replayed.
+///
+/// `method` and `payload`, the request body, identify the request in
+/// recordings.
+#[cfg(feature = "http")]
+fn send_with<F>(
+    settings: &Settings,
+    method: &str,
+    url: &str,
+    payload: &[u8],
+    timeout: u64,
+    request: F,
+) -> Result<Response>
+where
+    F: Fn() -> Result<ureq::Request>,
+{
+    if let Some(body) = recording::replayed(method, url, payload)? {
+        return Ok(Response::buffered(body));
+    }
+    if settings.offline {
+        bail!("not requesting {} while offline", url);
+    }
+
+    let timeout = settings
+        .timeout
+        .map_or(timeout, |timeout| timeout.as_secs().max(1));
+    let (retries, initial_backoff) = settings.retries;
+    let mut attempt = 0;
+    loop {
+        pace();
+        let timeout = match settings.remaining()? {
+            Some(remaining) => timeout.min(remaining.as_secs().max(1)),
+            None => timeout,
+        };
+        let backoff = jitter(initial_backoff.saturating_mul(1 << attempt.min(16)));
+        let mut request = request()?;
+        if let Some(authorization) = settings.authorization(url) {
+            request = request.set("Authorization", &authorization);
+        }
+        for (name, value) in settings.headers(url) {
+            request = request.set(&name, &value);
+        }
+        let request = request
+            .set("User-Agent", USER_AGENT)
+            .set("Accept-Encoding", ACCEPT_ENCODING)
+            .timeout(Duration::from_secs(timeout));
+        let sent = if payload.is_empty() {
+            request.call()
+        } else {
+            request.send_bytes(payload)
+        };
+        let response = match sent {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(err @ ureq::Error::Transport(_)) if is_transient(&err) && attempt < retries => {
+                tracing::warn!(
+                    url,
+                    "request failed ({}), retrying in {}",
+                    err,
+                    humantime::format_duration(backoff)
+                );
+                settings.wait(backoff)?;
+                attempt += 1;
+                continue;
+            }
+            Err(err) => {
+                settings.remaining()?;
+                let hint = if err.to_string().contains("invalid peer certificate") {
+                    ", whose certificate is not trusted; pass its CA's with --cacert"
+                } else {
+                    ""
+                };
+                return Err(
+                    anyhow::Error::new(err).context(format!("failed to reach {}{}", url, hint))
+                );
+            }
+        };
+
+        let status = response.status();
+        let etag = response
+            .header("etag")
+            .map(|value| value.trim().to_string());
+        let delay = match status {
+            200..=299 if recording::mode() == Some(recording::Mode::Record) => {
+                let mut body = Vec::new();
+                Body::new(url, response)?
+                    .read_to_end(&mut body)
+                    .with_context(|| format!("failed to read response from {}", url))?;
+                recording::save(method, url, payload, &body)?;
+                return Ok(Response::buffered(body));
+            }
+            200..=299 => return Ok(Response::new(status, etag, Body::new(url, response)?)),
+            304 => {
+                let body = Body(Box::new(std::io::empty()));
+                return Ok(Response::new(status, etag, body));
+            }
+            429 => {
+                let delay = retry_after(response.header("retry-after")).unwrap_or(backoff);
+                if attempt == retries || delay > MAX_RETRY_AFTER {
+                    bail!(
+                        "rate limited by the server (HTTP 429, retry after {}); lower --max-rps or try again later",
+                        humantime::format_duration(delay)
+                    );
+                }
+                delay
+            }
+            500..=599 if attempt < retries => backoff,
+            _ => {
+                let reason = response.status_text().to_string();
+                let mut start = Vec::new();
+                if let Ok(body) = Body::new(url, response) {
+                    let _ = body.take(EXCERPT_LENGTH as u64 * 4).read_to_end(&mut start);
+                }
+                let err = anyhow::Error::new(HttpError::new(url, status, &reason, &start));
+                return Err(match status {
+                    401 if settings.authorization(url).is_some() => {
+                        err.context("authentication failed, check the --token or --user given")
+                    }
+                    401 => err.context("the server requires authentication, pass --token or --user"),
+                    403 => err.context("access denied, check that the --token or --user given may read from the server"),
+                    _ => err,
+                });
+            }
+        };
+        tracing::warn!(
+            url,
+            "server responded with HTTP {} {}, retrying in {}",
+            status,
+            response.status_text(),
+            humantime::format_duration(delay)
+        );
+        settings.wait(delay)?;
+        attempt += 1;
+    }
+}
+
+/// Content codings the server may compress responses with.
+#[cfg(feature = "http")]
+const ACCEPT_ENCODING: &str = "gzip, deflate";
+
+/// Size of the buffer responses are read through.
+#[cfg(feature = "http")]
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// A response body, decompressed and read as it is received.
+#[cfg(feature = "http")]
+struct Body(Box<dyn Read + Send>);
+
+#[cfg(feature = "http")]
+impl Body {
+    fn new(url: &str, response: ureq::Response) -> Result<Self> {
+        let encoding = response
+            .header("content-encoding")
+            .map(|value| value.trim().to_ascii_lowercase());
+        let raw = std::io::BufReader::with_capacity(BUFFER_SIZE, response.into_reader());
+        let body: Box<dyn Read + Send> = match encoding.as_deref() {
+            None | Some("") | Some("identity") => Box::new(raw),
+            Some("gzip") | Some("x-gzip") => Box::new(std::io::BufReader::with_capacity(
+                BUFFER_SIZE,
+                flate2::bufread::MultiGzDecoder::new(raw),
+            )),
+            Some("deflate") => Box::new(std::io::BufReader::with_capacity(
+                BUFFER_SIZE,
+                flate2::bufread::ZlibDecoder::new(raw),
+            )),
+            Some(other) => bail!(
+                "{} responded with unsupported Content-Encoding '{}'",
+                url,
+                other
+            ),
+        };
+        Ok(Body(body))
+    }
+}
+
+#[cfg(feature = "http")]
+impl Read for Body {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+/// Delay requested by a `Retry-After` header, in seconds or as an HTTP date.
+#[cfg(feature = "http")]
+fn retry_after(value: Option<&str>) -> Option<Duration> {
+    let value = value?.trim();
+
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let date = httpdate::parse_http_date(value).ok()?;
+            Some(
+                date.duration_since(SystemTime::now())
+                    .unwrap_or(Duration::ZERO),
+            )
+        }
+    }
+}
+
+/// Whether `err` was caused by failing to connect to the server at all.
+#[cfg(feature = "http")]
+pub fn is_unreachable(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|cause| matches!(cause.downcast_ref::<ureq::Error>(), Some(err) if is_transient(err)))
+}
+
+/// Whether `err` is a failure to connect, or an interrupted connection,
+/// which may not recur.
+#[cfg(feature = "http")]
+fn is_transient(err: &ureq::Error) -> bool {
+    match err {
+        ureq::Error::Transport(transport) => matches!(
+            transport.kind(),
+            ureq::ErrorKind::Dns
+                | ureq::ErrorKind::ConnectionFailed
+                | ureq::ErrorKind::Io
+                | ureq::ErrorKind::ProxyConnect
+        ),
+        ureq::Error::Status(..) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_server_adds_https_and_strips_api_path() {
+        assert_eq!(
+            normalize_server("packagemanager.posit.co").unwrap(),
+            "https://packagemanager.posit.co"
+        );
+        assert_eq!(
+            normalize_server(" http://ppm.local:4242/__api__/ ").unwrap(),
+            "http://ppm.local:4242"
+        );
+        assert_eq!(
+            normalize_server("https://example.com/rspm/__api__/__api__").unwrap(),
+            "https://example.com/rspm"
+        );
+    }
+
+    #[test]
+    fn normalize_server_rejects_invalid_urls() {
+        for server in [
+            "",
+            "ftp://example.com",
+            "https://example.com/?q=1",
+            "https://example.com/#top",
+            "https://",
+        ] {
+            assert!(normalize_server(server).is_err(), "{}", server);
+        }
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn retry_after_reads_seconds_and_dates() {
+        assert_eq!(retry_after(None), None);
+        assert_eq!(retry_after(Some(" 30 ")), Some(Duration::from_secs(30)));
+        assert_eq!(
+            retry_after(Some("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Some(Duration::ZERO)
+        );
+        let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(120));
+        let delay = retry_after(Some(&later)).unwrap();
+        assert!(delay > Duration::from_secs(100) && delay <= Duration::from_secs(120));
+        assert_eq!(retry_after(Some("soon")), None);
+    }
+}
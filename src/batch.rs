@@ -0,0 +1,160 @@
+//! System requirements of large package sets, queried in chunks whose results
+//! are saved as they complete so that a failed run can resume.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::models::{APIRequirement, APISysReqs};
+use crate::{api, cache, files, jobs, paths, timings};
+
+/// Responses of the chunks completed so far, by chunk index.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Progress {
+    chunks: BTreeMap<usize, Vec<APIRequirement>>,
+}
+
+/// A query of the system requirements of `packages`, `chunk_size` at a time.
+pub struct Batch<'a> {
+    pub server: &'a str,
+    pub distribution: &'a str,
+    pub release: &'a str,
+    pub repo_id: u64,
+    pub chunk_size: usize,
+    pub settings: &'a api::Settings,
+    /// Whether completed chunks are saved for a rerun to resume.
+    pub persist: bool,
+}
+
+impl Batch<'_> {
+    /// Query all chunks, up to [`jobs::jobs`] at once, skipping those
+    /// completed by a previous run when `resume` is set. Progress is logged
+    /// as chunks complete, and, when persisted, discarded once every chunk
+    /// is done.
+    pub fn sysreqs(&self, packages: &[String], resume: bool) -> Result<APISysReqs> {
+        let chunks: Vec<_> = packages.chunks(self.chunk_size.max(1)).collect();
+        if chunks.len() <= 1 && !resume {
+            return api::sysreqs_with(
+                self.settings,
+                self.server,
+                self.distribution,
+                self.release,
+                self.repo_id,
+                packages,
+            );
+        }
+
+        let path = match self.persist {
+            true => Some(self.progress_path(packages)?),
+            false => None,
+        };
+        let progress = match &path {
+            Some(path) if resume => load(path)?,
+            _ => Progress::default(),
+        };
+        if let Some(path) = path.as_ref().filter(|_| !progress.chunks.is_empty()) {
+            info!(
+                "resuming from {}: {} of {} chunks already done",
+                path.display(),
+                progress.chunks.len(),
+                chunks.len()
+            );
+        }
+
+        let pending: Vec<(usize, &[String])> = chunks
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(index, _)| !progress.chunks.contains_key(index))
+            .collect();
+        let progress = Mutex::new(progress);
+        jobs::try_map(&pending, |_, (index, chunk)| {
+            let index = *index;
+            let _timing = timings::start("chunk", || {
+                format!(
+                    "{} of {}, {} packages",
+                    index + 1,
+                    chunks.len(),
+                    chunk.len()
+                )
+            });
+            let response = api::sysreqs_with(
+                self.settings,
+                self.server,
+                self.distribution,
+                self.release,
+                self.repo_id,
+                chunk,
+            )
+            .with_context(|| match path {
+                Some(_) => format!(
+                    "chunk {} of {} failed, rerun with --resume to continue",
+                    index + 1,
+                    chunks.len()
+                ),
+                None => format!("chunk {} of {} failed", index + 1, chunks.len()),
+            })?;
+            let mut progress = progress.lock().expect("progress lock poisoned");
+            progress.chunks.insert(index, response.requirements);
+            info!(
+                "system requirements of {} of {} chunks received",
+                progress.chunks.len(),
+                chunks.len()
+            );
+            match &path {
+                Some(path) => save(path, &progress),
+                None => Ok(()),
+            }
+        })?;
+
+        if let Some(path) = &path {
+            let _ = std::fs::remove_file(path);
+        }
+        let progress = progress.into_inner().expect("progress lock poisoned");
+        Ok(APISysReqs {
+            requirements: progress.chunks.into_values().flatten().collect(),
+        })
+    }
+
+    /// Progress is kept per query, so that only an identical rerun resumes it.
+    fn progress_path(&self, packages: &[String]) -> Result<PathBuf> {
+        let dir = paths::cache_dir().ok_or_else(|| anyhow!("failed to locate cache directory"))?;
+        let query = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            self.server,
+            self.repo_id,
+            self.distribution,
+            self.release,
+            self.chunk_size,
+            packages.join("\n")
+        );
+        let key = format!("{:x}", Sha256::digest(query.as_bytes()));
+
+        Ok(dir
+            .join(cache::PROGRESS_DIR)
+            .join(key)
+            .with_extension("json"))
+    }
+}
+
+fn load(path: &Path) -> Result<Progress> {
+    match std::fs::read(path) {
+        Ok(contents) => serde_json::from_slice(&contents)
+            .with_context(|| format!("failed to parse {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Progress::default()),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+fn save(path: &Path, progress: &Progress) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    files::write_atomic(path, &serde_json::to_vec(progress)?)
+}
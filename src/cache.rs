@@ -0,0 +1,593 @@
+//! On-disk cache of API responses, keyed by request URL, each stored with
+//! its checksum.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::files::{temp_path, write_atomic};
+use crate::paths;
+
+/// Directory of the resume progress of batched queries, next to the servers.
+pub const PROGRESS_DIR: &str = "progress";
+
+/// Usage of the cache by one server, or by resume progress.
+#[derive(Debug, Clone)]
+pub struct Usage {
+    pub name: String,
+    pub entries: usize,
+    pub bytes: u64,
+    pub oldest: Option<SystemTime>,
+    pub newest: Option<SystemTime>,
+}
+
+/// How long API responses are used without asking the server, by default.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How cached entries are used, for `--no-cache` and `--refresh`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Use entries younger than the TTL.
+    Use,
+    /// Fetch anew, revalidating entries with an ETag, and store the results.
+    Refresh,
+    /// Neither read nor store entries.
+    Bypass,
+}
+
+static POLICY: Mutex<Policy> = Mutex::new(Policy::Use);
+
+static TTL: Mutex<Duration> = Mutex::new(DEFAULT_TTL);
+
+/// Set how cached entries are used from now on.
+pub fn set_policy(policy: Policy) {
+    *POLICY.lock().unwrap() = policy;
+}
+
+/// How cached entries are used.
+pub fn policy() -> Policy {
+    *POLICY.lock().unwrap()
+}
+
+/// Set how long API responses are used without asking the server.
+pub fn set_ttl(ttl: Duration) {
+    *TTL.lock().unwrap() = ttl;
+}
+
+/// How long API responses are used without asking the server.
+pub fn ttl() -> Duration {
+    *TTL.lock().unwrap()
+}
+
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Cache {
+        Cache { dir, ttl }
+    }
+
+    /// Cache in the per-user cache directory.
+    pub fn open(ttl: Duration) -> Result<Cache> {
+        let dir = paths::cache_dir().ok_or_else(|| anyhow!("failed to locate cache directory"))?;
+        Ok(Cache::new(dir, ttl))
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// Entries are grouped by server host so they can be inspected per server.
+    fn path(&self, url: &str) -> PathBuf {
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|u| {
+                u.host_str().map(|host| match u.port() {
+                    Some(port) => format!("{}_{}", host, port),
+                    None => host.to_string(),
+                })
+            })
+            .unwrap_or_else(|| String::from("_"));
+        let key = checksum(url.as_bytes());
+        self.dir.join(host).join(key)
+    }
+
+    /// Lock the whole cache, shared for readers and exclusive for writers, so
+    /// that processes sharing it never see each other's partial changes.
+    ///
+    /// The lock is released when the returned file is dropped.
+    fn lock(&self, exclusive: bool) -> Result<File> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        let path = self.dir.join(".lock");
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        if exclusive {
+            file.lock()
+        } else {
+            file.lock_shared()
+        }
+        .with_context(|| format!("failed to lock {}", path.display()))?;
+
+        Ok(file)
+    }
+
+    /// The cached body for `url`, unless missing or older than the TTL, or
+    /// the [`policy`] is not to use entries.
+    ///
+    /// Entries failing their checksum are removed, so that they are fetched
+    /// again.
+    pub fn get(&self, url: &str) -> Option<Vec<u8>> {
+        read_all(self.reader(url)?)
+    }
+
+    /// The cached body for `url` however old, to revalidate with the server,
+    /// unless the [`policy`] is to bypass the cache.
+    pub fn get_stale(&self, url: &str) -> Option<Vec<u8>> {
+        read_all(self.stale_reader(url)?)
+    }
+
+    /// A reader of the body [`Cache::get`] returns, which is not held in
+    /// memory.
+    pub fn reader(&self, url: &str) -> Option<BufReader<File>> {
+        if policy() != Policy::Use {
+            return None;
+        }
+        self.open_entry(url, Some(self.ttl))
+    }
+
+    /// A reader of the body [`Cache::get_stale`] returns.
+    pub fn stale_reader(&self, url: &str) -> Option<BufReader<File>> {
+        if policy() == Policy::Bypass {
+            return None;
+        }
+        self.open_entry(url, None)
+    }
+
+    /// The entry for `url`, positioned at its body once checked against its
+    /// checksum.
+    fn open_entry(&self, url: &str, ttl: Option<Duration>) -> Option<BufReader<File>> {
+        let path = self.path(url);
+        let mut entry = {
+            let _lock = self.lock(false).ok()?;
+            let file = File::open(&path).ok()?;
+            let modified = file.metadata().and_then(|m| m.modified()).ok()?;
+            let age = SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default();
+            if ttl.is_some_and(|ttl| age > ttl) {
+                return None;
+            }
+            // entries are replaced by renaming, so the open file stays whole
+            BufReader::new(file)
+        };
+
+        match verify(&mut entry) {
+            Ok(Some(start)) => {
+                entry.seek(SeekFrom::Start(start)).ok()?;
+                Some(entry)
+            }
+            Ok(None) => {
+                tracing::warn!(
+                    "removing corrupted cache entry {} for {}",
+                    path.display(),
+                    url
+                );
+                if let Ok(_lock) = self.lock(true) {
+                    let _ = std::fs::remove_file(&path);
+                }
+                None
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// Mark the entry for `url` as fresh, as the server confirmed it current.
+    pub fn touch(&self, url: &str) -> Result<()> {
+        let path = self.path(url);
+        let _lock = self.lock(true)?;
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+            .with_context(|| format!("failed to update {}", path.display()))
+    }
+
+    /// Usage by server host, with the progress of batched queries under
+    /// `progress`.
+    pub fn usage(&self) -> Result<Vec<Usage>> {
+        let _lock = self.lock(false)?;
+        let mut usage = Vec::new();
+        for (name, dir) in self.subdirs()? {
+            let mut host = Usage {
+                name,
+                entries: 0,
+                bytes: 0,
+                oldest: None,
+                newest: None,
+            };
+            for (_, metadata) in entries(&dir)? {
+                host.entries += 1;
+                host.bytes += metadata.len();
+                if let Ok(modified) = metadata.modified() {
+                    host.oldest = Some(host.oldest.map_or(modified, |oldest| oldest.min(modified)));
+                    host.newest = Some(host.newest.map_or(modified, |newest| newest.max(modified)));
+                }
+            }
+            usage.push(host);
+        }
+
+        Ok(usage)
+    }
+
+    /// Remove all entries, returning how many and their size.
+    pub fn clear(&self) -> Result<(usize, u64)> {
+        self.remove(|_| true)
+    }
+
+    /// Remove entries not modified within `max_age`, and temporary files left
+    /// behind by interrupted writes.
+    pub fn prune(&self, max_age: Duration) -> Result<(usize, u64)> {
+        let now = SystemTime::now();
+        self.remove(|path_and_metadata| {
+            let (path, metadata) = path_and_metadata;
+            let age = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+            age > max_age || path.extension().is_some_and(|extension| extension == "tmp")
+        })
+    }
+
+    fn remove<F>(&self, filter: F) -> Result<(usize, u64)>
+    where
+        F: Fn(&(PathBuf, std::fs::Metadata)) -> bool,
+    {
+        let _lock = self.lock(true)?;
+        let (mut count, mut bytes) = (0, 0);
+        for (_, dir) in self.subdirs()? {
+            for entry in entries(&dir)?.into_iter().filter(&filter) {
+                std::fs::remove_file(&entry.0)
+                    .with_context(|| format!("failed to remove {}", entry.0.display()))?;
+                count += 1;
+                bytes += entry.1.len();
+            }
+            // only succeeds once empty
+            let _ = std::fs::remove_dir(&dir);
+        }
+
+        Ok((count, bytes))
+    }
+
+    /// Directories of server hosts and of progress, by name.
+    fn subdirs(&self) -> Result<Vec<(String, PathBuf)>> {
+        let mut dirs = Vec::new();
+        for (path, metadata) in entries_of(&self.dir, true)? {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if metadata.is_dir() && !name.starts_with('.') {
+                dirs.push((name, path));
+            }
+        }
+        dirs.sort();
+
+        Ok(dirs)
+    }
+
+    /// Store `body` for `url`, replacing any previous entry atomically,
+    /// unless the [`policy`] is to bypass the cache.
+    pub fn put(&self, url: &str, body: &[u8]) -> Result<()> {
+        if policy() == Policy::Bypass {
+            return Ok(());
+        }
+        let _lock = self.lock(true)?;
+        let path = self.path(url);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        write_atomic(&path, &seal(body))
+    }
+
+    /// A writer of the entry for `url`, stored by [`EntryWriter::finish`],
+    /// unless the [`policy`] is to bypass the cache.
+    pub fn writer(&self, url: &str) -> Result<Option<EntryWriter>> {
+        if policy() == Policy::Bypass {
+            return Ok(None);
+        }
+        let path = self.path(url);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let temp = temp_path(&path);
+        let mut file = File::create(&temp)
+            .map(BufWriter::new)
+            .with_context(|| format!("failed to create {}", temp.display()))?;
+        // the checksum is filled in once the body is complete
+        file.write_all(&header(&[b'0'; 64]))
+            .with_context(|| format!("failed to write {}", temp.display()))?;
+
+        Ok(Some(EntryWriter {
+            cache: self.clone(),
+            path,
+            temp,
+            file: Some(file),
+            hasher: Sha256::new(),
+        }))
+    }
+}
+
+/// A cache entry written as its body arrives, so that it is not held in
+/// memory. It is discarded unless [`EntryWriter::finish`] is called.
+pub struct EntryWriter {
+    cache: Cache,
+    path: PathBuf,
+    temp: PathBuf,
+    file: Option<BufWriter<File>>,
+    hasher: Sha256,
+}
+
+impl EntryWriter {
+    /// Seal the entry with the checksum of its body and replace any previous
+    /// entry with it atomically.
+    pub fn finish(mut self) -> Result<()> {
+        let file = self.file.take().expect("entry already finished");
+        let sum = format!("{:x}", self.hasher.clone().finalize());
+        let result = file
+            .into_inner()
+            .map_err(|err| err.into_error())
+            .and_then(|mut file| {
+                file.seek(SeekFrom::Start(0))?;
+                file.write_all(&header(sum.as_bytes()))?;
+                file.sync_all()
+            })
+            .with_context(|| format!("failed to write {}", self.temp.display()));
+        let result = result.and_then(|_| {
+            let _lock = self.cache.lock(true)?;
+            std::fs::rename(&self.temp, &self.path)
+                .with_context(|| format!("failed to write {}", self.path.display()))
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+        result
+    }
+}
+
+impl Write for EntryWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let file = self.file.as_mut().expect("entry already finished");
+        let written = file.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.as_mut().map_or(Ok(()), |file| file.flush())
+    }
+}
+
+impl Drop for EntryWriter {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// Start of the checksum line preceding the contents of sealed files.
+const CHECKSUM_PREFIX: &[u8] = b"sha256:";
+
+/// The SHA-256 of `contents`, in hex.
+pub fn checksum(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+/// The line preceding a body with the checksum `sum`.
+fn header(sum: &[u8]) -> Vec<u8> {
+    let mut header = CHECKSUM_PREFIX.to_vec();
+    header.extend_from_slice(sum);
+    header.push(b'\n');
+    header
+}
+
+/// `body` preceded by a line with its checksum, for [`unseal`].
+pub fn seal(body: &[u8]) -> Vec<u8> {
+    let mut sealed = header(checksum(body).as_bytes());
+    sealed.extend_from_slice(body);
+    sealed
+}
+
+/// The body of `sealed` contents, if it matches its checksum.
+pub fn unseal(sealed: &[u8]) -> Option<&[u8]> {
+    let rest = sealed.strip_prefix(CHECKSUM_PREFIX)?;
+    let newline = rest.iter().position(|byte| *byte == b'\n')?;
+    let (sum, body) = (&rest[..newline], &rest[newline + 1..]);
+    if sum == checksum(body).as_bytes() {
+        Some(body)
+    } else {
+        None
+    }
+}
+
+/// Check sealed contents against their checksum as [`unseal`] does, but
+/// reading them through, returning where the body starts if they match.
+fn verify<R: BufRead>(sealed: &mut R) -> std::io::Result<Option<u64>> {
+    let mut line = Vec::new();
+    sealed.read_until(b'\n', &mut line)?;
+    let sum = match line
+        .strip_prefix(CHECKSUM_PREFIX)
+        .and_then(|rest| rest.strip_suffix(b"\n"))
+    {
+        Some(sum) => sum.to_vec(),
+        None => return Ok(None),
+    };
+    let mut hasher = Sha256::new();
+    std::io::copy(sealed, &mut hasher)?;
+    if sum == format!("{:x}", hasher.finalize()).as_bytes() {
+        Ok(Some(line.len() as u64))
+    } else {
+        Ok(None)
+    }
+}
+
+fn read_all(mut reader: impl Read) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    reader.read_to_end(&mut body).ok()?;
+    Some(body)
+}
+
+/// Files of a directory, with their metadata.
+fn entries(dir: &Path) -> Result<Vec<(PathBuf, std::fs::Metadata)>> {
+    Ok(entries_of(dir, false)?
+        .into_iter()
+        .filter(|(_, metadata)| metadata.is_file())
+        .collect())
+}
+
+fn entries_of(dir: &Path, missing_ok: bool) -> Result<Vec<(PathBuf, std::fs::Metadata)>> {
+    let read = match std::fs::read_dir(dir) {
+        Ok(read) => read,
+        Err(err) if missing_ok && err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Vec::new())
+        }
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", dir.display())),
+    };
+    let mut entries = Vec::new();
+    for entry in read {
+        let entry = entry.with_context(|| format!("failed to read {}", dir.display()))?;
+        let metadata = entry
+            .metadata()
+            .with_context(|| format!("failed to read {}", entry.path().display()))?;
+        entries.push((entry.path(), metadata));
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://example.com/__api__/status";
+
+    /// A cache in a directory of its own, removed beforehand.
+    fn cache(name: &str) -> Cache {
+        let dir =
+            std::env::temp_dir().join(format!("r-sysdeps-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        Cache::new(dir, DEFAULT_TTL)
+    }
+
+    #[test]
+    fn unseal_returns_sealed_body() {
+        let sealed = seal(b"{\"version\": \"2024.11.0\"}\n");
+        assert!(sealed.starts_with(CHECKSUM_PREFIX));
+        assert_eq!(
+            unseal(&sealed),
+            Some(&b"{\"version\": \"2024.11.0\"}\n"[..])
+        );
+        assert_eq!(unseal(&seal(b"")), Some(&b""[..]));
+    }
+
+    #[test]
+    fn unseal_rejects_damaged_contents() {
+        let mut sealed = seal(b"body");
+        *sealed.last_mut().unwrap() = b'x';
+        assert_eq!(unseal(&sealed), None);
+        assert_eq!(unseal(&sealed[..sealed.len() - 1]), None);
+        assert_eq!(unseal(b"body"), None);
+    }
+
+    #[test]
+    fn verify_agrees_with_unseal() {
+        let sealed = seal(b"body");
+        let start = verify(&mut &sealed[..]).unwrap();
+        assert_eq!(start, Some((sealed.len() - b"body".len()) as u64));
+
+        let mut damaged = sealed.clone();
+        damaged.push(b'!');
+        assert_eq!(verify(&mut &damaged[..]).unwrap(), None);
+    }
+
+    #[test]
+    fn put_then_get() {
+        let cache = cache("put");
+        assert_eq!(cache.get(URL), None);
+        cache.put(URL, b"body").unwrap();
+        assert_eq!(cache.get(URL).as_deref(), Some(&b"body"[..]));
+        let _ = std::fs::remove_dir_all(cache.dir());
+    }
+
+    #[test]
+    fn writer_stores_entry_once_finished() {
+        let cache = cache("writer");
+        let mut entry = cache.writer(URL).unwrap().unwrap();
+        entry.write_all(b"streamed ").unwrap();
+        entry.write_all(b"body").unwrap();
+        assert_eq!(cache.get(URL), None);
+        entry.finish().unwrap();
+        assert_eq!(cache.get(URL).as_deref(), Some(&b"streamed body"[..]));
+
+        let mut body = String::new();
+        cache
+            .reader(URL)
+            .unwrap()
+            .read_to_string(&mut body)
+            .unwrap();
+        assert_eq!(body, "streamed body");
+        let _ = std::fs::remove_dir_all(cache.dir());
+    }
+
+    #[test]
+    fn dropped_writer_leaves_nothing() {
+        let cache = cache("dropped");
+        let mut entry = cache.writer(URL).unwrap().unwrap();
+        entry.write_all(b"partial").unwrap();
+        drop(entry);
+        assert_eq!(cache.get(URL), None);
+        let dir = cache.path(URL).parent().unwrap().to_path_buf();
+        assert_eq!(std::fs::read_dir(dir).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(cache.dir());
+    }
+
+    #[test]
+    fn corrupted_entry_is_removed() {
+        let cache = cache("corrupted");
+        cache.put(URL, b"body").unwrap();
+        let path = cache.path(URL);
+        let mut contents = std::fs::read(&path).unwrap();
+        *contents.last_mut().unwrap() = b'x';
+        std::fs::write(&path, contents).unwrap();
+
+        assert_eq!(cache.get_stale(URL), None);
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(cache.dir());
+    }
+
+    #[test]
+    fn expired_entry_is_stale() {
+        let cache = Cache::new(cache("expired").dir().clone(), Duration::ZERO);
+        cache.put(URL, b"body").unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(cache.get(URL), None);
+        assert_eq!(cache.get_stale(URL).as_deref(), Some(&b"body"[..]));
+        let _ = std::fs::remove_dir_all(cache.dir());
+    }
+}
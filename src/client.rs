@@ -0,0 +1,229 @@
+//! A client of a Package Manager server, for Rust tools resolving system
+//! requirements without running the CLI.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use r_sysdeps::client::Client;
+//!
+//! let client = Client::new("packagemanager.posit.co")?.with_retries(5, Duration::from_secs(2));
+//! let repository = client.repository(client.status()?.cran_repo(), None)?;
+//! let sysreqs = client.sysreqs(&repository, "ubuntu", "22.04", &["curl".to_string()])?;
+//! # Ok::<(), r_sysdeps::client::Error>(())
+//! ```
+//!
+//! Each client keeps its own credentials, headers, retries, timeouts and TLS
+//! settings, unaffected by those of the CLI or of other clients.
+
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::api::{self, HttpError};
+use crate::batch::Batch;
+use crate::models::{APIRepository, APIStatusResponse, APISysReqs};
+use crate::{os, repository};
+
+/// Packages queried per request by [`Client::sysreqs`], and by the command
+/// line unless given `--chunk-size`.
+pub const CHUNK_SIZE: usize = 100;
+
+/// Errors of [`Client`] calls.
+#[derive(Debug)]
+pub enum Error {
+    /// The server URL is invalid.
+    InvalidServer(anyhow::Error),
+    /// The server could not be connected to.
+    Unreachable(anyhow::Error),
+    /// The server requires credentials, or refused those given.
+    Unauthorized(anyhow::Error),
+    /// The server responded with another non-2xx status.
+    Http(HttpError),
+    /// The repository does not exist, or its name is ambiguous.
+    Repository(anyhow::Error),
+    /// The OS could not be detected.
+    Os(anyhow::Error),
+    /// Anything else, such as an invalid package name or response.
+    Other(anyhow::Error),
+}
+
+impl Error {
+    /// Classify an error of the underlying API calls.
+    fn from_api(err: anyhow::Error) -> Self {
+        if let Some(http) = err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<HttpError>())
+        {
+            if matches!(http.status, 401 | 403) {
+                return Error::Unauthorized(err);
+            }
+            return Error::Http(http.clone());
+        }
+        if api::is_unreachable(&err) {
+            return Error::Unreachable(err);
+        }
+        Error::Other(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(err) => write!(f, "{}", err),
+            Error::InvalidServer(err)
+            | Error::Unreachable(err)
+            | Error::Unauthorized(err)
+            | Error::Repository(err)
+            | Error::Os(err)
+            | Error::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+// the causes of the underlying error are kept, for callers walking the chain
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(_) => None,
+            Error::InvalidServer(err)
+            | Error::Unreachable(err)
+            | Error::Unauthorized(err)
+            | Error::Repository(err)
+            | Error::Os(err)
+            | Error::Other(err) => err.source(),
+        }
+    }
+}
+
+/// A Package Manager server.
+#[derive(Debug, Clone)]
+pub struct Client {
+    server: String,
+    settings: api::Settings,
+}
+
+impl Client {
+    /// A client of `server`, normalized as [`api::normalize_server`] does,
+    /// with the default [`api::Settings`].
+    pub fn new(server: &str) -> Result<Client, Error> {
+        let server = api::normalize_server(server).map_err(Error::InvalidServer)?;
+        Ok(Client {
+            server,
+            settings: api::Settings::new(),
+        })
+    }
+
+    /// This client with `settings` instead of its own.
+    pub fn with_settings(mut self, settings: api::Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// This client authenticating with an API token.
+    pub fn with_token(mut self, token: &str) -> Result<Self, Error> {
+        self.settings
+            .set_bearer_token(&self.server, token)
+            .map_err(Error::InvalidServer)?;
+        Ok(self)
+    }
+
+    /// This client authenticating with HTTP Basic authentication.
+    pub fn with_basic_auth(mut self, user: &str, password: &str) -> Result<Self, Error> {
+        self.settings
+            .set_basic_auth(&self.server, user, password)
+            .map_err(Error::InvalidServer)?;
+        Ok(self)
+    }
+
+    /// This client sending a header with each request.
+    pub fn with_header(mut self, name: &str, value: &str) -> Result<Self, Error> {
+        self.settings
+            .add_header(&self.server, name, value)
+            .map_err(Error::InvalidServer)?;
+        Ok(self)
+    }
+
+    /// This client retrying requests up to `retries` times, waiting
+    /// `backoff` before the first retry and twice as long before each next.
+    pub fn with_retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.settings.set_retries(retries, backoff);
+        self
+    }
+
+    /// This client timing out each request attempt after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.set_timeout(timeout);
+        self
+    }
+
+    /// This client verifying the server against the certificates of the
+    /// PEM file `cacert` too, or not at all when `insecure`.
+    pub fn with_tls(mut self, cacert: Option<&Path>, insecure: bool) -> Result<Self, Error> {
+        self.settings
+            .set_tls(cacert, insecure)
+            .map_err(Error::Other)?;
+        Ok(self)
+    }
+
+    /// This client failing its requests once `budget` has passed from now.
+    pub fn with_deadline(mut self, budget: Duration) -> Self {
+        self.settings.set_deadline(budget);
+        self
+    }
+
+    /// This client answering from cached responses only.
+    pub fn offline(mut self) -> Self {
+        self.settings.set_offline();
+        self
+    }
+
+    /// The normalized server URL.
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    /// The server status.
+    pub fn status(&self) -> Result<APIStatusResponse, Error> {
+        api::status_with(&self.settings, &self.server).map_err(Error::from_api)
+    }
+
+    /// The repositories of the server.
+    pub fn repositories(&self) -> Result<Vec<APIRepository>, Error> {
+        api::repositories_with(&self.settings, &self.server).map_err(Error::from_api)
+    }
+
+    /// The repository named `name`, of type `kind` or else of an R type.
+    pub fn repository(&self, name: &str, kind: Option<&str>) -> Result<APIRepository, Error> {
+        let repositories = self.repositories()?;
+        repository::find(&repositories, name, kind)
+            .cloned()
+            .map_err(Error::Repository)
+    }
+
+    /// The system requirements of `packages` in `repository` on a
+    /// distribution release, [`CHUNK_SIZE`] packages per request. Unlike
+    /// the CLI, no progress is saved for a failed query to resume.
+    pub fn sysreqs(
+        &self,
+        repository: &APIRepository,
+        distribution: &str,
+        release: &str,
+        packages: &[String],
+    ) -> Result<APISysReqs, Error> {
+        let batch = Batch {
+            server: &self.server,
+            distribution,
+            release,
+            repo_id: repository.id,
+            chunk_size: CHUNK_SIZE,
+            settings: &self.settings,
+            persist: false,
+        };
+        batch.sysreqs(packages, false).map_err(Error::from_api)
+    }
+
+    /// The distribution and release of the running OS.
+    pub fn detect_os() -> Result<(String, String), Error> {
+        os::detect(None, None).map_err(Error::Os)
+    }
+}
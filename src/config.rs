@@ -0,0 +1,340 @@
+//! The configuration file, holding defaults for the global options so that
+//! they need not be passed on every invocation.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use toml_edit::{DocumentMut, Item, Value};
+
+use crate::{api, files, paths, repository, suggest};
+
+/// Name of the configuration file in the configuration directory.
+pub const FILE: &str = "config.toml";
+
+/// Configuration file of a project, in its directory, whose settings take
+/// precedence over those of the user's.
+pub const PROJECT_FILE: &str = "r-sysdeps.toml";
+
+/// A setting of the configuration file, named after its command-line option.
+#[derive(Debug)]
+pub struct Key {
+    pub name: &'static str,
+    pub description: &'static str,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Text,
+    Server,
+    Snapshot,
+    Rate,
+    /// A whole number of at least the one given.
+    Count(i64),
+    Duration,
+    Choice(&'static [&'static str]),
+}
+
+/// The settings the configuration file may hold.
+pub const KEYS: &[Key] = &[
+    Key {
+        name: "server",
+        description: "Package Manager server URL",
+        kind: Kind::Server,
+    },
+    Key {
+        name: "token",
+        description: "API token to authenticate to the server with",
+        kind: Kind::Text,
+    },
+    Key {
+        name: "cacert",
+        description: "PEM file of certificates to trust in addition to the bundled ones",
+        kind: Kind::Text,
+    },
+    Key {
+        name: "repository",
+        description: "Repository name",
+        kind: Kind::Text,
+    },
+    Key {
+        name: "repository-type",
+        description: "Repository type, e.g. R or Bioconductor",
+        kind: Kind::Text,
+    },
+    Key {
+        name: "os-name",
+        description: "Operating System name",
+        kind: Kind::Text,
+    },
+    Key {
+        name: "os-version",
+        description: "Operating System version",
+        kind: Kind::Text,
+    },
+    Key {
+        name: "arch",
+        description: "Architecture of binary packages, e.g. x86_64 or aarch64",
+        kind: Kind::Text,
+    },
+    Key {
+        name: "snapshot",
+        description: "Date, as YYYY-MM-DD, or transaction ID of the repository snapshot",
+        kind: Kind::Snapshot,
+    },
+    Key {
+        name: "r-version",
+        description: "R version, e.g. 4.3.2 or latest",
+        kind: Kind::Text,
+    },
+    Key {
+        name: "overrides",
+        description: "TOML file renaming and adding system packages",
+        kind: Kind::Text,
+    },
+    Key {
+        name: "constraints",
+        description: "File of version constraints of system packages",
+        kind: Kind::Text,
+    },
+    Key {
+        name: "exclude",
+        description: "Comma-separated system packages to leave out",
+        kind: Kind::Text,
+    },
+    Key {
+        name: "jobs",
+        description: "Number of requests sent at once",
+        kind: Kind::Count(1),
+    },
+    Key {
+        name: "max-rps",
+        description: "Maximum number of requests per second sent to the server",
+        kind: Kind::Rate,
+    },
+    Key {
+        name: "deadline",
+        description: "Time budget for all requests of an invocation, e.g. 30s",
+        kind: Kind::Duration,
+    },
+    Key {
+        name: "timeout",
+        description: "Timeout of each request to the server, e.g. 30s",
+        kind: Kind::Duration,
+    },
+    Key {
+        name: "retries",
+        description: "Times a rate-limited, failing or interrupted request is retried",
+        kind: Kind::Count(0),
+    },
+    Key {
+        name: "retry-backoff",
+        description: "Delay before the first retry, doubled for each next one, e.g. 2s",
+        kind: Kind::Duration,
+    },
+    Key {
+        name: "cache-ttl",
+        description: "How long server responses are reused from the cache, e.g. 1h",
+        kind: Kind::Duration,
+    },
+    Key {
+        name: "pkg-manager",
+        description: "Package manager to install with",
+        kind: Kind::Choice(&[
+            "apt", "apt-get", "nala", "dnf", "dnf5", "yum", "zypper", "apk",
+        ]),
+    },
+    Key {
+        name: "log-format",
+        description: "Log format: human or json",
+        kind: Kind::Choice(&["human", "json"]),
+    },
+];
+
+/// The key named `name`, failing with the closest known ones.
+pub fn key(name: &str) -> Result<&'static Key> {
+    KEYS.iter().find(|key| key.name == name).ok_or_else(|| {
+        let suggestions = suggest::closest(name, KEYS.iter().map(|key| key.name));
+        let hint = if suggestions.is_empty() {
+            String::new()
+        } else {
+            format!("; did you mean {}?", suggestions.join(" or "))
+        };
+        anyhow!(
+            "unknown configuration key '{}'{} (known: {})",
+            name,
+            hint,
+            KEYS.iter()
+                .map(|key| key.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
+/// The settings given by environment variables, validated, which take
+/// precedence over the configuration files.
+pub fn env_entries() -> Result<Vec<(&'static str, String)>> {
+    let mut entries = Vec::new();
+    for key in KEYS {
+        let value = match std::env::var(key.env_var()) {
+            Ok(value) if !value.is_empty() => value,
+            _ => continue,
+        };
+        key.parse(&value)
+            .with_context(|| format!("invalid environment variable {}", key.env_var()))?;
+        entries.push((key.name, value));
+    }
+    Ok(entries)
+}
+
+/// A configuration file with all settings commented out, to fill in.
+pub fn template() -> String {
+    KEYS.iter()
+        .map(|key| {
+            format!(
+                "# {}\n# (environment variable {})\n# {} = ...\n\n",
+                key.description,
+                key.env_var(),
+                key.name
+            )
+        })
+        .collect()
+}
+
+impl Key {
+    /// The environment variable setting the key, e.g. `R_SYSDEPS_OS_NAME`.
+    pub fn env_var(&self) -> String {
+        format!("R_SYSDEPS_{}", self.name.to_uppercase().replace('-', "_"))
+    }
+
+    /// `value` as stored in the file, failing when it is invalid for the key.
+    fn parse(&self, value: &str) -> Result<Value> {
+        let invalid = || format!("invalid value '{}' for {}", value, self.name);
+        Ok(match self.kind {
+            Kind::Text if value.trim().is_empty() => bail!("{} must not be empty", self.name),
+            Kind::Text => Value::from(value),
+            Kind::Server => Value::from(api::normalize_server(value).with_context(invalid)?),
+            Kind::Snapshot => {
+                repository::check_snapshot(value).with_context(invalid)?;
+                Value::from(value)
+            }
+            Kind::Rate => match value.parse::<f64>() {
+                Ok(rate) if rate.is_finite() && rate > 0.0 => Value::from(rate),
+                _ => bail!("{}: expected a positive number", invalid()),
+            },
+            Kind::Count(min) => match value.parse::<i64>() {
+                Ok(count) if count >= min => Value::from(count),
+                _ => bail!("{}: expected a whole number of at least {}", invalid(), min),
+            },
+            Kind::Duration => {
+                humantime::parse_duration(value).with_context(invalid)?;
+                Value::from(value)
+            }
+            Kind::Choice(choices) if choices.contains(&value) => Value::from(value),
+            Kind::Choice(choices) => bail!("{}: expected {}", invalid(), choices.join(" or ")),
+        })
+    }
+
+    /// The stored `value` as it would be given on the command line,
+    /// validated.
+    fn format(&self, value: &Item) -> Result<String> {
+        let text = match (self.kind, value.as_value()) {
+            (Kind::Rate, Some(Value::Float(rate))) => rate.value().to_string(),
+            (Kind::Rate, Some(Value::Integer(rate))) => rate.value().to_string(),
+            (Kind::Count(_), Some(Value::Integer(count))) => count.value().to_string(),
+            (_, Some(Value::String(text))) => text.value().clone(),
+            _ => bail!("{} must be a {}", self.name, self.type_name()),
+        };
+        self.parse(&text)?;
+        Ok(text)
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self.kind {
+            Kind::Rate | Kind::Count(_) => "number",
+            _ => "string",
+        }
+    }
+}
+
+/// Location of the configuration file, overridden by `R_SYSDEPS_CONFIG`.
+pub fn path() -> Option<PathBuf> {
+    std::env::var_os("R_SYSDEPS_CONFIG")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| paths::config_dir().map(|dir| dir.join(FILE)))
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    document: DocumentMut,
+}
+
+impl Config {
+    /// Read the configuration at `path`, which is empty when there is none.
+    pub fn load(path: &Path) -> Result<Config> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text)
+                .with_context(|| format!("invalid configuration {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+        }
+    }
+
+    /// Parse a configuration, checking its keys and values.
+    pub fn parse(text: &str) -> Result<Config> {
+        let config = Config {
+            document: text.parse()?,
+        };
+        config.entries()?;
+        Ok(config)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        files::write_atomic(path, self.document.to_string().as_bytes())
+    }
+
+    /// The value of `name`, if set.
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        let key = key(name)?;
+        self.document
+            .get(key.name)
+            .map(|value| key.format(value))
+            .transpose()
+    }
+
+    /// Set `name` to `value`, keeping the rest of the file as it is.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let key = key(name)?;
+        self.document[key.name] = Item::Value(key.parse(value)?);
+        Ok(())
+    }
+
+    /// Remove `name`, returning whether it was set.
+    pub fn unset(&mut self, name: &str) -> Result<bool> {
+        Ok(self.document.remove(key(name)?.name).is_some())
+    }
+
+    /// The settings in the order of the file, as given on the command line.
+    pub fn entries(&self) -> Result<Vec<(&'static str, String)>> {
+        self.document
+            .iter()
+            .map(|(name, value)| {
+                let key = key(name)?;
+                Ok((key.name, key.format(value)?))
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
@@ -0,0 +1,97 @@
+//! Version constraints of system packages, as apt relations such as
+//! `libgdal-dev >= 3.4`, pinned in the install scripts of any package
+//! manager.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::models::APISysReqs;
+use crate::shell;
+
+/// Split a constraint into the system package and its apt relation, e.g.
+/// `libgdal-dev>=3.4` into `libgdal-dev` and `>= 3.4`.
+pub fn parse(s: &str) -> Result<(String, String)> {
+    let split = s.find(['<', '>', '=']);
+    let (name, relation) = split.map(|i| s.split_at(i)).unwrap_or((s, ""));
+    let operator = ["<<", "<=", ">=", ">>", "="]
+        .iter()
+        .find(|op| relation.starts_with(*op));
+    match operator {
+        Some(op) if !name.trim().is_empty() && !relation[op.len()..].trim().is_empty() => Ok((
+            name.trim().to_string(),
+            format!("{} {}", op, relation[op.len()..].trim()),
+        )),
+        _ => bail!(
+            "invalid constraint '{}', expected a package, one of << <= = >= >> and a version",
+            s
+        ),
+    }
+}
+
+/// Read a constraints file of one constraint per line, skipping blank lines
+/// and `#` comments.
+pub fn load(path: &Path) -> Result<Vec<(String, String)>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.split('#').next().unwrap_or_default().trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| parse(line).with_context(|| format!("{}:{}", path.display(), i + 1)))
+        .collect()
+}
+
+/// Pin the versions of the constrained system packages in the install
+/// scripts of `sysreqs`.
+pub fn pin(sysreqs: &mut APISysReqs, constraints: &[(String, String)]) -> Result<()> {
+    for req in sysreqs.requirements.iter_mut() {
+        for script in req.requirements.install_scripts.iter_mut() {
+            let (installer, package) = match script.rsplit_once(' ') {
+                Some((installer, package)) => (installer, package.trim_matches('\'')),
+                None => continue,
+            };
+            if let Some((_, relation)) = constraints.iter().find(|(name, _)| name == package) {
+                *script = pinned(installer, package, relation)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The install command of `package` at the versions of the apt `relation`.
+///
+/// apt-get installs exact versions only, so other relations are installed
+/// with `apt-get satisfy`.
+fn pinned(installer: &str, package: &str, relation: &str) -> Result<String> {
+    let (op, version) = relation.split_once(' ').unwrap_or(("=", relation));
+    let spec = match (installer.split_whitespace().next(), op) {
+        (Some("apt-get"), "=") => format!("{}={}", package, version),
+        (Some("apt-get"), _) => {
+            return Ok(format!(
+                "apt-get satisfy -y {}",
+                shell::quote(&format!("{} ({} {})", package, op, version))
+            ))
+        }
+        (Some("dnf") | Some("yum"), "=") => format!("{}-{}", package, version),
+        (Some("dnf") | Some("yum"), _) => format!("{} {} {}", package, rpm_operator(op), version),
+        (Some("zypper") | Some("apk"), _) => format!("{}{}{}", package, rpm_operator(op), version),
+        _ => bail!(
+            "cannot pin the version of {} installed with '{}'",
+            package,
+            installer
+        ),
+    };
+
+    Ok(format!("{} {}", installer, shell::quote(&spec)))
+}
+
+/// The operator of an apt relation as rpm and apk write it.
+fn rpm_operator(op: &str) -> &str {
+    match op {
+        "<<" => "<",
+        ">>" => ">",
+        op => op,
+    }
+}
@@ -0,0 +1,157 @@
+//! Disposable containers of a target OS to try install commands in.
+
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::rules;
+
+/// Container runtimes looked for, in order of preference.
+pub const RUNTIMES: &[&str] = &["docker", "podman"];
+
+/// The container runtime to use: the one given, or else the first of
+/// [`RUNTIMES`] installed.
+pub fn runtime(given: Option<&str>) -> Result<String> {
+    if let Some(given) = given {
+        return Ok(given.to_string());
+    }
+    RUNTIMES
+        .iter()
+        .find(|runtime| {
+            Command::new(runtime)
+                .arg("--version")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map(|status| status.success())
+                .unwrap_or(false)
+        })
+        .map(|runtime| runtime.to_string())
+        .ok_or_else(|| {
+            anyhow!(
+                "no container runtime found, install {}",
+                RUNTIMES.join(" or ")
+            )
+        })
+}
+
+/// The official image of `distribution` `release`.
+pub fn image(distribution: &str, release: &str) -> Result<String> {
+    let major = release.split('.').next().unwrap_or(release);
+    Ok(match rules::canonical_distribution(distribution) {
+        "ubuntu" => format!("docker.io/library/ubuntu:{}", release),
+        "debian" => format!("docker.io/library/debian:{}", major),
+        "fedora" => format!("docker.io/library/fedora:{}", major),
+        "centos" => format!("quay.io/centos/centos:{}", release),
+        "rockylinux" => format!("docker.io/rockylinux/rockylinux:{}", major),
+        "almalinux" => format!("docker.io/library/almalinux:{}", major),
+        "redhat" => format!("registry.access.redhat.com/ubi{}/ubi", major),
+        "opensuse" => format!("registry.opensuse.org/opensuse/leap:{}", release),
+        "alpine" => format!("docker.io/library/alpine:{}", release),
+        _ => bail!(
+            "no image known for {} {}, specify one with --image",
+            distribution,
+            release
+        ),
+    })
+}
+
+/// A command run in the container.
+#[derive(Debug)]
+pub struct Step {
+    pub command: String,
+    pub success: bool,
+    pub duration: Duration,
+    /// Its stdout and stderr.
+    pub output: String,
+}
+
+/// A running container, removed when dropped.
+pub struct Container {
+    runtime: String,
+    id: String,
+}
+
+impl Container {
+    /// Start a container of `image` that idles until removed.
+    pub fn start(runtime: &str, image: &str) -> Result<Container> {
+        let output = Command::new(runtime)
+            .args(["run", "--detach", "--rm", "--entrypoint", "sh", image])
+            .args(["-c", "while :; do sleep 3600; done"])
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("failed to run {}", runtime))?;
+        if !output.status.success() {
+            bail!(
+                "failed to start a container of {}: {}",
+                image,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(Container {
+            runtime: runtime.to_string(),
+            id: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        })
+    }
+
+    /// Run `command` with `sh` in the container, as root.
+    pub fn run(&self, command: &str) -> Result<Step> {
+        let start = Instant::now();
+        let Output {
+            status,
+            stdout,
+            stderr,
+        } = Command::new(&self.runtime)
+            .args([
+                "exec",
+                "--user",
+                "root",
+                "--env",
+                "DEBIAN_FRONTEND=noninteractive",
+            ])
+            .args([&self.id, "sh", "-c", command])
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("failed to run {}", self.runtime))?;
+
+        Ok(Step {
+            command: command.to_string(),
+            success: status.success(),
+            duration: start.elapsed(),
+            output: format!(
+                "{}{}",
+                String::from_utf8_lossy(&stdout),
+                String::from_utf8_lossy(&stderr)
+            ),
+        })
+    }
+}
+
+impl Drop for Container {
+    fn drop(&mut self) {
+        let _ = Command::new(&self.runtime)
+            .args(["rm", "--force", &self.id])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+/// Run `commands` in order in a fresh container of `image`, up to the first
+/// that fails.
+pub fn test(runtime: &str, image: &str, commands: &[String]) -> Result<Vec<Step>> {
+    let container = Container::start(runtime, image)?;
+    let mut steps = Vec::new();
+    for command in commands.iter() {
+        let step = container.run(command)?;
+        let success = step.success;
+        steps.push(step);
+        if !success {
+            break;
+        }
+    }
+
+    Ok(steps)
+}
@@ -0,0 +1,60 @@
+//! System dependencies in the shape containerit works with when generating
+//! Dockerfiles: system packages of each R package, for an r-hub platform.
+
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::models::APISysReqs;
+use crate::packages;
+use crate::provenance::Provenance;
+
+/// System packages of R packages on a platform.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct ContaineritSysreqs {
+    /// r-hub platform, e.g. `linux-x86_64-debian-gcc`.
+    pub platform: String,
+    /// System packages by R package; empty for packages without any.
+    pub packages: BTreeMap<String, Vec<String>>,
+    /// Where the system requirements came from, with `--provenance`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+/// r-hub platform containerit uses for images of `distribution`.
+pub fn platform(distribution: &str) -> String {
+    let family = match distribution {
+        "ubuntu" | "debian" => "debian",
+        "centos" | "redhat" | "rockylinux" | "almalinux" | "fedora" => "fedora",
+        other => other,
+    };
+    format!("linux-x86_64-{}-gcc", family)
+}
+
+/// System packages of each of `names`, base packages left out.
+pub fn from_sysreqs(
+    distribution: &str,
+    names: &[String],
+    sysreqs: &APISysReqs,
+) -> ContaineritSysreqs {
+    let packages = names
+        .iter()
+        .filter(|name| !packages::is_base(name))
+        .map(|name| {
+            let system_packages = sysreqs
+                .requirements
+                .iter()
+                .find(|req| req.name == *name)
+                .map(|req| req.requirements.packages.clone())
+                .unwrap_or_default();
+            (name.clone(), system_packages)
+        })
+        .collect();
+
+    ContaineritSysreqs {
+        platform: platform(distribution),
+        packages,
+        provenance: None,
+    }
+}
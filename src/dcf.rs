@@ -0,0 +1,33 @@
+//! Debian Control File format, as used by R's `DESCRIPTION` and `PACKAGES` files.
+
+use std::collections::BTreeMap;
+
+/// Parse records separated by blank lines; continuation lines are joined with a space.
+pub fn parse(text: &str) -> Vec<BTreeMap<String, String>> {
+    let mut records = Vec::new();
+    let mut record: BTreeMap<String, String> = BTreeMap::new();
+    let mut last_key: Option<String> = None;
+
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !record.is_empty() {
+                records.push(std::mem::take(&mut record));
+            }
+            last_key = None;
+        } else if line.starts_with(|c: char| c.is_whitespace()) {
+            if let Some(value) = last_key.as_ref().and_then(|key| record.get_mut(key)) {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some(i) = line.find(':') {
+            let key = line[..i].trim().to_string();
+            record.insert(key.clone(), line[i + 1..].trim().to_string());
+            last_key = Some(key);
+        }
+    }
+    if !record.is_empty() {
+        records.push(record);
+    }
+
+    records
+}
@@ -0,0 +1,38 @@
+//! Development container definitions, for VS Code and GitHub Codespaces.
+
+use serde_json::json;
+
+use crate::models::APISysReqs;
+use crate::render;
+
+/// Files of a `.devcontainer` directory: a Dockerfile with the system
+/// requirements baked into `base_image`, and the `devcontainer.json` building it.
+///
+/// With `layered`, the Dockerfile is the layered one installing these R
+/// packages on top of the system requirements.
+pub fn files(
+    base_image: &str,
+    sysreqs: &APISysReqs,
+    repository_url: Option<&str>,
+    layered: Option<&[String]>,
+) -> Vec<(&'static str, String)> {
+    let config = json!({
+        "name": "R",
+        "build": { "dockerfile": "Dockerfile" },
+        "customizations": {
+            "vscode": { "extensions": ["REditorSupport.r"] }
+        }
+    });
+
+    let dockerfile = match layered {
+        Some(r_packages) => {
+            render::dockerfile_layered(base_image, sysreqs, repository_url, r_packages)
+        }
+        None => render::dockerfile(base_image, sysreqs, repository_url),
+    };
+
+    vec![
+        ("devcontainer.json", format!("{:#}\n", config)),
+        ("Dockerfile", dockerfile),
+    ]
+}
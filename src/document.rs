@@ -0,0 +1,90 @@
+//! The system requirements of R packages as a machine-readable document, for
+//! scripts and CI to consume without parsing the text output.
+
+use std::fmt::Write;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{APIPrePost, APISysReqs};
+use crate::provenance::Provenance;
+
+/// System requirements of a set of R packages on an OS.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct SysreqsDocument {
+    pub distribution: String,
+    pub release: String,
+    pub packages: Vec<PackageSysreqs>,
+    /// Where the system requirements came from, with `--provenance`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+/// System requirements of a single R package, with the scripts installing them.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct PackageSysreqs {
+    pub name: String,
+    pub system_packages: Vec<String>,
+    pub pre_install: Vec<String>,
+    pub install_scripts: Vec<String>,
+    pub post_install: Vec<String>,
+}
+
+/// The document of the server's system requirements.
+pub fn from_sysreqs(distribution: &str, release: &str, sysreqs: &APISysReqs) -> SysreqsDocument {
+    let scripts = |steps: &Option<Vec<APIPrePost>>| -> Vec<String> {
+        steps
+            .iter()
+            .flatten()
+            .map(|step| step.script.clone())
+            .collect()
+    };
+    SysreqsDocument {
+        distribution: distribution.to_string(),
+        release: release.to_string(),
+        packages: sysreqs
+            .requirements
+            .iter()
+            .map(|req| PackageSysreqs {
+                name: req.name.clone(),
+                system_packages: req.requirements.packages.clone(),
+                pre_install: scripts(&req.requirements.pre_install),
+                install_scripts: req.requirements.install_scripts.clone(),
+                post_install: scripts(&req.requirements.post_install),
+            })
+            .collect(),
+        provenance: None,
+    }
+}
+
+/// The document as YAML, with strings quoted as JSON, which YAML reads alike.
+pub fn yaml(document: &SysreqsDocument) -> String {
+    let quote = |value: &str| serde_json::to_string(value).expect("strings serialize");
+    let list = |out: &mut String, key: &str, values: &[String]| {
+        if values.is_empty() {
+            writeln!(out, "    {}: []", key).unwrap();
+            return;
+        }
+        writeln!(out, "    {}:", key).unwrap();
+        for value in values.iter() {
+            writeln!(out, "      - {}", quote(value)).unwrap();
+        }
+    };
+
+    let mut out = String::new();
+    writeln!(out, "distribution: {}", quote(&document.distribution)).unwrap();
+    writeln!(out, "release: {}", quote(&document.release)).unwrap();
+    if document.packages.is_empty() {
+        writeln!(out, "packages: []").unwrap();
+        return out;
+    }
+    writeln!(out, "packages:").unwrap();
+    for package in document.packages.iter() {
+        writeln!(out, "  - name: {}", quote(&package.name)).unwrap();
+        list(&mut out, "system_packages", &package.system_packages);
+        list(&mut out, "pre_install", &package.pre_install);
+        list(&mut out, "install_scripts", &package.install_scripts);
+        list(&mut out, "post_install", &package.post_install);
+    }
+    out
+}
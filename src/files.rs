@@ -0,0 +1,58 @@
+//! Writing files so that readers never see them half written.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{bail, Context, Result};
+
+/// Distinguishes temporary files written concurrently by one process.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary file next to `path`, unique to this write.
+pub fn temp_path(path: &Path) -> PathBuf {
+    path.with_extension(format!(
+        "{}-{}.tmp",
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Write to a temporary file next to `path` and rename it into place, so
+/// that readers see either the old or the new contents.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let temp = temp_path(path);
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+
+    result.with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Write `path` as [`write_atomic`] does, unless it exists: the temporary
+/// file is linked into place, which fails rather than replace a file
+/// created meanwhile.
+pub fn write_new(path: &Path, contents: &[u8]) -> Result<()> {
+    let temp = temp_path(path);
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::hard_link(&temp, path));
+    let _ = std::fs::remove_file(&temp);
+
+    match result {
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+            bail!("{} already exists", path.display())
+        }
+        result => result.with_context(|| format!("failed to write {}", path.display())),
+    }
+}
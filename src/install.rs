@@ -0,0 +1,60 @@
+//! Running the install commands of system requirements on this machine.
+
+use std::fmt;
+use std::process::Command;
+
+use anyhow::{Context, Result};
+
+/// A command that failed, with its exit code unless killed by a signal.
+#[derive(Debug)]
+pub struct Failed {
+    pub command: String,
+    pub code: Option<i32>,
+}
+
+impl fmt::Display for Failed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "'{}' failed with exit code {}", self.command, code),
+            None => write!(f, "'{}' was killed by a signal", self.command),
+        }
+    }
+}
+
+impl std::error::Error for Failed {}
+
+/// The program and arguments running `command` with `sh`, through sudo when
+/// `sudo` is set, without prompts of apt.
+pub fn invocation(command: &str, sudo: bool) -> Vec<String> {
+    let mut args = Vec::new();
+    if sudo {
+        args.push(String::from("sudo"));
+    }
+    args.extend(
+        ["env", "DEBIAN_FRONTEND=noninteractive", "sh", "-c", command]
+            .iter()
+            .map(|arg| arg.to_string()),
+    );
+    args
+}
+
+/// Run `commands` in order with their output shown, stopping at the first
+/// failing, which is returned as a [`Failed`] error.
+pub fn run(commands: &[String], sudo: bool) -> Result<()> {
+    for command in commands.iter() {
+        eprintln!("+ {}", command);
+        let args = invocation(command, sudo);
+        let status = Command::new(&args[0])
+            .args(&args[1..])
+            .status()
+            .with_context(|| format!("failed to run {}", args[0]))?;
+        if !status.success() {
+            return Err(Failed {
+                command: command.clone(),
+                code: status.code(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! The system packages installed on this machine, looked up with the package
+//! manager of the distribution, to check requirements before installing R
+//! packages.
+
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::rules;
+
+/// Whether a system package is installed, and at a suitable version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum State {
+    Present {
+        version: String,
+    },
+    Missing,
+    /// Installed at a version not satisfying the apt relation constraining
+    /// it, e.g. `>= 3.4`.
+    Outdated {
+        version: String,
+        relation: String,
+    },
+}
+
+impl State {
+    pub fn name(&self) -> &'static str {
+        match self {
+            State::Present { .. } => "present",
+            State::Missing => "missing",
+            State::Outdated { .. } => "outdated",
+        }
+    }
+}
+
+/// The state of `package` on `distribution`, as outdated when its version
+/// does not satisfy the apt `relation` given.
+pub fn check(distribution: &str, package: &str, relation: Option<&str>) -> Result<State> {
+    let version = match version(distribution, package)? {
+        Some(version) => version,
+        None => return Ok(State::Missing),
+    };
+    match relation {
+        Some(relation) if !satisfies(distribution, &version, relation)? => Ok(State::Outdated {
+            version,
+            relation: relation.to_string(),
+        }),
+        _ => Ok(State::Present { version }),
+    }
+}
+
+/// The installed version of `package`, if it is installed.
+pub fn version(distribution: &str, package: &str) -> Result<Option<String>> {
+    let mut command = match rules::query_command(distribution)? {
+        "dpkg -s" => query(
+            "dpkg-query",
+            &["-W", "-f=${db:Status-Status}\t${Version}", package],
+        ),
+        "rpm -q" => query(
+            "rpm",
+            &["-q", "--qf", "installed\t%{VERSION}-%{RELEASE}", package],
+        ),
+        _ => query("apk", &["list", "--installed", package]),
+    };
+    let output = command
+        .output()
+        .with_context(|| format!("failed to run {:?}", command))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // apk lists e.g. `curl-8.5.0-r0 x86_64 {curl} (MIT) [installed]`
+    if let Some(line) = stdout.lines().find(|line| line.ends_with("[installed]")) {
+        let name = line.split_whitespace().next().unwrap_or_default();
+        return Ok(name
+            .strip_prefix(package)
+            .and_then(|version| version.strip_prefix('-'))
+            .map(String::from));
+    }
+    Ok(stdout
+        .trim()
+        .split_once('\t')
+        .filter(|(status, _)| *status == "installed")
+        .map(|(_, version)| version.to_string()))
+}
+
+/// Whether `version` satisfies the apt `relation`, compared by the package
+/// manager of `distribution`.
+fn satisfies(distribution: &str, version: &str, relation: &str) -> Result<bool> {
+    let (op, wanted) = relation.split_once(' ').unwrap_or(("=", relation));
+    let ordering = match rules::query_command(distribution)? {
+        "dpkg -s" => {
+            let op = match op {
+                "<<" => "lt",
+                "<=" => "le",
+                ">=" => "ge",
+                ">>" => "gt",
+                _ => "eq",
+            };
+            return Ok(query("dpkg", &["--compare-versions", version, op, wanted])
+                .status()
+                .context("failed to run dpkg --compare-versions")?
+                .success());
+        }
+        "rpm -q" => {
+            let lua = format!(
+                "%{{lua: print(rpm.vercmp(\"{}\", \"{}\"))}}",
+                version, wanted
+            );
+            compare(query("rpm", &["--eval", &lua]), &["-1", "0", "1"])?
+        }
+        _ => compare(
+            query("apk", &["version", "-t", version, wanted]),
+            &["<", "=", ">"],
+        )?,
+    };
+    Ok(match op {
+        "<<" => ordering.is_lt(),
+        "<=" => ordering.is_le(),
+        ">=" => ordering.is_ge(),
+        ">>" => ordering.is_gt(),
+        _ => ordering.is_eq(),
+    })
+}
+
+/// The ordering printed by `command`, as one of `outputs` for less, equal
+/// and greater.
+fn compare(mut command: Command, outputs: &[&str; 3]) -> Result<std::cmp::Ordering> {
+    let output = command
+        .output()
+        .with_context(|| format!("failed to run {:?}", command))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match outputs.iter().position(|out| *out == stdout.trim()) {
+        Some(i) => Ok((i as i32 - 1).cmp(&0)),
+        None => bail!(
+            "cannot compare versions with {:?}: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    }
+}
+
+fn query(program: &str, args: &[&str]) -> Command {
+    let mut command = Command::new(program);
+    command.args(args);
+    command
+}
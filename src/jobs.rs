@@ -0,0 +1,73 @@
+//! Concurrency of independent requests, such as the chunks of a batched
+//! query, bounded by `--jobs`.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use anyhow::Result;
+
+/// Requests sent at once unless set otherwise, few enough for any server.
+pub const DEFAULT_JOBS: usize = 4;
+
+static JOBS: AtomicUsize = AtomicUsize::new(DEFAULT_JOBS);
+
+/// Run at most `jobs` requests at once from now on.
+pub fn set_jobs(jobs: usize) {
+    JOBS.store(jobs.max(1), Ordering::Relaxed);
+}
+
+/// The number of requests run at once.
+pub fn jobs() -> usize {
+    JOBS.load(Ordering::Relaxed)
+}
+
+/// Apply `f` to each of `items` and their index, at most [`jobs`] at once,
+/// returning the results in order.
+///
+/// No further items are started once one fails, and the failure of the
+/// first item failing, by order, is returned.
+pub fn try_map<T, R, F>(items: &[T], f: F) -> Result<Vec<R>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(usize, &T) -> Result<R> + Sync,
+{
+    let threads = jobs().min(items.len());
+    if threads <= 1 {
+        return items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| f(index, item))
+            .collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results: Mutex<Vec<Option<Result<R>>>> = Mutex::new(items.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while !failed.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let item = match items.get(index) {
+                        Some(item) => item,
+                        None => break,
+                    };
+                    let result = f(index, item);
+                    if result.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    results.lock().expect("results lock poisoned")[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    // items are started in order, so those never started follow a failure
+    results
+        .into_inner()
+        .expect("results lock poisoned")
+        .into_iter()
+        .flatten()
+        .collect()
+}
@@ -0,0 +1,64 @@
+//! Resolve system dependencies of R packages using an RStudio Package Manager server.
+//!
+//! The HTTP client is behind the default `http` feature; without it the crate
+//! builds for `wasm32-unknown-unknown`, where the `wasm` feature provides
+//! `fetch`-based bindings.
+
+pub mod api;
+#[cfg(feature = "http")]
+pub mod batch;
+pub mod cache;
+#[cfg(feature = "http")]
+pub mod client;
+pub mod config;
+pub mod constraints;
+pub mod container;
+pub mod containerit;
+pub mod dcf;
+pub mod devcontainer;
+pub mod document;
+pub mod files;
+pub mod install;
+pub mod installed;
+#[cfg(feature = "http")]
+pub mod jobs;
+pub mod lockfile;
+pub mod manifest;
+pub mod manpage;
+pub mod matrix;
+pub mod models;
+pub mod nix;
+pub mod os;
+#[cfg(feature = "http")]
+pub mod osv;
+pub mod overrides;
+pub mod package_manager;
+pub mod packages;
+pub mod pak;
+pub mod paths;
+pub mod project;
+pub mod provenance;
+pub mod provision;
+#[cfg(feature = "http")]
+pub mod r2u;
+pub mod recording;
+pub mod render;
+#[cfg(feature = "http")]
+pub mod repology;
+pub mod repository;
+pub mod rig;
+pub mod rocker;
+pub mod rules;
+pub mod schema;
+#[cfg(feature = "http")]
+pub mod serve;
+pub mod shell;
+pub mod signing;
+pub mod spack;
+pub mod suggest;
+pub mod timings;
+pub mod translate;
+pub mod userspace;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod windows;
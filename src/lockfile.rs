@@ -0,0 +1,225 @@
+//! Lockfiles recording how the system requirements of R packages were
+//! resolved, for review and reproducible installs.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::files;
+use crate::manifest::{Change, Manifest};
+use crate::models::{APIPackageRequirements, APIRequirement, APISysReqs};
+
+/// Default lockfile name.
+pub const LOCKFILE: &str = "r-sysdeps.lock";
+
+/// Version of the lockfile format.
+pub const VERSION: u32 = 1;
+
+/// The system requirements of R packages, and where and when they were
+/// resolved.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct Lockfile {
+    pub version: u32,
+    pub server: String,
+    pub repository: String,
+    pub snapshot: String,
+    pub distribution: String,
+    pub release: String,
+    pub resolved_at: String,
+    /// Requirements by R package, empty for packages without any.
+    pub packages: BTreeMap<String, APIPackageRequirements>,
+    /// Versions of the R packages the requirements were resolved for, when
+    /// known, as from an renv.lock.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub versions: BTreeMap<String, String>,
+}
+
+impl Lockfile {
+    /// Lock the resolution of `names`, resolved now.
+    pub fn new(
+        server: &str,
+        repository: &str,
+        snapshot: &str,
+        distribution: &str,
+        release: &str,
+        names: &[String],
+        sysreqs: &APISysReqs,
+    ) -> Lockfile {
+        let mut packages: BTreeMap<String, APIPackageRequirements> = names
+            .iter()
+            .map(|name| (name.clone(), APIPackageRequirements::default()))
+            .collect();
+        for req in sysreqs.requirements.iter() {
+            packages.insert(req.name.clone(), req.requirements.clone());
+        }
+
+        Lockfile {
+            version: VERSION,
+            server: server.to_string(),
+            repository: repository.to_string(),
+            snapshot: snapshot.to_string(),
+            distribution: distribution.to_string(),
+            release: release.to_string(),
+            resolved_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            packages,
+            versions: BTreeMap::new(),
+        }
+    }
+
+    /// The R packages of `names` to resolve again: those not locked, and
+    /// those whose version in `versions` differs from the locked one.
+    pub fn stale(&self, names: &[String], versions: &BTreeMap<String, String>) -> Vec<String> {
+        names
+            .iter()
+            .filter(|name| {
+                !self.packages.contains_key(*name)
+                    || versions.get(*name) != self.versions.get(*name)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Lock `names` at `versions`, replacing the requirements of the `stale`
+    /// packages with those of `sysreqs` and dropping the packages no longer
+    /// named.
+    pub fn update(
+        &mut self,
+        names: &[String],
+        versions: BTreeMap<String, String>,
+        stale: &[String],
+        sysreqs: &APISysReqs,
+    ) {
+        self.packages
+            .retain(|name, _| names.contains(name) && !stale.contains(name));
+        for name in stale {
+            self.packages
+                .insert(name.clone(), APIPackageRequirements::default());
+        }
+        for req in sysreqs.requirements.iter() {
+            if stale.contains(&req.name) {
+                self.packages
+                    .insert(req.name.clone(), req.requirements.clone());
+            }
+        }
+        self.versions = versions;
+        if !stale.is_empty() {
+            self.resolved_at = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Lockfile> {
+        let contents =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let lockfile: Lockfile = serde_json::from_slice(&contents)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        if lockfile.version > VERSION {
+            bail!(
+                "{} has lockfile version {}, this release of r-sysdeps reads up to {}",
+                path.display(),
+                lockfile.version,
+                VERSION
+            );
+        }
+
+        Ok(lockfile)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut contents = serde_json::to_vec_pretty(self)?;
+        contents.push(b'\n');
+        files::write_atomic(path, &contents)
+    }
+
+    /// The locked requirements, of the packages that have any.
+    pub fn sysreqs(&self) -> APISysReqs {
+        APISysReqs {
+            requirements: self
+                .packages
+                .iter()
+                .filter(|(_, requirements)| !requirements.packages.is_empty())
+                .map(|(name, requirements)| APIRequirement {
+                    name: name.clone(),
+                    requirements: requirements.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        Manifest::from_sysreqs(&self.distribution, &self.release, &self.sysreqs())
+    }
+
+    /// Changes from `self` to `new`: of the server, repository, snapshot and
+    /// OS, of the system packages of each R package, and of their scripts.
+    pub fn diff(&self, new: &Lockfile) -> Vec<Change> {
+        let mut changes = Vec::new();
+        for (name, old_value, new_value) in [
+            ("server", &self.server, &new.server),
+            ("repository", &self.repository, &new.repository),
+            ("snapshot", &self.snapshot, &new.snapshot),
+        ] {
+            if old_value != new_value {
+                changes.push(Change::Setting {
+                    name,
+                    old: old_value.clone(),
+                    new: new_value.clone(),
+                });
+            }
+        }
+        changes.extend(self.manifest().diff(&new.manifest()));
+
+        let names: BTreeSet<&String> = self.packages.keys().chain(new.packages.keys()).collect();
+        for name in names {
+            let old_scripts = scripts(self.packages.get(name));
+            let new_scripts = scripts(new.packages.get(name));
+            for script in new_scripts.difference(&old_scripts) {
+                changes.push(Change::ScriptAdded {
+                    package: name.clone(),
+                    script: script.to_string(),
+                });
+            }
+            for script in old_scripts.difference(&new_scripts) {
+                changes.push(Change::ScriptRemoved {
+                    package: name.clone(),
+                    script: script.to_string(),
+                });
+            }
+        }
+
+        changes
+    }
+}
+
+/// Pre-install and post-install scripts of a package's requirements, and
+/// install scripts other than those installing one of its system packages,
+/// whose changes show as changes of the system packages.
+fn scripts(requirements: Option<&APIPackageRequirements>) -> BTreeSet<&str> {
+    let mut scripts = BTreeSet::new();
+    if let Some(requirements) = requirements {
+        let steps = requirements
+            .pre_install
+            .iter()
+            .chain(requirements.post_install.iter())
+            .flatten();
+        scripts.extend(steps.map(|step| step.script.as_str()));
+        let installs_package = |script: &str| {
+            script.rsplit_once(' ').is_some_and(|(_, last)| {
+                let last = last.trim_matches('\'');
+                requirements.packages.iter().any(|package| package == last)
+            })
+        };
+        scripts.extend(
+            requirements
+                .install_scripts
+                .iter()
+                .map(String::as_str)
+                .filter(|script| !installs_package(script)),
+        );
+    }
+
+    scripts
+}
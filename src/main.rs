@@ -1,34 +1,618 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 
 use anyhow::{anyhow, bail, Context, Result};
-use serde::Deserialize;
+use r_sysdeps::batch::Batch;
+use r_sysdeps::cache::Cache;
+use r_sysdeps::client::{self, Client};
+use r_sysdeps::config::{self, Config};
+use r_sysdeps::lockfile::{self, Lockfile};
+use r_sysdeps::manifest::{self, Manifest};
+use r_sysdeps::models::{APIRepository, APIStatusResponse, APISysReqs, DEFAULT_CRAN_REPO};
+use r_sysdeps::overrides::{self, Overrides};
+use r_sysdeps::package_manager::PackageManager;
+use r_sysdeps::packages::Merged;
+use r_sysdeps::provenance::{self, Metadata, Provenance};
+use r_sysdeps::render::Phase;
+use r_sysdeps::{
+    api, cache, constraints, container, containerit, devcontainer, document, files, install,
+    installed, jobs, manpage, matrix, nix, os, osv, pak, project, provision, r2u, recording,
+    render, repology, repository, rig, rocker, rules, schema, serve, shell, signing, spack,
+    suggest, timings, translate, userspace, windows,
+};
+use serde::Serialize;
+use structopt::clap::{App, AppSettings, ArgGroup, ArgMatches, ErrorKind, Shell};
 use structopt::StructOpt;
+use tracing::{debug, info, warn};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+/// `print!` through the locked stdout, returning an error instead of
+/// panicking when it is closed, e.g. by `| head`.
+macro_rules! out {
+    ($($arg:tt)*) => {
+        write!(std::io::stdout().lock(), $($arg)*)
+    };
+}
+
+/// `println!` as [`out!`] does `print!`.
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        writeln!(std::io::stdout().lock(), $($arg)*)
+    };
+}
+
+/// How long a cached repository listing is used to look up repository ids.
+const REPOSITORIES_TTL: Duration = Duration::from_secs(24 * 60 * 60);
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "action")]
 enum Action {
     /// Get system dependencies for R packages
     #[structopt(name = "package")]
-    Package {
-        /// R packages
-        #[structopt()]
-        packages: Vec<String>,
-    },
+    Package(Box<PackageOpt>),
 
     /// Get repository information
-    #[structopt(name = "repository")]
+    #[structopt(name = "repository", group = ArgGroup::with_name("query").required(true))]
     Repository {
         /// List all R repositories on server
-        #[structopt(short, long = "list-repositories")]
+        #[structopt(short, long = "list-repositories", group = "query")]
         list: bool,
 
         /// Print binary package URL for repository
-        #[structopt(short, long)]
+        #[structopt(short, long, group = "query")]
         binary_repository: bool,
 
         /// Print source package URL for repository
-        #[structopt(short, long)]
+        #[structopt(short, long, group = "query")]
         source_repository: bool,
+
+        /// Print R code setting the repository, from binary packages where the
+        /// server has them, for an .Rprofile
+        #[structopt(long, group = "query")]
+        rprofile: bool,
+
+        /// Print the Bioconductor release for the R version
+        #[structopt(long = "bioc-version", group = "query")]
+        bioc_version: bool,
+
+        /// Print the frozen CRAN snapshot URL matching the Bioconductor release
+        /// of the R version, or of --bioc
+        #[structopt(long = "cran-snapshot", group = "query")]
+        cran_snapshot: bool,
+
+        /// Bioconductor release to match the CRAN snapshot to, e.g. 3.18
+        #[structopt(long, requires = "cran-snapshot")]
+        bioc: Option<String>,
+    },
+
+    /// Show the server's version, settings, supported OS releases and
+    /// Bioconductor releases
+    #[structopt(name = "status")]
+    Status {
+        /// Output format: text or json
+        #[structopt(long, default_value = "text")]
+        format: StatusFormat,
+    },
+
+    /// Print the OS releases with system requirements as a JSON array, for CI
+    /// matrices
+    #[structopt(name = "matrix")]
+    Matrix {
+        /// Only releases with binary packages
+        #[structopt(long)]
+        binaries: bool,
+
+        /// Indent the JSON
+        #[structopt(long)]
+        pretty: bool,
+    },
+
+    /// Check that a committed manifest matches the system requirements the
+    /// project resolves to, exiting non-zero on drift
+    #[structopt(name = "verify-manifest")]
+    VerifyManifest {
+        /// Manifest of system packages by R package
+        #[structopt(long, default_value = "sysdeps.lock", parse(from_os_str))]
+        manifest: PathBuf,
+
+        /// DESCRIPTION or renv.lock to read the R packages from [default:
+        /// renv.lock if present, else DESCRIPTION]
+        #[structopt(long, parse(from_os_str))]
+        project: Option<PathBuf>,
+
+        /// Include the Suggests of a DESCRIPTION
+        #[structopt(long)]
+        suggests: bool,
+
+        /// Write the manifest instead of failing when it is missing or differs
+        #[structopt(long)]
+        update: bool,
+    },
+
+    /// Write a lockfile of the system requirements of R packages, with the
+    /// server, repository and OS they were resolved for
+    #[structopt(name = "lock")]
+    Lock {
+        /// Lockfile to write
+        #[structopt(long, default_value = lockfile::LOCKFILE, parse(from_os_str))]
+        output: PathBuf,
+
+        /// DESCRIPTION or renv.lock to read the R packages from, when none are
+        /// given [default: renv.lock if present, else DESCRIPTION]
+        #[structopt(long, parse(from_os_str))]
+        project: Option<PathBuf>,
+
+        /// Include the Suggests of a DESCRIPTION
+        #[structopt(long)]
+        suggests: bool,
+
+        /// Only resolve again the R packages added, or whose version in the
+        /// renv.lock changed, since the lockfile was written
+        #[structopt(long)]
+        update: bool,
+
+        /// R packages
+        packages: Vec<String>,
+    },
+
+    /// Show how system requirements changed between two lockfiles
+    #[structopt(name = "diff")]
+    Diff {
+        /// Lockfile before the change
+        #[structopt(parse(from_os_str))]
+        old: PathBuf,
+
+        /// Lockfile after the change
+        #[structopt(parse(from_os_str))]
+        new: PathBuf,
+    },
+
+    /// Record the system packages of each package of an renv.lock in its
+    /// record, or in a sidecar manifest
+    #[structopt(name = "annotate-renv")]
+    AnnotateRenv {
+        /// renv.lock to annotate
+        #[structopt(long, default_value = "renv.lock", parse(from_os_str))]
+        lock: PathBuf,
+
+        /// Write a manifest of system packages by R package to this file,
+        /// leaving the renv.lock as is
+        #[structopt(long, parse(from_os_str))]
+        sidecar: Option<PathBuf>,
+    },
+
+    /// Recommend the rocker image leaving the fewest system requirements to install
+    #[structopt(name = "advise-image")]
+    AdviseImage {
+        /// R packages
+        #[structopt(required = true)]
+        packages: Vec<String>,
+    },
+
+    /// Run the install commands of R packages' system requirements in a
+    /// disposable container of the OS, reporting each step
+    #[structopt(name = "test")]
+    Test {
+        /// Container image [default: the official image of the OS]
+        #[structopt(long)]
+        image: Option<String>,
+
+        /// Container runtime, e.g. docker or podman [default: the first of
+        /// these installed]
+        #[structopt(long)]
+        runtime: Option<String>,
+
+        /// R packages
+        #[structopt(required = true)]
+        packages: Vec<String>,
+    },
+
+    /// Run the install commands of R packages' system requirements on this
+    /// machine, exiting with the exit code of the first failing
+    #[structopt(name = "install")]
+    Install {
+        /// Print the commands instead of running them
+        #[structopt(long)]
+        dry_run: bool,
+
+        /// Run the commands with sudo unless running as root
+        #[structopt(long)]
+        sudo: bool,
+
+        /// Run the commands without asking for confirmation
+        #[structopt(short, long)]
+        yes: bool,
+
+        /// R packages
+        #[structopt(required = true)]
+        packages: Vec<String>,
+    },
+
+    /// Sign files, such as saved install scripts, with --sign and --sign-key
+    #[structopt(name = "sign")]
+    Sign {
+        /// Files to sign
+        #[structopt(required = true, parse(from_os_str))]
+        files: Vec<PathBuf>,
+    },
+
+    /// Check that the system requirements of R packages are installed on
+    /// this machine, at the versions of the --constraints file, failing when
+    /// any are missing or outdated; with --signature, check the signatures of
+    /// files instead, and that they are unchanged since their --metadata was
+    /// written
+    #[structopt(name = "verify")]
+    Verify {
+        /// Check the signatures of the files given instead of R packages
+        #[structopt(long)]
+        signature: bool,
+
+        /// Public key to check signatures with, needed unless they are
+        /// keyless cosign signatures
+        #[structopt(long, parse(from_os_str), requires = "signature")]
+        key: Option<PathBuf>,
+
+        /// Identity in the certificate of keyless cosign signatures, e.g. an
+        /// email address
+        #[structopt(long = "certificate-identity", requires = "signature")]
+        certificate_identity: Option<String>,
+
+        /// OIDC issuer of the certificate of keyless cosign signatures
+        #[structopt(long = "certificate-oidc-issuer", requires = "signature")]
+        certificate_oidc_issuer: Option<String>,
+
+        /// R packages, or signed files with --signature
+        #[structopt(required = true)]
+        targets: Vec<String>,
+    },
+
+    /// Write a .devcontainer with the system requirements of R packages baked in
+    #[structopt(name = "devcontainer")]
+    Devcontainer {
+        /// Base image; the OS of images other than rocker's must be given
+        /// with --os-name and --os-version
+        #[structopt(long, default_value = "rocker/r-ver:latest")]
+        image: String,
+
+        /// Directory to write to
+        #[structopt(long, default_value = ".devcontainer", parse(from_os_str))]
+        output: PathBuf,
+
+        /// Overwrite existing files
+        #[structopt(long)]
+        force: bool,
+
+        /// Write a multi-stage Dockerfile that keeps the system requirements in
+        /// a layer of their own, with build arguments for the base image,
+        /// extra system packages and the R packages
+        #[structopt(long)]
+        layered: bool,
+
+        /// R packages
+        #[structopt(required = true)]
+        packages: Vec<String>,
+    },
+
+    /// Set up a project: write its configuration file with the global
+    /// options given, and optionally a lockfile and a Dockerfile fragment for
+    /// the R packages of its renv.lock, DESCRIPTION or Quarto project
+    #[structopt(name = "init")]
+    Init {
+        /// Overwrite existing files
+        #[structopt(long)]
+        force: bool,
+
+        /// Include the Suggests of a DESCRIPTION
+        #[structopt(long)]
+        suggests: bool,
+
+        /// Also write the lockfile
+        #[structopt(long)]
+        lock: bool,
+
+        /// Also write a Dockerfile fragment installing the system requirements
+        #[structopt(long)]
+        dockerfile: bool,
+    },
+
+    /// Read and write the configuration file holding defaults for the global
+    /// options
+    #[structopt(name = "config")]
+    Config {
+        /// Use the configuration file of the project in the current directory
+        #[structopt(long)]
+        project: bool,
+
+        #[structopt(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Inspect and manage the cache of server responses
+    #[structopt(name = "cache")]
+    Cache {
+        #[structopt(subcommand)]
+        action: CacheAction,
+    },
+
+    /// Search the system requirements rules by rule name or system package,
+    /// showing the system packages by distribution and the R packages needing
+    /// them
+    #[structopt(name = "search")]
+    Search {
+        /// Part of a rule name or system package, e.g. gdal
+        query: String,
+
+        /// List all R packages of each rule rather than the first few
+        #[structopt(long)]
+        all: bool,
+    },
+
+    /// Manage the local system requirements database
+    #[structopt(name = "db")]
+    Db {
+        #[structopt(subcommand)]
+        action: DbAction,
+    },
+
+    /// Serve a local caching proxy of the server's API
+    #[structopt(name = "serve")]
+    Serve {
+        /// Address to listen on
+        #[structopt(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+
+        /// Seconds to keep cached responses
+        #[structopt(long, default_value = "3600")]
+        ttl: u64,
+
+        /// Number of threads handling requests
+        #[structopt(long, default_value = "4")]
+        workers: usize,
+    },
+
+    /// Print JSON Schemas of the machine-readable outputs
+    #[structopt(name = "schema")]
+    Schema {
+        /// Document name (default: all documents)
+        #[structopt()]
+        name: Option<String>,
+    },
+
+    /// Print a completion script for a shell: bash, zsh, fish, powershell or
+    /// elvish
+    #[structopt(name = "completions")]
+    Completions {
+        /// Shell to complete in
+        #[structopt(possible_values = &Shell::variants(), case_insensitive = true)]
+        shell: Shell,
+    },
+
+    /// Print the man page, in roff
+    #[structopt(name = "manpage")]
+    Manpage,
+}
+
+#[derive(StructOpt, Debug)]
+struct PackageOpt {
+    /// R packages
+    #[structopt()]
+    packages: Vec<String>,
+
+    /// Show the changes from this lockfile instead of the requirements
+    #[structopt(long, parse(from_os_str))]
+    against: Option<PathBuf>,
+
+    /// Show the requirements on two OS releases side by side instead, e.g.
+    /// ubuntu-22.04,centos-8
+    #[structopt(
+        long = "diff-os",
+        use_delimiter = true,
+        min_values = 2,
+        max_values = 2,
+        parse(try_from_str = parse_os)
+    )]
+    diff_os: Vec<(String, String)>,
+
+    /// Show the requirements on all OS releases the server supports side by
+    /// side instead
+    #[structopt(long = "all-os", conflicts_with = "diff-os")]
+    all_os: bool,
+
+    /// Files listing R packages to add, one per line
+    #[structopt(long = "packages-file", parse(from_os_str), number_of_values = 1)]
+    packages_files: Vec<PathBuf>,
+
+    /// Quarto project directory whose documents' R packages to add
+    #[structopt(long, parse(from_os_str))]
+    quarto: Option<PathBuf>,
+
+    /// DESCRIPTION file whose Depends, Imports and LinkingTo packages to add
+    #[structopt(long, parse(from_os_str))]
+    description: Option<PathBuf>,
+
+    /// Also add the Suggests packages of --description
+    #[structopt(long, requires = "description")]
+    suggests: bool,
+
+    /// renv.lock whose CRAN and Bioconductor packages to add, queried
+    /// --chunk-size at a time
+    #[structopt(long = "renv-lock", parse(from_os_str))]
+    renv_lock: Option<PathBuf>,
+
+    /// Fail when a package is not found in the repository
+    #[structopt(long)]
+    strict: bool,
+
+    /// Fail when a package has no system requirements entry, listing which
+    /// of them the repository doesn't know
+    #[structopt(long = "require-sysreqs")]
+    require_sysreqs: bool,
+
+    /// Annotate the output with the R packages requiring each system package
+    /// and the server and snapshot they were resolved from
+    #[structopt(long, conflicts_with = "eval")]
+    provenance: bool,
+
+    /// Print the install commands as one line without comments, for `eval
+    /// "$(r-sysdeps package --eval ...)"`, instead of in --format
+    #[structopt(long)]
+    eval: bool,
+
+    /// Exit with status 10 when any system requirements are found, and 0
+    /// when none are, e.g. to skip a privileged install step
+    #[structopt(long = "exit-code")]
+    exit_code: bool,
+
+    /// Number of packages per request [default: 100]
+    #[structopt(long = "chunk-size")]
+    chunk_size: Option<usize>,
+
+    /// Continue a failed query from its last completed chunk
+    #[structopt(long)]
+    resume: bool,
+
+    /// Output format: text, bspm to install R packages available as system
+    /// packages with the system package manager, pak for the JSON of
+    /// pak::pkg_sysreqs(), containerit for system packages by R package, or
+    /// nix-shell and nix-flake for a shell.nix or flake.nix providing R and
+    /// the system libraries, spack for a spack.yaml environment, or winget
+    /// and choco for Windows install commands, satisfy for an `apt-get
+    /// satisfy` command, packer and packer-json for a Packer shell
+    /// provisioner, k8s for a Kubernetes init container checking --image,
+    /// vagrant for a Vagrant shell provisioner, or databricks for a Databricks
+    /// cluster init script (for Ubuntu 22.04 unless --os-name is given), or
+    /// conda and brew to install into user space without root, or phases
+    /// for the system packages, pre-install, install and post-install
+    /// scripts in sections between marker lines, json and yaml for these by
+    /// R package, or dockerfile for RUN instructions to paste into a
+    /// Dockerfile
+    #[structopt(long, default_value = "text")]
+    format: Format,
+
+    /// List all system packages in one install command, for --format text
+    #[structopt(long)]
+    merged: bool,
+
+    /// Phases to leave out of --format phases: system-packages, pre-install,
+    /// install or post-install
+    #[structopt(long = "skip-phase", use_delimiter = true, number_of_values = 1)]
+    skip_phases: Vec<Phase>,
+
+    /// Also report which packages r2u provides as Ubuntu binaries
+    #[structopt(long)]
+    r2u: bool,
+
+    /// r2u apt repository
+    #[structopt(long = "r2u-url", default_value = r2u::R2U_URL)]
+    r2u_url: String,
+
+    /// Version constraint of a system package for --format satisfy, e.g.
+    /// 'libgdal-dev>=3.4', replacing any of the --constraints file
+    #[structopt(long = "constraint", number_of_values = 1, parse(try_from_str = constraints::parse))]
+    constraints: Vec<(String, String)>,
+
+    /// Report known vulnerabilities of the system packages from OSV
+    #[structopt(long)]
+    osv: bool,
+
+    /// OSV API server
+    #[structopt(long = "osv-url", default_value = osv::OSV_URL)]
+    osv_url: String,
+
+    /// Report the versions of the system packages in the OS release from
+    /// Repology, and whether they are outdated
+    #[structopt(long)]
+    repology: bool,
+
+    /// Repology server
+    #[structopt(long = "repology-url", default_value = repology::REPOLOGY_URL)]
+    repology_url: String,
+
+    /// Image of the R container, for --format k8s
+    #[structopt(long, default_value = "rocker/r-ver:latest")]
+    image: String,
+}
+
+#[derive(StructOpt, Debug)]
+enum ConfigAction {
+    /// Print the value of a setting, exiting non-zero when it is not set
+    #[structopt(name = "get")]
+    Get { key: String },
+
+    /// Set a setting, checking its value
+    #[structopt(name = "set")]
+    Set { key: String, value: String },
+
+    /// Remove a setting
+    #[structopt(name = "unset")]
+    Unset { key: String },
+
+    /// List the settings of the configuration file
+    #[structopt(name = "list")]
+    List {
+        /// List all known settings with their descriptions
+        #[structopt(long)]
+        all: bool,
+    },
+
+    /// Open the configuration file in $VISUAL or $EDITOR, checking it once
+    /// saved
+    #[structopt(name = "edit")]
+    Edit,
+
+    /// Write a configuration file with all settings commented out, to fill in
+    #[structopt(name = "init")]
+    Init {
+        /// Replace an existing configuration file
+        #[structopt(long)]
+        force: bool,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum CacheAction {
+    /// Show the number, size and age of cached responses by server
+    #[structopt(name = "stats")]
+    Stats,
+
+    /// Remove all cached responses and resume progress
+    #[structopt(name = "clear")]
+    Clear,
+
+    /// Print the cache directory
+    #[structopt(name = "path")]
+    Path,
+
+    /// Remove cached responses older than a given age
+    #[structopt(name = "prune")]
+    Prune {
+        /// Age beyond which responses are removed, e.g. 12h or 7days
+        #[structopt(long = "older-than", default_value = "24h", parse(try_from_str = humantime::parse_duration))]
+        older_than: Duration,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum DbAction {
+    /// Download the latest rules and package metadata, used when the server is unreachable
+    #[structopt(name = "update")]
+    Update {
+        /// Archive of the rstudio/r-system-requirements repository
+        #[structopt(long = "rules-url", default_value = rules::RULES_URL)]
+        rules_url: String,
+
+        /// PACKAGES file listing SystemRequirements [default: the repository's PACKAGES]
+        #[structopt(long = "packages-url")]
+        packages_url: Option<String>,
+
+        /// File to write the database to [default: the user's data directory]
+        #[structopt(long, parse(from_os_str))]
+        output: Option<PathBuf>,
     },
 }
 
@@ -39,288 +623,2578 @@ struct Opt {
     #[structopt(long = "os-name")]
     os_name: Option<String>,
 
+    /// Architecture of binary packages, e.g. x86_64 or aarch64 [auto-detected]
+    #[structopt(long)]
+    arch: Option<String>,
+
+    /// Snapshot of the repository to pin its URLs to, as a date YYYY-MM-DD
+    /// or a transaction ID, for reproducible builds [default: latest]
+    #[structopt(long, parse(try_from_str = parse_snapshot))]
+    snapshot: Option<String>,
+
     /// Operating System version [auto-detected]
     #[structopt(long = "os-version")]
     os_version: Option<String>,
 
+    /// R version, e.g. 4.3.2 or latest [default: rig's default R if rig is
+    /// installed, else latest]
+    #[structopt(long = "r-version")]
+    r_version: Option<String>,
+
     /// RStudio Package Manager Server
-    #[structopt(long = "server", default_value = "https://packagemanager.rstudio.com")]
+    #[structopt(
+        long = "server",
+        default_value = "https://packagemanager.rstudio.com",
+        parse(try_from_str = api::normalize_server)
+    )]
     server: String,
 
+    /// User and password to authenticate to the server with, as user:password,
+    /// taking precedence over those in the server URL
+    #[structopt(long, env = "R_SYSDEPS_USER", hide_env_values = true)]
+    user: Option<String>,
+
+    /// API token to authenticate to the server with, instead of a user
+    #[structopt(
+        long,
+        env = "R_SYSDEPS_TOKEN",
+        hide_env_values = true,
+        conflicts_with = "user"
+    )]
+    token: Option<String>,
+
+    /// PEM file of certificates to trust in addition to the bundled ones,
+    /// e.g. of a private CA or a TLS-intercepting proxy
+    #[structopt(long, parse(from_os_str), conflicts_with = "insecure")]
+    cacert: Option<PathBuf>,
+
+    /// Do not verify the certificates of servers; for testing only
+    #[structopt(long)]
+    insecure: bool,
+
+    /// Header to send with requests to the server, as KEY=VALUE, e.g. for a
+    /// proxy in front of it
+    #[structopt(long = "header", number_of_values = 1, parse(try_from_str = parse_header))]
+    headers: Vec<(String, String)>,
+
     /// Repository name (case-sensitive, default value: specified by server)
     #[structopt(short, long)]
     repository: Option<String>,
 
+    /// Repository type, e.g. R, Bioconductor or Python [default: R or Bioconductor]
+    #[structopt(long = "repository-type")]
+    repository_type: Option<String>,
+
+    /// TOML file renaming system packages, under [rename], and adding system
+    /// packages to R packages, under [extra]
+    #[structopt(long, parse(from_os_str))]
+    overrides: Option<PathBuf>,
+
+    /// File of version constraints of system packages, one per line, e.g.
+    /// 'libgdal-dev >= 3.4', pinned in the install commands
+    #[structopt(long, parse(from_os_str))]
+    constraints: Option<PathBuf>,
+
+    /// System packages to leave out, as they are provided otherwise, e.g. by
+    /// the base image
+    #[structopt(long, use_delimiter = true, number_of_values = 1)]
+    exclude: Vec<String>,
+
+    /// Package manager to install with instead of the one of the server's
+    /// scripts: apt, apt-get, nala, dnf, dnf5, yum, zypper or apk
+    #[structopt(long = "pkg-manager")]
+    pkg_manager: Option<PackageManager>,
+
+    /// Number of requests sent at once, e.g. for the chunks of large queries
+    #[structopt(
+        long,
+        visible_alias = "concurrency",
+        default_value = "4",
+        parse(try_from_str = parse_jobs)
+    )]
+    jobs: usize,
+
+    /// Maximum number of requests per second sent to the server
+    #[structopt(long = "max-rps", parse(try_from_str = parse_rate))]
+    max_rps: Option<f64>,
+
+    /// Time budget for all requests of the invocation, e.g. 30s or 5m
+    #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+    deadline: Option<Duration>,
+
+    /// Timeout of each request to the server, e.g. 30s [default: 10s, or 60s
+    /// for system requirements]
+    #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+    timeout: Option<Duration>,
+
+    /// Times a rate-limited (429), failing (5xx) or interrupted request is
+    /// retried
+    #[structopt(long, default_value = "3")]
+    retries: u32,
+
+    /// Delay before the first retry, doubled for each next one and shortened
+    /// by up to half at random; a server's Retry-After takes precedence
+    #[structopt(long = "retry-backoff", default_value = "1s", parse(try_from_str = humantime::parse_duration))]
+    retry_backoff: Duration,
+
+    /// How long server responses are reused from the cache before asking
+    /// the server again, e.g. 10m or 1day
+    #[structopt(long = "cache-ttl", default_value = "1h", parse(try_from_str = humantime::parse_duration))]
+    cache_ttl: Duration,
+
+    /// Never contact the server: resolve with the local system requirements
+    /// database (see `db update`), and use cached responses only, however old
+    #[structopt(long)]
+    offline: bool,
+
+    /// Neither read nor write the cache of server responses
+    #[structopt(long = "no-cache", conflicts_with = "refresh")]
+    no_cache: bool,
+
+    /// Ask the server again instead of using cached responses, updating the
+    /// cache
+    #[structopt(long)]
+    refresh: bool,
+
+    /// Report the duration of OS detection, each request and chunk, and
+    /// rendering as JSON on stderr when done
+    #[structopt(long)]
+    timings: bool,
+
+    /// Write a <file>.meta.json with the tool version, server, repository,
+    /// OS, time and a hash of the R packages next to each file written
+    #[structopt(long)]
+    metadata: bool,
+
+    /// Sign the files written with minisign or cosign
+    #[structopt(long)]
+    sign: Option<signing::Tool>,
+
+    /// Secret key to sign with [default: that of the signing tool, or keyless
+    /// for cosign]
+    #[structopt(long = "sign-key", parse(from_os_str))]
+    sign_key: Option<PathBuf>,
+
+    /// Save all server responses to this directory, to rerun the invocation
+    /// with --replay
+    #[structopt(long, parse(from_os_str), conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Answer all requests from the responses saved by --record to this
+    /// directory, without network access
+    #[structopt(long, parse(from_os_str))]
+    replay: Option<PathBuf>,
+
+    /// Log format: human or json (warnings only, and requests of serve, unless
+    /// filtered with RUST_LOG, e.g. RUST_LOG=info)
+    #[structopt(long = "log-format", default_value = "human")]
+    log_format: LogFormat,
+
     /// Action
     #[structopt(subcommand)]
     action: Action,
 }
 
-#[derive(Debug, Deserialize)]
-struct APIStatusResponse {
-    version: String,
-    build_date: String,
-    r_configured: bool,
-    binaries_enabled: bool,
-    distros: Vec<APIDistribution>,
-    cran_repo: String,
-    bioc_versions: Vec<APIBioConductorVersion>,
+#[derive(Debug, Clone, Copy)]
+enum LogFormat {
+    Human,
+    Json,
 }
 
-#[derive(Debug, Deserialize)]
-struct APIDistribution {
-    #[serde(rename = "binaryDisplay")]
-    binary_display: String,
-    #[serde(rename = "binaryURL")]
-    binary_url: String,
-    display: String,
-    distribution: String,
-    release: String,
-    #[serde(rename = "sysReqs")]
-    sys_reqs: bool,
-    binaries: bool,
-}
+impl FromStr for LogFormat {
+    type Err = String;
 
-#[derive(Debug, Deserialize)]
-struct APIBioConductorVersion {
-    bioc_version: String,
-    r_version: String,
-    cran_snapshot: String,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(LogFormat::Human),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!(
+                "unknown log format '{}', expected human or json",
+                s
+            )),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct APIRepository {
-    id: u64,
-    name: String,
-    description: Option<String>,
-    #[serde(rename = "type")]
-    language: String,
+fn parse_rate(s: &str) -> Result<f64> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        _ => bail!("invalid rate '{}', expected a positive number", s),
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct APISysReqs {
-    requirements: Vec<APIRequirement>,
+fn parse_header(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((name, value))
+            if !name.trim().is_empty()
+                && !name.contains(|c: char| c.is_whitespace() || c == ':') =>
+        {
+            Ok((name.to_string(), value.to_string()))
+        }
+        _ => bail!("invalid header '{}', expected KEY=VALUE", s),
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct APIRequirement {
-    name: String,
-    requirements: APIPackageRequirements,
+fn parse_snapshot(s: &str) -> Result<String> {
+    repository::check_snapshot(s)?;
+    Ok(s.to_string())
 }
 
-#[derive(Debug, Deserialize)]
-struct APIPackageRequirements {
-    packages: Vec<String>,
-    pre_install: Option<Vec<APIPrePost>>,
-    install_scripts: Vec<String>,
-    post_install: Option<Vec<APIPrePost>>,
+fn parse_jobs(s: &str) -> Result<usize> {
+    match s.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => bail!(
+            "invalid number of jobs '{}', expected a positive whole number",
+            s
+        ),
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct APIPrePost {
-    command: String,
-    script: String,
+/// Parse an OS release such as `ubuntu-22.04` or `opensuse-leap-15.5`.
+fn parse_os(s: &str) -> Result<(String, String)> {
+    match s.rsplit_once('-') {
+        Some((distribution, release)) if !distribution.is_empty() && !release.is_empty() => {
+            Ok((distribution.to_string(), release.to_string()))
+        }
+        _ => bail!(
+            "invalid OS '{}', expected a distribution and release like ubuntu-22.04",
+            s
+        ),
+    }
 }
 
-fn main() -> Result<()> {
-    let opt: Opt = Opt::from_args();
-    let (distribution, release) = detect_os(opt.os_name, opt.os_version)?;
-    let rspm_status = server_status(&opt.server)?;
-    let repositories = server_repositories(&opt.server)?;
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Text,
+    Bspm,
+    Pak,
+    Containerit,
+    NixShell,
+    NixFlake,
+    Spack,
+    Winget,
+    Choco,
+    Satisfy,
+    Packer,
+    PackerJson,
+    K8s,
+    Vagrant,
+    Databricks,
+    Conda,
+    Brew,
+    Phases,
+    Json,
+    Yaml,
+    Dockerfile,
+}
 
-    let repository_name = opt.repository.unwrap_or(rspm_status.cran_repo);
-    let repository = repositories
-        .iter()
-        .filter(|&repo| repo.name == repository_name)
-        .take(1)
-        .next()
-        .ok_or(anyhow!(
-            "Specified repository '{}' does not exist on the server",
-            repository_name
-        ))?;
+#[derive(Debug, Clone, Copy)]
+enum StatusFormat {
+    Text,
+    Json,
+}
 
-    match opt.action {
-        Action::Package { packages } => {
-            let response = server_sysreqs(
-                &opt.server,
-                &distribution,
-                &release,
-                repository.id,
-                &packages,
-            )
-            .with_context(|| "failed to do get system requirements")?;
+impl FromStr for StatusFormat {
+    type Err = String;
 
-            for req in response.requirements {
-                println!("# R package: {}", req.name);
-                println!(
-                    "## System libraries: {}",
-                    req.requirements.packages.join(", ")
-                );
-                if let Some(pre_install) = req.requirements.pre_install {
-                    pre_install.iter().for_each(|p| println!("{}", p.script));
-                }
-                req.requirements
-                    .install_scripts
-                    .iter()
-                    .for_each(|script| println!("{}", script));
-                if let Some(post_install) = req.requirements.post_install {
-                    post_install.iter().for_each(|p| println!("{}", p.script));
-                }
-                println!();
-            }
-        }
-        Action::Repository {
-            list,
-            binary_repository,
-            source_repository,
-        } => {
-            if list {
-                for repo in repositories.iter() {
-                    println!("{}", repo.name);
-                }
-            } else if source_repository {
-                println!("{}/{}/latest", opt.server, repository_name);
-            } else if binary_repository {
-                let distro = rspm_status
-                    .distros
-                    .iter()
-                    .filter(|distro| {
-                        distro.distribution == distribution && release.starts_with(&distro.release)
-                    })
-                    .take(1)
-                    .next()
-                    .ok_or(anyhow!(
-                        "server does not support OS {}-{}",
-                        distribution,
-                        release,
-                    ))?;
-
-                if !rspm_status.binaries_enabled {
-                    bail!("binary repositories not enabled on server")
-                } else if !distro.binaries {
-                    bail!(
-                        "binary repositories not enabled for {}-{}",
-                        distribution,
-                        release
-                    )
-                } else {
-                    println!(
-                        "{}/{}/__linux__/{}/latest",
-                        opt.server, repository_name, distro.binary_url
-                    );
-                }
-            }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(StatusFormat::Text),
+            "json" => Ok(StatusFormat::Json),
+            _ => Err(format!("unknown format '{}', expected text or json", s)),
         }
     }
-
-    Ok(())
 }
 
-fn server_sysreqs(
-    server: &String,
-    distribution: &String,
-    release: &String,
-    repo_id: u64,
-    packages: &Vec<String>,
-) -> Result<APISysReqs> {
-    let mut u = url::Url::parse_with_params(
-        format!("{}/__api__/repos/{}/sysreqs", server, repo_id).as_str(),
-        &[("distribution", distribution), ("release", release)],
-    )
-    .with_context(|| "failed to construct server URL")?;
-    for pkgname in packages {
-        u.query_pairs_mut().append_pair("pkgname", pkgname);
-    }
-
-    let http_response = minreq::get(u.as_str())
-        .with_timeout(60)
-        .send()
-        .with_context(|| format!("failed to reach server {}", server))?;
-    if http_response.status_code < 200 || http_response.status_code > 299 {
-        bail!(format!(
-            "failed to reach {}/__api__/repos/{}/sysreqs",
-            server, repo_id
-        ));
+impl Format {
+    /// The ecosystem whose package names the format lists, when these are
+    /// translated from the reference distribution's.
+    fn target(&self) -> Option<translate::Target> {
+        match self {
+            Format::NixShell | Format::NixFlake => Some(translate::Target::Nix),
+            Format::Spack => Some(translate::Target::Spack),
+            Format::Winget => Some(translate::Target::Winget),
+            Format::Choco => Some(translate::Target::Choco),
+            Format::Conda => Some(translate::Target::Conda),
+            Format::Brew => Some(translate::Target::Brew),
+            _ => None,
+        }
     }
 
-    let api_response = http_response.json().with_context(|| {
-        format!(
-            "failed to parse JSON response from {}/__api__/repos/{}/sysreqs",
-            server, repo_id
+    /// Whether the format is JSON, which can't carry comments.
+    fn is_json(&self) -> bool {
+        matches!(
+            self,
+            Format::Pak | Format::Containerit | Format::PackerJson | Format::Json
         )
-    })?;
-
-    Ok(api_response)
+    }
 }
 
-fn server_repositories(server: &String) -> Result<Vec<APIRepository>> {
-    let http_response = minreq::get(format!("{}/__api__/repos", server))
-        .with_timeout(10)
-        .send()
-        .with_context(|| format!("failed to reach server {}", server))?;
+impl FromStr for Format {
+    type Err = String;
 
-    if http_response.status_code < 200 || http_response.status_code > 299 {
-        bail!(format!("failed to reach {}/__api__/repos", server));
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "bspm" => Ok(Format::Bspm),
+            "pak" => Ok(Format::Pak),
+            "containerit" => Ok(Format::Containerit),
+            "nix-shell" => Ok(Format::NixShell),
+            "nix-flake" => Ok(Format::NixFlake),
+            "spack" => Ok(Format::Spack),
+            "winget" => Ok(Format::Winget),
+            "choco" => Ok(Format::Choco),
+            "satisfy" => Ok(Format::Satisfy),
+            "packer" => Ok(Format::Packer),
+            "packer-json" => Ok(Format::PackerJson),
+            "k8s" => Ok(Format::K8s),
+            "vagrant" => Ok(Format::Vagrant),
+            "databricks" => Ok(Format::Databricks),
+            "conda" => Ok(Format::Conda),
+            "brew" => Ok(Format::Brew),
+            "phases" => Ok(Format::Phases),
+            "json" => Ok(Format::Json),
+            "yaml" => Ok(Format::Yaml),
+            "dockerfile" => Ok(Format::Dockerfile),
+            _ => Err(format!(
+                "unknown format '{}', expected text, bspm, pak, containerit, nix-shell, nix-flake, spack, winget, choco, satisfy, packer, packer-json, k8s, vagrant, databricks, conda, brew, phases, json, yaml or dockerfile",
+                s
+            )),
+        }
     }
-
-    let api_response = http_response.json().with_context(|| {
-        format!(
-            "failed to parse JSON response from {}/__api__/repos",
-            server
-        )
-    })?;
-
-    Ok(api_response)
 }
 
-fn server_status(server: &String) -> Result<APIStatusResponse> {
-    let http_response = minreq::get(format!("{}/__api__/status", server))
-        .with_timeout(10)
-        .send()
-        .with_context(|| format!("failed to reach server {}", server))?;
-
-    if http_response.status_code < 200 || http_response.status_code > 299 {
-        bail!(format!("failed to reach {}/__api__/status", server));
-    }
+/// Log messages shown without RUST_LOG: warnings, and the requests `serve`
+/// handles, which are what a server is watched for.
+const DEFAULT_LOG_FILTER: &str = "warn,r_sysdeps::serve=info";
 
-    let api_response = http_response.json().with_context(|| {
-        format!(
-            "failed to parse JSON response from {}/__api__/status",
-            server
-        )
-    })?;
+/// Log to stderr, filtered by RUST_LOG, else [`DEFAULT_LOG_FILTER`]; API call
+/// spans are timed at debug level.
+fn init_logging(format: LogFormat) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(std::io::stderr);
 
-    Ok(api_response)
+    match format {
+        LogFormat::Human => builder.without_time().with_target(false).init(),
+        LogFormat::Json => builder.json().init(),
+    }
 }
 
-fn detect_os(os_name: Option<String>, os_version: Option<String>) -> Result<(String, String)> {
-    if let (Some(name), Some(version)) = (os_name, os_version) {
-        // user provided so just use it
-        return Ok((name, version));
+fn main() -> Result<()> {
+    let matches = Opt::clap().get_matches();
+    let mut opt = Opt::from_clap(&matches);
+    if !matches!(
+        opt.action,
+        Action::Config { .. } | Action::Completions { .. } | Action::Manpage
+    ) {
+        apply_config(&mut opt, &matches)?;
     }
-
-    let mut os_rename = HashMap::new();
-    os_rename.insert("rhel", "redhat");
-
-    let mut os_attributes = HashMap::new();
-
-    let os_release = std::fs::read_to_string("/etc/os-release")
-        .with_context(|| format!("failed to read /etc/os-release"))?;
-
-    // read key value pairs from file
-    os_release
-        .lines()
-        .map(|line| line.split("=").collect())
-        .filter(|parts: &Vec<_>| parts.len() == 2)
-        .for_each(|key_value| {
-            &os_attributes.insert(
-                key_value[0].replace("\"", ""),
-                key_value[1].replace("\"", ""),
-            );
-        });
-
-    match (os_attributes.get("ID"), os_attributes.get("VERSION_ID")) {
-        (Some(name), Some(version)) => Ok((String::from(name), String::from(version))),
-        _ => bail!("failed to detect linux distribution and/or version"),
+    init_logging(opt.log_format);
+    if opt.timings {
+        timings::enable();
+    }
+    if let Some(max_rps) = opt.max_rps {
+        api::set_max_rps(max_rps);
+    }
+    if let Some(deadline) = opt.deadline {
+        api::set_deadline(deadline);
+    }
+    if let Some(timeout) = opt.timeout {
+        api::set_timeout(timeout);
+    }
+    api::set_retries(opt.retries, opt.retry_backoff);
+    if opt.insecure {
+        warn!("not verifying the certificates of servers (--insecure)");
+    }
+    if opt.cacert.is_some() || opt.insecure {
+        api::set_tls(opt.cacert.as_deref(), opt.insecure)?;
+    }
+    jobs::set_jobs(opt.jobs);
+    cache::set_ttl(opt.cache_ttl);
+    if opt.offline {
+        api::set_offline();
+    }
+    if opt.no_cache {
+        cache::set_policy(cache::Policy::Bypass);
+    } else if opt.refresh {
+        cache::set_policy(cache::Policy::Refresh);
     }
+    let (server, credentials) = api::split_userinfo(&opt.server)?;
+    opt.server = server;
+    let credentials = match &opt.user {
+        Some(user) => match user.split_once(':') {
+            Some((user, password)) => Some((user.to_string(), password.to_string())),
+            None => bail!("invalid --user '{}', expected user:password", user),
+        },
+        None => credentials,
+    };
+    if let Some((user, password)) = credentials {
+        api::set_basic_auth(&opt.server, &user, &password)?;
+    }
+    if let Some(token) = &opt.token {
+        api::set_bearer_token(&opt.server, token)?;
+    }
+    for (name, value) in opt.headers.iter() {
+        api::add_header(&opt.server, name, value)?;
+    }
+    if let Some(dir) = &opt.record {
+        recording::record(dir)?;
+    }
+    if let Some(dir) = &opt.replay {
+        recording::replay(dir)?;
+    }
+
+    let result = match &opt.action {
+        Action::Package(args) => {
+            let mut merged = Merged::default();
+            merged.extend("the command line", &args.packages)?;
+            for path in args.packages_files.iter() {
+                let listed = r_sysdeps::packages::load(path)?;
+                merged.extend(&path.display().to_string(), &listed)?;
+            }
+            if let Some(dir) = &args.quarto {
+                let found = project::quarto_packages(dir)?;
+                debug!("found {} R packages in {}", found.len(), dir.display());
+                merged.extend(&format!("Quarto project {}", dir.display()), &found)?;
+            }
+            if let Some(path) = &args.description {
+                let text = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                let found = project::description_packages(&text, args.suggests)
+                    .with_context(|| format!("failed to read packages from {}", path.display()))?;
+                merged.extend(&path.display().to_string(), &found)?;
+            }
+            if let Some(path) = &args.renv_lock {
+                let text = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                let found = project::renv_lock_packages(&text)
+                    .with_context(|| format!("failed to read packages from {}", path.display()))?;
+                merged.extend(&path.display().to_string(), &found)?;
+            }
+            report_duplicates(&merged);
+            package(&opt, args, merged.names())
+        }
+        Action::Status { format } => {
+            let mut endpoints = Endpoints::new(&opt);
+            let status = endpoints.status()?;
+            match format {
+                StatusFormat::Text => out!("{}", render::status(status))?,
+                StatusFormat::Json => outln!("{}", serde_json::to_string_pretty(status)?)?,
+            }
+            Ok(())
+        }
+        Action::Matrix { binaries, pretty } => {
+            let mut endpoints = Endpoints::new(&opt);
+            let entries = matrix::entries(endpoints.status()?, *binaries);
+            if *pretty {
+                outln!("{}", serde_json::to_string_pretty(&entries)?)?;
+            } else {
+                outln!("{}", serde_json::to_string(&entries)?)?;
+            }
+            Ok(())
+        }
+        Action::VerifyManifest {
+            manifest,
+            project,
+            suggests,
+            update,
+        } => verify_manifest(&opt, manifest, project.as_deref(), *suggests, *update),
+        Action::Lock {
+            output,
+            project,
+            suggests,
+            update,
+            packages,
+        } => {
+            let (packages, mut versions) = if packages.is_empty() {
+                let project = project.clone().unwrap_or_else(project::default_path);
+                (
+                    project::read(&project, *suggests)?,
+                    project::versions(&project)?,
+                )
+            } else {
+                (merge_packages(packages)?, BTreeMap::new())
+            };
+            versions.retain(|name, _| packages.contains(name));
+            write_lockfile(&opt, output, &packages, versions, *update)
+        }
+        Action::Diff { old, new } => {
+            print_changes(&Lockfile::load(old)?.diff(&Lockfile::load(new)?))
+        }
+        Action::AnnotateRenv { lock, sidecar } => annotate_renv(&opt, lock, sidecar.as_deref()),
+        Action::AdviseImage { packages } => {
+            let packages = merge_packages(packages)?;
+            advise_image(&opt, &r_version(&opt)?, &packages)
+        }
+        Action::Test {
+            image,
+            runtime,
+            packages,
+        } => {
+            let packages = merge_packages(packages)?;
+            test_in_container(&opt, image.as_deref(), runtime.as_deref(), &packages)
+        }
+        Action::Install {
+            dry_run,
+            sudo,
+            yes,
+            packages,
+        } => {
+            let packages = merge_packages(packages)?;
+            install_packages(&opt, *dry_run, *sudo, *yes, &packages)
+        }
+        Action::Sign { files } => {
+            let tool = opt.sign.unwrap_or(signing::Tool::Minisign);
+            sign_files(tool, opt.sign_key.as_deref(), files)
+        }
+        Action::Verify {
+            signature: true,
+            key,
+            certificate_identity,
+            certificate_oidc_issuer,
+            targets,
+        } => verify_files(
+            &opt,
+            key.as_deref(),
+            certificate_identity.as_deref(),
+            certificate_oidc_issuer.as_deref(),
+            &targets.iter().map(PathBuf::from).collect::<Vec<_>>(),
+        ),
+        Action::Verify {
+            signature: false,
+            targets,
+            ..
+        } => {
+            let packages = merge_packages(targets)?;
+            check_installed(&opt, &packages)
+        }
+        Action::Devcontainer {
+            image,
+            output,
+            force,
+            layered,
+            packages,
+        } => {
+            let packages = merge_packages(packages)?;
+            write_devcontainer(&opt, image, output, *force, *layered, &packages)
+        }
+        Action::Repository {
+            list,
+            binary_repository,
+            source_repository,
+            rprofile,
+            bioc_version,
+            cran_snapshot,
+            bioc,
+        } => {
+            if *cran_snapshot {
+                let mut endpoints = Endpoints::new(&opt);
+                let status = endpoints.status()?;
+                let release = match bioc {
+                    Some(bioc) => repository::bioc_release(status, bioc)?,
+                    None => repository::bioc_version(status, &r_version(&opt)?)?,
+                };
+                outln!(
+                    "{}",
+                    repository::snapshot_url(
+                        &opt.server,
+                        status.cran_repo(),
+                        &release.cran_snapshot
+                    )
+                )?;
+                return Ok(());
+            }
+            if *bioc_version {
+                let r_version = r_version(&opt)?;
+                let mut endpoints = Endpoints::new(&opt);
+                let bioc = repository::bioc_version(endpoints.status()?, &r_version)?;
+                outln!("{}", bioc.bioc_version)?;
+                return Ok(());
+            }
+            repository(
+                &opt,
+                *list,
+                *binary_repository,
+                *source_repository,
+                *rprofile,
+            )
+        }
+        Action::Serve {
+            listen,
+            ttl,
+            workers,
+        } => serve::run(
+            &opt.server,
+            listen,
+            Cache::open(Duration::from_secs(*ttl))?,
+            *workers,
+        ),
+        Action::Schema { name } => print_schema(name.as_deref()),
+        Action::Completions { shell } => {
+            // clap panics on failed writes, so the script is written here
+            let mut script = Vec::new();
+            Opt::clap().gen_completions_to("r-sysdeps", *shell, &mut script);
+            std::io::stdout().lock().write_all(&script)?;
+            Ok(())
+        }
+        Action::Manpage => print_manpage(),
+        Action::Init {
+            force,
+            suggests,
+            lock,
+            dockerfile,
+        } => init_project(&opt, *force, *suggests, *lock, *dockerfile),
+        Action::Config { project, action } => manage_config(*project, action),
+        Action::Cache { action } => manage_cache(action),
+        Action::Search { query, all } => search(query, *all),
+        Action::Db {
+            action:
+                DbAction::Update {
+                    rules_url,
+                    packages_url,
+                    output,
+                },
+        } => db_update(&opt, rules_url, packages_url.as_deref(), output.as_deref()),
+    };
+
+    if opt.timings {
+        eprintln!("{}", serde_json::to_string(&timings::report())?);
+    }
+    match result {
+        Err(err) if is_broken_pipe(&err) => Ok(()),
+        Err(err) if err.is::<SysdepsRequired>() => std::process::exit(EXIT_SYSDEPS_REQUIRED),
+        Err(err) => match err.downcast_ref::<install::Failed>() {
+            Some(failed) => {
+                eprintln!("Error: {}", failed);
+                std::process::exit(failed.code.unwrap_or(1))
+            }
+            None => Err(err),
+        },
+        result => result,
+    }
+}
+
+/// Whether `err` comes from writing to a closed pipe, as when the output is
+/// cut short by `| head`, which is no failure.
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|err| err.kind() == std::io::ErrorKind::BrokenPipe)
+    })
+}
+
+/// The package names given on the command line, each once.
+fn merge_packages(packages: &[String]) -> Result<Vec<String>> {
+    let mut merged = Merged::default();
+    merged.extend("the command line", packages)?;
+    report_duplicates(&merged);
+    Ok(merged.names().to_vec())
+}
+
+/// Log the package names given more than once, which are queried once.
+fn report_duplicates(merged: &Merged) {
+    for line in merged.report().lines() {
+        info!("merged duplicate package {}", line);
+    }
+}
+
+/// Exit status of `package --exit-code` when system requirements were found.
+const EXIT_SYSDEPS_REQUIRED: i32 = 10;
+
+/// Returned by `package --exit-code` to exit with [`EXIT_SYSDEPS_REQUIRED`].
+#[derive(Debug)]
+struct SysdepsRequired;
+
+impl fmt::Display for SysdepsRequired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "system requirements found")
+    }
+}
+
+impl std::error::Error for SysdepsRequired {}
+
+/// `value` as pretty JSON.
+fn to_json<T: Serialize>(value: &T) -> Result<String> {
+    Ok(format!("{}\n", serde_json::to_string_pretty(value)?))
+}
+
+fn package(opt: &Opt, args: &PackageOpt, packages: &[String]) -> Result<()> {
+    if !args.skip_phases.is_empty() && !matches!(args.format, Format::Phases) {
+        bail!("--skip-phase needs --format phases");
+    }
+    if args.merged && !matches!(args.format, Format::Text) {
+        bail!("--merged needs --format text");
+    }
+    if args.all_os {
+        let targets: Vec<(String, String)> = Endpoints::new(opt)
+            .status()?
+            .distros
+            .iter()
+            .filter(|distro| distro.sys_reqs)
+            .map(|distro| (distro.distribution.clone(), distro.release.clone()))
+            .collect();
+        if targets.is_empty() {
+            bail!("server lists no OSes with system requirements");
+        }
+        return diff_os(opt, args, packages, &targets);
+    }
+    if !args.diff_os.is_empty() {
+        return diff_os(opt, args, packages, &args.diff_os);
+    }
+
+    let detected = match args.format.target() {
+        Some(_) => {
+            let (distribution, release) = translate::REFERENCE;
+            Ok((distribution.to_string(), release.to_string()))
+        }
+        None if matches!(args.format, Format::Databricks)
+            && opt.os_name.is_none()
+            && opt.os_version.is_none() =>
+        {
+            let (distribution, release) = provision::DATABRICKS_OS;
+            Ok((distribution.to_string(), release.to_string()))
+        }
+        None => {
+            let _timing = timings::start("detect-os", String::new);
+            os::detect(opt.os_name.clone(), opt.os_version.clone())
+        }
+    };
+    let (distribution, release, local) = match detected {
+        Ok((distribution, release)) => (distribution, release, false),
+        Err(err) => match err.downcast_ref::<os::UnversionedDistribution>() {
+            // the server needs a release, the local rules can do without
+            Some(unversioned) if rules::supports(&unversioned.distribution) => {
+                warn!("{}", err);
+                warn!("resolving with the local system requirements database instead");
+                (unversioned.distribution.clone(), String::new(), true)
+            }
+            _ => return Err(err),
+        },
+    };
+
+    let (response, repository_name) = if local {
+        (local_sysreqs(opt, &distribution, &release, packages)?, None)
+    } else {
+        resolve(
+            &mut Endpoints::new(opt),
+            args,
+            &distribution,
+            &release,
+            packages,
+        )?
+    };
+
+    let against = match &args.against {
+        Some(path) => {
+            let locked = Lockfile::load(path)?;
+            let repository = repository_name.as_deref().unwrap_or(&locked.repository);
+            let resolved = Lockfile::new(
+                repository_name
+                    .as_ref()
+                    .map_or(provenance::LOCAL_SOURCE, |_| &opt.server),
+                repository,
+                snapshot(opt),
+                &distribution,
+                &release,
+                packages,
+                &response,
+            );
+            Some(locked.diff(&resolved))
+        }
+        None => None,
+    };
+
+    let missing = missing_packages(packages, &response);
+    // the index is only worth fetching when unknown packages are reported
+    let reported = args.strict || args.require_sysreqs || tracing::enabled!(tracing::Level::WARN);
+    let unknown = match &repository_name {
+        Some(repository_name) if !missing.is_empty() && reported => {
+            match unknown_packages(opt, repository_name, &missing) {
+                Ok(unknown) => unknown,
+                Err(err) if args.strict => return Err(err),
+                Err(err) => {
+                    warn!("{:#}; not checking for unknown packages", err);
+                    Vec::new()
+                }
+            }
+        }
+        Some(_) => Vec::new(),
+        // resolved locally, where unknown packages would pass for having none
+        None => local_unknown_packages(&missing)?,
+    };
+    if !unknown.is_empty() {
+        let message = match &repository_name {
+            Some(repository_name) => format!(
+                "packages not found in repository '{}': {}",
+                repository_name,
+                describe_unknown(&unknown)
+            ),
+            None => format!(
+                "packages not in the local system requirements database: {}",
+                describe_unknown(&unknown)
+            ),
+        };
+        if args.strict {
+            bail!(message);
+        }
+        warn!("{}", message);
+    }
+    if args.require_sysreqs && !missing.is_empty() {
+        bail!(
+            "no system requirements metadata for: {}",
+            missing
+                .iter()
+                .map(
+                    |name| if unknown.iter().any(|(unknown, _)| unknown == name) {
+                        format!("{} (not in repository)", name)
+                    } else {
+                        name.clone()
+                    }
+                )
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    if let Some(changes) = against {
+        return print_changes(&changes);
+    }
+
+    let timing = timings::start("render", || format!("{:?}", args.format));
+    let provenance = if args.provenance {
+        Some(Provenance::new(
+            repository_name
+                .as_ref()
+                .map_or(provenance::LOCAL_SOURCE, |_| &opt.server),
+            repository_name.as_deref(),
+            repository_name.as_ref().map(|_| snapshot(opt)),
+            &distribution,
+            &release,
+            &response,
+        ))
+    } else {
+        None
+    };
+    let output = match args.format {
+        _ if args.eval => {
+            let (output, skipped) = render::eval(&response);
+            if !skipped.is_empty() {
+                warn!(
+                    "left out unrecognized install scripts:\n{}",
+                    skipped.join("\n")
+                );
+            }
+            output
+        }
+        Format::Text => {
+            let output = if args.merged {
+                render::merged(&response)
+            } else {
+                render::text(&response)
+            };
+            let local = opt.os_name.is_none() && opt.os_version.is_none();
+            if local
+                && !response.requirements.is_empty()
+                && !userspace::can_install_system_packages()
+            {
+                warn!("installing system packages needs root; without root or sudo, install into user space with --format conda or --format brew");
+            }
+            output
+        }
+        Format::Packer => provision::packer_hcl(&response),
+        Format::Vagrant => provision::vagrant(&response),
+        Format::Phases => render::phases(&response, &args.skip_phases),
+        Format::Json => to_json(&document::SysreqsDocument {
+            provenance: provenance.clone(),
+            ..document::from_sysreqs(&distribution, &release, &response)
+        })?,
+        Format::Dockerfile => render::dockerfile_fragment(&response),
+        Format::Yaml => document::yaml(&document::from_sysreqs(&distribution, &release, &response)),
+        Format::Databricks => provision::databricks(&response, packages),
+        Format::K8s => provision::k8s_init_container(&args.image, &distribution, &response)?,
+        Format::PackerJson => to_json(&provision::PackerTemplate {
+            provenance: provenance.clone(),
+            ..provision::packer_json(&response)
+        })?,
+        Format::Satisfy => {
+            let installers = response
+                .requirements
+                .iter()
+                .flat_map(|req| req.requirements.install_scripts.iter());
+            if let Some(script) = installers.clone().find(|script| !render::is_apt(script)) {
+                bail!(
+                    "--format satisfy needs apt, but {} {} installs with '{}'",
+                    distribution,
+                    release,
+                    script
+                );
+            }
+            for (name, _) in args.constraints.iter() {
+                let required = response
+                    .requirements
+                    .iter()
+                    .any(|req| req.requirements.packages.contains(name));
+                if !required {
+                    warn!("constraint on {} which is not a system requirement", name);
+                }
+            }
+            let mut constraints = match &opt.constraints {
+                Some(path) => constraints::load(path)?,
+                None => Vec::new(),
+            };
+            constraints
+                .retain(|(name, _)| !args.constraints.iter().any(|(given, _)| given == name));
+            constraints.extend(args.constraints.iter().cloned());
+            render::satisfy(&response, &constraints)
+        }
+        Format::Pak => to_json(&pak::PakSysreqs {
+            provenance: provenance.clone(),
+            ..pak::from_sysreqs(&distribution, &release, &response)
+        })?,
+        Format::Containerit => to_json(&containerit::ContaineritSysreqs {
+            provenance: provenance.clone(),
+            ..containerit::from_sysreqs(&distribution, packages, &response)
+        })?,
+        Format::NixShell
+        | Format::NixFlake
+        | Format::Spack
+        | Format::Winget
+        | Format::Choco
+        | Format::Conda
+        | Format::Brew => {
+            let target = args.format.target().unwrap();
+            let debs = response
+                .requirements
+                .iter()
+                .flat_map(|req| req.requirements.packages.iter());
+            let (packages, untranslated) = translate::packages(target, debs);
+            let windows = matches!(args.format, Format::Winget | Format::Choco);
+            if !untranslated.is_empty() && !windows {
+                warn!(
+                    "no {} equivalent known for: {}",
+                    target.name(),
+                    untranslated.join(", ")
+                );
+            }
+            match args.format {
+                Format::NixShell => nix::shell(&packages, &untranslated),
+                Format::NixFlake => nix::flake(&packages, &untranslated),
+                Format::Spack => spack::environment(&packages, &untranslated),
+                Format::Winget => windows::winget(&packages, &untranslated),
+                Format::Conda => userspace::conda(&packages, &untranslated),
+                Format::Brew => userspace::brew(&packages, &untranslated),
+                _ => windows::choco(&packages, &untranslated),
+            }
+        }
+        Format::Bspm => {
+            let (binaries, sources) = match os::codename(&distribution, &release) {
+                Some(codename) if distribution == "ubuntu" => {
+                    let available = r2u::available(&args.r2u_url, codename)?;
+                    let (binaries, sources) = r2u::split(packages, &available);
+                    let binaries = binaries.into_iter().map(|(_, apt)| apt).collect();
+                    (binaries, sources)
+                }
+                _ => {
+                    warn!(
+                        "no binary R packages known for {} {}, listing all packages as built from source",
+                        distribution, release
+                    );
+                    let sources = packages
+                        .iter()
+                        .filter(|name| !r_sysdeps::packages::is_base(name))
+                        .cloned()
+                        .collect();
+                    (Vec::new(), sources)
+                }
+            };
+            render::bspm("apt-get install -y", &binaries, &sources, &response)
+        }
+    };
+    match &provenance {
+        Some(provenance) if !args.format.is_json() => out!("{}", provenance.annotate(&output))?,
+        _ => out!("{}", output)?,
+    }
+    drop(timing);
+
+    if args.osv {
+        let ecosystem = osv::ecosystem(&distribution, &release)?;
+        let system_packages = system_packages(&response);
+        let local = opt.os_name.is_none() && opt.os_version.is_none();
+        let queries = osv::queries(&distribution, &system_packages, local);
+        if queries.iter().any(|query| query.version.is_none()) {
+            warn!("source packages of system packages that are not installed are guessed from their names, and all of their advisories reported");
+        }
+        let findings = osv::advisories(&args.osv_url, &ecosystem, queries)?;
+        out!("{}", osv::report(&ecosystem, &findings))?;
+    }
+
+    if args.repology {
+        let repo = repology::repository(&distribution, &release)?;
+        let versions = repology::versions(&args.repology_url, &repo, &system_packages(&response))?;
+        out!("{}", repology::report(&repo, &versions))?;
+    }
+
+    if args.r2u {
+        match os::codename(&distribution, &release) {
+            Some(codename) if distribution == "ubuntu" => {
+                let available = r2u::available(&args.r2u_url, codename)?;
+                out!("{}", r2u::report(codename, packages, &available))?;
+            }
+            _ => warn!(
+                "r2u only provides binaries for Ubuntu LTS releases, not {} {}",
+                distribution, release
+            ),
+        }
+    }
+
+    let required = response.requirements.iter().any(|req| {
+        !req.requirements.packages.is_empty() || !req.requirements.install_scripts.is_empty()
+    });
+    if args.exit_code && required {
+        return Err(SysdepsRequired.into());
+    }
+
+    Ok(())
+}
+
+/// The system packages of all requirements, without duplicates.
+fn system_packages(sysreqs: &APISysReqs) -> Vec<String> {
+    let mut packages: Vec<String> = Vec::new();
+    for package in sysreqs
+        .requirements
+        .iter()
+        .flat_map(|req| req.requirements.packages.iter())
+    {
+        if !packages.contains(package) {
+            packages.push(package.clone());
+        }
+    }
+
+    packages
+}
+
+/// Resolve with the server, falling back to the local rules when it is
+/// unreachable, in which case there is no repository name.
+fn resolve(
+    endpoints: &mut Endpoints,
+    args: &PackageOpt,
+    distribution: &str,
+    release: &str,
+    packages: &[String],
+) -> Result<(APISysReqs, Option<String>)> {
+    if endpoints.opt.offline {
+        let response = local_sysreqs(endpoints.opt, distribution, release, packages)?;
+        return Ok((response, None));
+    }
+    match resolve_sysreqs(
+        endpoints,
+        distribution,
+        release,
+        packages,
+        args.chunk_size.unwrap_or(client::CHUNK_SIZE),
+        args.resume,
+    ) {
+        Ok((response, source)) => Ok((response, Some(source.repository))),
+        Err(err) if api::is_unreachable(&err) => {
+            warn!("{} ({})", err, err.root_cause());
+            warn!("falling back to the local system requirements database, which may be out of date (refresh it with `r-sysdeps db update`)");
+            let response = local_sysreqs(endpoints.opt, distribution, release, packages)?;
+            Ok((response, None))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn diff_os(
+    opt: &Opt,
+    args: &PackageOpt,
+    packages: &[String],
+    targets: &[(String, String)],
+) -> Result<()> {
+    let mut endpoints = Endpoints::new(opt);
+    if let Some(status) = endpoints
+        .status()
+        .ok()
+        .filter(|status| status.lists_distros())
+    {
+        for (distribution, release) in targets.iter() {
+            repository::supported_distro(status, distribution, release)?;
+        }
+    }
+    let resolved = jobs::try_map(targets, |_, (distribution, release)| {
+        let (response, _) = resolve(
+            &mut endpoints.clone(),
+            args,
+            distribution,
+            release,
+            packages,
+        )?;
+        Ok((format!("{}-{}", distribution, release), response))
+    })?;
+
+    let oses: Vec<(&str, &APISysReqs)> = resolved
+        .iter()
+        .map(|(os, sysreqs)| (os.as_str(), sysreqs))
+        .collect();
+    out!("{}", render::os_diff(&oses))?;
+    Ok(())
+}
+
+/// Where system requirements were resolved.
+struct Source {
+    /// The server, or [`provenance::LOCAL_SOURCE`].
+    server: String,
+    /// The repository of the server, or the one the local rules stand in for.
+    repository: String,
+}
+
+/// Resolve with the server, or with the local rules when offline, along with
+/// where they were resolved.
+fn resolve_sysreqs(
+    endpoints: &mut Endpoints,
+    distribution: &str,
+    release: &str,
+    packages: &[String],
+    chunk_size: usize,
+    resume: bool,
+) -> Result<(APISysReqs, Source)> {
+    let opt = endpoints.opt;
+    if opt.offline {
+        let response = local_sysreqs(opt, distribution, release, packages)?;
+        let unknown = local_unknown_packages(&missing_packages(packages, &response))?;
+        if !unknown.is_empty() {
+            warn!(
+                "packages not in the local system requirements database, taken to have none: {}",
+                describe_unknown(&unknown)
+            );
+        }
+        let source = Source {
+            server: provenance::LOCAL_SOURCE.to_string(),
+            repository: opt
+                .repository
+                .as_deref()
+                .unwrap_or(DEFAULT_CRAN_REPO)
+                .to_string(),
+        };
+        return Ok((response, source));
+    }
+    if opt.os_name.is_some() || opt.os_version.is_some() {
+        let status = endpoints.status()?;
+        if status.lists_distros() {
+            repository::supported_distro(status, distribution, release)?;
+        }
+    }
+    let repository_name = endpoints.repository_name()?;
+    let repository = endpoints.repository(&repository_name)?;
+
+    let settings = api::settings();
+    let batch = Batch {
+        server: &opt.server,
+        distribution,
+        release,
+        repo_id: repository.id,
+        chunk_size,
+        settings: &settings,
+        persist: true,
+    };
+    let mut response = batch
+        .sysreqs(packages, resume)
+        .with_context(|| "failed to do get system requirements")?;
+    apply_overrides(opt, distribution, release, packages, &mut response)?;
+
+    let source = Source {
+        server: opt.server.clone(),
+        repository: repository_name,
+    };
+    Ok((response, source))
+}
+
+/// Resolve with the local rules database.
+fn local_sysreqs(
+    opt: &Opt,
+    distribution: &str,
+    release: &str,
+    packages: &[String],
+) -> Result<APISysReqs> {
+    let database = rules::Database::load()?;
+    let mut response = database.resolve(distribution, release, packages)?;
+    apply_overrides(opt, distribution, release, packages, &mut response)?;
+    Ok(response)
+}
+
+/// Apply the --overrides file, --exclude and the --constraints file to
+/// `sysreqs`.
+fn apply_overrides(
+    opt: &Opt,
+    distribution: &str,
+    release: &str,
+    packages: &[String],
+    sysreqs: &mut APISysReqs,
+) -> Result<()> {
+    if let Some(path) = &opt.overrides {
+        Overrides::load(path)?.apply(distribution, release, packages, sysreqs)?;
+    }
+    overrides::exclude(sysreqs, &opt.exclude);
+    if let Some(path) = &opt.constraints {
+        constraints::pin(sysreqs, &constraints::load(path)?)?;
+    }
+    if let Some(manager) = opt.pkg_manager {
+        manager.apply(sysreqs)?;
+    }
+    Ok(())
+}
+
+/// Server endpoints of one invocation, each fetched on first use only.
+#[derive(Clone)]
+struct Endpoints<'a> {
+    opt: &'a Opt,
+    status: Option<APIStatusResponse>,
+    repositories: Option<Vec<APIRepository>>,
+}
+
+impl<'a> Endpoints<'a> {
+    fn new(opt: &'a Opt) -> Self {
+        Endpoints {
+            opt,
+            status: None,
+            repositories: None,
+        }
+    }
+
+    fn status(&mut self) -> Result<&APIStatusResponse> {
+        if self.status.is_none() {
+            self.status = Some(
+                Client::new(&self.opt.server)?
+                    .with_settings(api::settings())
+                    .status()?,
+            );
+        }
+        Ok(self.status.as_ref().expect("status was fetched"))
+    }
+
+    /// The repository given by the user, or else the server's CRAN repository.
+    fn repository_name(&mut self) -> Result<String> {
+        match &self.opt.repository {
+            Some(name) => Ok(name.clone()),
+            None => Ok(self.status()?.cran_repo().to_string()),
+        }
+    }
+
+    /// The repositories of the server, cached for [`cache::ttl`].
+    fn repositories(&mut self) -> Result<&[APIRepository]> {
+        if self.repositories.is_none() {
+            self.repositories = Some(
+                Client::new(&self.opt.server)?
+                    .with_settings(api::settings())
+                    .repositories()?,
+            );
+        }
+        Ok(self
+            .repositories
+            .as_deref()
+            .expect("repositories were fetched"))
+    }
+
+    /// The repository named `name`, looked up in a recently cached listing
+    /// before asking the server.
+    fn repository(&mut self, name: &str) -> Result<APIRepository> {
+        let kind = self.opt.repository_type.as_deref();
+        if self.repositories.is_none() && recording::mode().is_none() {
+            let cached = Cache::open(REPOSITORIES_TTL)
+                .ok()
+                .and_then(|cache| cache.get(&api::repositories_url(&self.opt.server)))
+                .and_then(|body| serde_json::from_slice::<Vec<APIRepository>>(&body).ok());
+            if let Some(repository) = cached
+                .as_deref()
+                .and_then(|cached| repository::find(cached, name, kind).ok())
+            {
+                return Ok(repository.clone());
+            }
+        }
+
+        Ok(repository::find(self.repositories()?, name, kind)?.clone())
+    }
+}
+
+/// Requested packages without an entry in the response; base packages never have one.
+fn missing_packages(packages: &[String], response: &APISysReqs) -> Vec<String> {
+    packages
+        .iter()
+        .filter(|name| {
+            !r_sysdeps::packages::is_base(name)
+                && !response.requirements.iter().any(|req| &req.name == *name)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Packages of `missing` which are not in the repository index either, with
+/// the names of the index they were likely meant to be.
+///
+/// Names are case-sensitive, so names differing only in case are suggested
+/// first, and otherwise names a few edits away.
+fn unknown_packages(
+    opt: &Opt,
+    repository_name: &str,
+    missing: &[String],
+) -> Result<Vec<(String, Vec<String>)>> {
+    let index = api::package_index(&opt.server, repository_name).with_context(|| {
+        format!(
+            "failed to list packages of repository '{}'",
+            repository_name
+        )
+    })?;
+
+    Ok(missing
+        .iter()
+        .filter(|name| !index.contains(*name))
+        .map(|name| (name.clone(), suggestions(&index, name)))
+        .collect())
+}
+
+/// Names of the repository `index` that `name` was likely meant to be.
+/// Packages of `missing` which the local rules database does not know, with
+/// the names it does know they were likely meant to be.
+fn local_unknown_packages(missing: &[String]) -> Result<Vec<(String, Vec<String>)>> {
+    if missing.is_empty() {
+        return Ok(Vec::new());
+    }
+    let database = rules::Database::load()?;
+    let unknown = database.unknown(missing);
+    if unknown.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let index: BTreeSet<String> = database.packages.into_keys().collect();
+    Ok(unknown
+        .into_iter()
+        .map(|name| (name.to_string(), suggestions(&index, name)))
+        .collect())
+}
+
+/// Unknown packages as listed in messages, with their suggestions.
+fn describe_unknown(unknown: &[(String, Vec<String>)]) -> String {
+    unknown
+        .iter()
+        .map(|(name, suggestions)| {
+            if suggestions.is_empty() {
+                name.clone()
+            } else {
+                format!("{} (did you mean {}?)", name, suggestions.join(" or "))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn suggestions(index: &BTreeSet<String>, name: &str) -> Vec<String> {
+    let cased: Vec<String> = index
+        .iter()
+        .filter(|candidate| candidate.eq_ignore_ascii_case(name))
+        .cloned()
+        .collect();
+    if !cased.is_empty() {
+        return cased;
+    }
+    suggest::closest(name, index.iter().map(String::as_str))
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+fn repository(
+    opt: &Opt,
+    list: bool,
+    binary_repository: bool,
+    source_repository: bool,
+    rprofile: bool,
+) -> Result<()> {
+    let mut endpoints = Endpoints::new(opt);
+    if list {
+        for repo in endpoints.repositories()?.iter() {
+            outln!("{}", repo.name)?;
+        }
+        return Ok(());
+    }
+
+    let repository_name = endpoints.repository_name()?;
+    endpoints.repository(&repository_name)?;
+    if let Some(snapshot) = &opt.snapshot {
+        api::check_snapshot(&opt.server, &repository_name, snapshot)?;
+    }
+
+    if source_repository {
+        outln!(
+            "{}",
+            repository::snapshot_url(&opt.server, &repository_name, snapshot(opt))
+        )?;
+    } else if binary_repository {
+        let (distribution, release) = os::detect(opt.os_name.clone(), opt.os_version.clone())?;
+        outln!(
+            "{}",
+            repository::binary_url(
+                &opt.server,
+                &repository_name,
+                endpoints.status()?,
+                &distribution,
+                &release,
+                &arch(opt),
+                snapshot(opt)
+            )?
+        )?;
+    } else if rprofile {
+        let (distribution, release) = os::detect(opt.os_name.clone(), opt.os_version.clone())?;
+        let (url, linux_binaries) = match repository::binary_url(
+            &opt.server,
+            &repository_name,
+            endpoints.status()?,
+            &distribution,
+            &release,
+            &arch(opt),
+            snapshot(opt),
+        ) {
+            Ok(url) => (
+                url,
+                ![os::MACOS, os::WINDOWS].contains(&distribution.as_str()),
+            ),
+            Err(err) => {
+                warn!("{:#}, using source packages", err);
+                let url = repository::snapshot_url(&opt.server, &repository_name, snapshot(opt));
+                (url, false)
+            }
+        };
+        out!("{}", render::rprofile(&url, linux_binaries))?;
+    }
+
+    Ok(())
+}
+
+/// The repository snapshot given, else the latest.
+fn snapshot(opt: &Opt) -> &str {
+    opt.snapshot.as_deref().unwrap_or(repository::LATEST)
+}
+
+/// The architecture given, else that of the running system.
+fn arch(opt: &Opt) -> String {
+    match &opt.arch {
+        Some(arch) => os::arch(arch),
+        None => os::detect_arch(),
+    }
+}
+
+/// The R version given, else the default of rig, else the latest.
+fn r_version(opt: &Opt) -> Result<String> {
+    if let Some(version) = &opt.r_version {
+        return Ok(version.clone());
+    }
+    match rig::default_version() {
+        Ok(Some(version)) => {
+            debug!("using R {}, rig's default", version);
+            Ok(version)
+        }
+        Ok(None) => Ok(String::from("latest")),
+        Err(err) => {
+            warn!("{:#}, assuming the latest R", err);
+            Ok(String::from("latest"))
+        }
+    }
+}
+
+fn verify_manifest(
+    opt: &Opt,
+    path: &Path,
+    project: Option<&Path>,
+    suggests: bool,
+    update: bool,
+) -> Result<()> {
+    let project = project.map_or_else(project::default_path, Path::to_path_buf);
+    let packages = project::read(&project, suggests)?;
+
+    let committed =
+        if path.exists() || !update {
+            Some(Manifest::load(path).context(
+                "no usable manifest, create it with `r-sysdeps verify-manifest --update`",
+            )?)
+        } else {
+            None
+        };
+    // resolve for the OS of the manifest unless overridden
+    let (distribution, release) = match (&committed, &opt.os_name, &opt.os_version) {
+        (Some(manifest), None, None) => (manifest.distribution.clone(), manifest.release.clone()),
+        _ => os::detect(opt.os_name.clone(), opt.os_version.clone())?,
+    };
+    let (response, _) = resolve_sysreqs(
+        &mut Endpoints::new(opt),
+        &distribution,
+        &release,
+        &packages,
+        client::CHUNK_SIZE,
+        false,
+    )?;
+    let resolved = Manifest::from_sysreqs(&distribution, &release, &response);
+
+    let changes = match &committed {
+        Some(committed) => committed.diff(&resolved),
+        None => Vec::new(),
+    };
+    if committed.is_some() && changes.is_empty() {
+        outln!("{} is up to date", path.display())?;
+        return Ok(());
+    }
+    for change in changes.iter() {
+        outln!("{}", change)?;
+    }
+    if !update {
+        bail!(
+            "{} differs from the system requirements of {}, update it with `r-sysdeps verify-manifest --update`",
+            path.display(),
+            project.display()
+        );
+    }
+    if path.exists() && manifest::is_lockfile(&std::fs::read(path)?) {
+        bail!(
+            "{} is a lockfile, refresh it with `r-sysdeps lock`",
+            path.display()
+        );
+    }
+    resolved.save(path)?;
+    outln!("wrote {}", path.display())?;
+
+    Ok(())
+}
+
+fn print_changes(changes: &[manifest::Change]) -> Result<()> {
+    if changes.is_empty() {
+        outln!("no changes")?;
+    }
+    for change in changes {
+        outln!("{}", change)?;
+    }
+    Ok(())
+}
+
+fn write_lockfile(
+    opt: &Opt,
+    path: &Path,
+    packages: &[String],
+    versions: BTreeMap<String, String>,
+    update: bool,
+) -> Result<()> {
+    let lockfile = if update && path.exists() {
+        update_lockfile(opt, Lockfile::load(path)?, packages, versions)?
+    } else {
+        Lockfile {
+            versions,
+            ..resolve_lockfile(opt, packages)?
+        }
+    };
+    write_lockfile_summary(opt, &lockfile, path)
+}
+
+/// Resolve again the R packages of `locked` that were added or changed
+/// version, or all of them when it was resolved for another server,
+/// repository or OS.
+fn update_lockfile(
+    opt: &Opt,
+    mut locked: Lockfile,
+    packages: &[String],
+    versions: BTreeMap<String, String>,
+) -> Result<Lockfile> {
+    let (distribution, release) = os::detect(opt.os_name.clone(), opt.os_version.clone())?;
+    let mut endpoints = Endpoints::new(opt);
+    let repository_name = endpoints.repository_name()?;
+    let server = match opt.offline {
+        true => provenance::LOCAL_SOURCE,
+        false => opt.server.as_str(),
+    };
+    if (
+        locked.server.as_str(),
+        &locked.repository,
+        &locked.distribution,
+        &locked.release,
+    ) != (server, &repository_name, &distribution, &release)
+    {
+        info!("lockfile was resolved for another server, repository or OS, resolving all packages");
+        return Ok(Lockfile {
+            versions,
+            ..resolve_lockfile(opt, packages)?
+        });
+    }
+
+    let stale = locked.stale(packages, &versions);
+    info!("resolving {} of {} R packages", stale.len(), packages.len());
+    let response = if stale.is_empty() {
+        APISysReqs {
+            requirements: Vec::new(),
+        }
+    } else {
+        resolve_sysreqs(
+            &mut endpoints,
+            &distribution,
+            &release,
+            &stale,
+            client::CHUNK_SIZE,
+            false,
+        )?
+        .0
+    };
+    locked.update(packages, versions, &stale, &response);
+
+    Ok(locked)
+}
+
+fn write_lockfile_summary(opt: &Opt, lockfile: &Lockfile, path: &Path) -> Result<()> {
+    lockfile.save(path)?;
+    outln!(
+        "wrote {} ({} R packages, {} with system requirements)",
+        path.display(),
+        lockfile.packages.len(),
+        lockfile.sysreqs().requirements.len()
+    )?;
+    write_sidecars(opt, path, lockfile)
+}
+
+/// Write the --metadata of the file at `path`, generated from the
+/// resolution `resolved`, and --sign both.
+fn write_sidecars(opt: &Opt, path: &Path, resolved: &Lockfile) -> Result<()> {
+    let mut written = vec![path.to_path_buf()];
+    if opt.metadata {
+        let repository_id = Endpoints::new(opt)
+            .repository(&resolved.repository)
+            .map(|repository| repository.id)
+            .ok();
+        let metadata = Metadata::new(path, resolved, repository_id)?.save(path)?;
+        outln!("wrote {}", metadata.display())?;
+        written.push(metadata);
+    }
+    if let Some(tool) = opt.sign {
+        sign_files(tool, opt.sign_key.as_deref(), &written)?;
+    }
+
+    Ok(())
+}
+
+fn sign_files(tool: signing::Tool, key: Option<&Path>, files: &[PathBuf]) -> Result<()> {
+    for file in files.iter() {
+        let signature = tool.sign(file, key)?;
+        outln!("wrote {}", signature.display())?;
+    }
+    Ok(())
+}
+
+/// Check the signatures of `files`, and that they are unchanged since their
+/// metadata was written.
+fn verify_files(
+    opt: &Opt,
+    key: Option<&Path>,
+    identity: Option<&str>,
+    issuer: Option<&str>,
+    files: &[PathBuf],
+) -> Result<()> {
+    for file in files.iter() {
+        let tool = opt
+            .sign
+            .or_else(|| signing::Tool::of_signed(file))
+            .ok_or_else(|| anyhow!("no signature of {} found", file.display()))?;
+        tool.verify(file, key, identity, issuer)?;
+        if let Some(metadata) = Metadata::load(file)? {
+            let sidecar = provenance::metadata_path(file);
+            tool.verify(&sidecar, key, identity, issuer)?;
+            metadata.check(file)?;
+        }
+        outln!("verified {}", file.display())?;
+    }
+    Ok(())
+}
+
+fn resolve_lockfile(opt: &Opt, packages: &[String]) -> Result<Lockfile> {
+    let (distribution, release) = os::detect(opt.os_name.clone(), opt.os_version.clone())?;
+    let (response, source) = resolve_sysreqs(
+        &mut Endpoints::new(opt),
+        &distribution,
+        &release,
+        packages,
+        client::CHUNK_SIZE,
+        false,
+    )?;
+    Ok(Lockfile::new(
+        &source.server,
+        &source.repository,
+        snapshot(opt),
+        &distribution,
+        &release,
+        packages,
+        &response,
+    ))
+}
+
+/// Dockerfile fragment written by `init --dockerfile`.
+const DOCKERFILE_FRAGMENT: &str = "Dockerfile.sysdeps";
+
+fn init_project(
+    opt: &Opt,
+    force: bool,
+    suggests: bool,
+    lock: bool,
+    dockerfile: bool,
+) -> Result<()> {
+    let dir = Path::new(".");
+    let kinds = project::detect(dir);
+    match kinds.first() {
+        Some(_) => outln!(
+            "found {}",
+            kinds
+                .iter()
+                .map(|kind| kind.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?,
+        None if lock || dockerfile => {
+            bail!("no renv.lock, DESCRIPTION or _quarto.yml to read R packages from")
+        }
+        None => warn!("no renv.lock, DESCRIPTION or _quarto.yml to read R packages from"),
+    }
+
+    let config_path = Path::new(config::PROJECT_FILE);
+    let mut outputs = vec![config_path];
+    if lock {
+        outputs.push(Path::new(lockfile::LOCKFILE));
+    }
+    if dockerfile {
+        outputs.push(Path::new(DOCKERFILE_FRAGMENT));
+    }
+    if !force {
+        if let Some(path) = outputs.iter().find(|path| path.exists()) {
+            bail!(
+                "{} already exists, use --force to overwrite",
+                path.display()
+            );
+        }
+    }
+
+    // Resolve first, so that nothing is written when the server fails.
+    let locked = match kinds.first() {
+        Some(kind) if lock || dockerfile => {
+            let packages = project::packages_of(dir, *kind, suggests)?;
+            let mut versions = match kind {
+                project::Kind::Renv => project::versions(&dir.join("renv.lock"))?,
+                _ => BTreeMap::new(),
+            };
+            versions.retain(|name, _| packages.contains(name));
+            Some(Lockfile {
+                versions,
+                ..resolve_lockfile(opt, &packages)?
+            })
+        }
+        _ => None,
+    };
+
+    let mut config = Config::default();
+    config.set("server", &opt.server)?;
+    let settings = [
+        ("repository", &opt.repository),
+        ("repository-type", &opt.repository_type),
+        ("os-name", &opt.os_name),
+        ("os-version", &opt.os_version),
+        ("r-version", &opt.r_version),
+    ];
+    for (key, value) in settings {
+        if let Some(value) = value {
+            config.set(key, value)?;
+        }
+    }
+    let header = "# r-sysdeps settings of this project, taking precedence over the user's;\n\
+                  # list them all with `r-sysdeps config --project list --all`\n";
+    files::write_atomic(config_path, format!("{}{}", header, config).as_bytes())?;
+    outln!("wrote {}", config_path.display())?;
+
+    if let Some(locked) = locked {
+        if lock {
+            write_lockfile_summary(opt, &locked, Path::new(lockfile::LOCKFILE))?;
+        }
+        if dockerfile {
+            let fragment = format!(
+                "# System requirements for {}-{}, generated by r-sysdeps; paste after FROM\n{}",
+                locked.distribution,
+                locked.release,
+                render::dockerfile_fragment(&locked.sysreqs())
+            );
+            // the fragment may have been created while resolving
+            let path = Path::new(DOCKERFILE_FRAGMENT);
+            if force {
+                files::write_atomic(path, fragment.as_bytes())?;
+            } else {
+                files::write_new(path, fragment.as_bytes())?;
+            }
+            outln!("wrote {}", DOCKERFILE_FRAGMENT)?;
+            write_sidecars(opt, Path::new(DOCKERFILE_FRAGMENT), &locked)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn annotate_renv(opt: &Opt, lock: &Path, sidecar: Option<&Path>) -> Result<()> {
+    let packages = project::read(lock, false)?;
+    let (distribution, release) = os::detect(opt.os_name.clone(), opt.os_version.clone())?;
+    let (response, _) = resolve_sysreqs(
+        &mut Endpoints::new(opt),
+        &distribution,
+        &release,
+        &packages,
+        client::CHUNK_SIZE,
+        false,
+    )?;
+    let manifest = Manifest::from_sysreqs(&distribution, &release, &response);
+
+    match sidecar {
+        Some(path) => {
+            manifest.save(path)?;
+            outln!("wrote {}", path.display())?;
+        }
+        None => {
+            let text = std::fs::read_to_string(lock)
+                .with_context(|| format!("failed to read {}", lock.display()))?;
+            let annotated = project::annotate_renv_lock(&text, &manifest)?;
+            files::write_atomic(lock, annotated.as_bytes())?;
+            outln!(
+                "annotated {} with system packages for {}-{}",
+                lock.display(),
+                distribution,
+                release
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn advise_image(opt: &Opt, r_version: &str, packages: &[String]) -> Result<()> {
+    let release = rocker::ubuntu_release(r_version)?;
+    let mut endpoints = Endpoints::new(opt);
+    let (response, _) = resolve_sysreqs(
+        &mut endpoints,
+        "ubuntu",
+        release,
+        packages,
+        client::CHUNK_SIZE,
+        false,
+    )?;
+    let binaries = endpoints.status()?.distros.iter().any(|distro| {
+        distro.distribution == "ubuntu" && distro.release == release && distro.binaries
+    });
+
+    let advice = rocker::advise(&response);
+    let best = &advice[0];
+    outln!(
+        "Recommended image: {}:{} (Ubuntu {})",
+        best.image.name,
+        r_version,
+        release
+    )?;
+    if binaries {
+        outln!(
+            "Binary R packages: available from {} for Ubuntu {}",
+            opt.server,
+            release
+        )?;
+    } else {
+        outln!(
+            "Binary R packages: not available from {} for Ubuntu {}, packages build from source",
+            opt.server,
+            release
+        )?;
+    }
+    outln!("Already in the image: {}", list_or_none(&best.satisfied))?;
+    outln!("Still to install: {}", list_or_none(&best.missing))?;
+    outln!()?;
+    outln!("Candidates:")?;
+    for candidate in advice.iter() {
+        outln!(
+            "  {:<20} {} of {} system packages to install",
+            candidate.image.name,
+            candidate.missing.len(),
+            candidate.missing.len() + candidate.satisfied.len()
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_devcontainer(
+    opt: &Opt,
+    image: &str,
+    output: &Path,
+    force: bool,
+    layered: bool,
+    packages: &[String],
+) -> Result<()> {
+    let (distribution, release) = image_target(opt, image)?;
+    let mut endpoints = Endpoints::new(opt);
+    let (response, source) = resolve_sysreqs(
+        &mut endpoints,
+        &distribution,
+        &release,
+        packages,
+        client::CHUNK_SIZE,
+        false,
+    )?;
+    let repository_name = &source.repository;
+    if let Some(snapshot) = &opt.snapshot {
+        api::check_snapshot(&opt.server, repository_name, snapshot)?;
+    }
+    let repository_url = match repository::binary_url(
+        &opt.server,
+        repository_name,
+        endpoints.status()?,
+        &distribution,
+        &release,
+        &arch(opt),
+        snapshot(opt),
+    ) {
+        Ok(url) => url,
+        Err(err) => {
+            warn!("{:#}, configuring source packages", err);
+            repository::snapshot_url(&opt.server, repository_name, snapshot(opt))
+        }
+    };
+
+    let files = devcontainer::files(
+        image,
+        &response,
+        Some(&repository_url),
+        Some(packages).filter(|_| layered),
+    );
+    if !force {
+        if let Some(path) = files
+            .iter()
+            .map(|(name, _)| output.join(name))
+            .find(|path| path.exists())
+        {
+            bail!(
+                "{} already exists, use --force to overwrite",
+                path.display()
+            );
+        }
+    }
+    std::fs::create_dir_all(output)
+        .with_context(|| format!("failed to create {}", output.display()))?;
+    let resolved = Lockfile::new(
+        &source.server,
+        &source.repository,
+        snapshot(opt),
+        &distribution,
+        &release,
+        packages,
+        &response,
+    );
+    for (name, contents) in files {
+        let path = output.join(name);
+        if force {
+            files::write_atomic(&path, contents.as_bytes())?;
+        } else {
+            files::write_new(&path, contents.as_bytes())?;
+        }
+        outln!("wrote {}", path.display())?;
+        write_sidecars(opt, &path, &resolved)?;
+    }
+
+    Ok(())
+}
+
+/// Number of output lines shown of a failed step.
+const FAILED_OUTPUT_LINES: usize = 20;
+
+fn test_in_container(
+    opt: &Opt,
+    image: Option<&str>,
+    runtime: Option<&str>,
+    packages: &[String],
+) -> Result<()> {
+    let (distribution, release) = os::detect(opt.os_name.clone(), opt.os_version.clone())?;
+    let image = match image {
+        Some(image) => image.to_string(),
+        None => container::image(&distribution, &release)?,
+    };
+    let runtime = container::runtime(runtime)?;
+    let (response, _) = resolve_sysreqs(
+        &mut Endpoints::new(opt),
+        &distribution,
+        &release,
+        packages,
+        client::CHUNK_SIZE,
+        false,
+    )?;
+    let (commands, skipped) = render::commands(&response);
+    for script in skipped.iter() {
+        warn!("not testing {}", script);
+    }
+    if commands.is_empty() {
+        eprintln!("no system requirements to install");
+        return Ok(());
+    }
+
+    info!("testing in {} with {}", image, runtime);
+    let steps = container::test(&runtime, &image, &commands)?;
+    for step in steps.iter() {
+        let outcome = if step.success { "ok" } else { "FAIL" };
+        outln!(
+            "{:<4} {:>7.1}s  {}",
+            outcome,
+            step.duration.as_secs_f64(),
+            step.command
+        )?;
+        if !step.success {
+            let lines: Vec<&str> = step.output.lines().collect();
+            for line in &lines[lines.len().saturating_sub(FAILED_OUTPUT_LINES)..] {
+                outln!("    {}", line)?;
+            }
+        }
+    }
+    for command in commands.iter().skip(steps.len()) {
+        outln!("{:<4} {:>8}  {}", "skip", "", command)?;
+    }
+
+    if steps.iter().any(|step| !step.success) {
+        bail!("installing the system requirements failed in {}", image);
+    }
+    Ok(())
+}
+
+fn check_installed(opt: &Opt, packages: &[String]) -> Result<()> {
+    let (distribution, release) = os::detect(opt.os_name.clone(), opt.os_version.clone())?;
+    let (response, _) = resolve_sysreqs(
+        &mut Endpoints::new(opt),
+        &distribution,
+        &release,
+        packages,
+        client::CHUNK_SIZE,
+        false,
+    )?;
+    let constraints = match &opt.constraints {
+        Some(path) => constraints::load(path)?,
+        None => Vec::new(),
+    };
+    let system: BTreeSet<&String> = response
+        .requirements
+        .iter()
+        .flat_map(|req| req.requirements.packages.iter())
+        .collect();
+    if system.is_empty() {
+        eprintln!("no system requirements to check");
+        return Ok(());
+    }
+
+    let mut failing = 0;
+    for package in system.iter() {
+        let relation = constraints
+            .iter()
+            .find(|(name, _)| name == *package)
+            .map(|(_, relation)| relation.as_str());
+        let state = installed::check(&distribution, package, relation)?;
+        match &state {
+            installed::State::Present { version } => {
+                outln!("{:<9} {} {}", state.name(), package, version)?
+            }
+            installed::State::Missing => outln!("{:<9} {}", state.name(), package)?,
+            installed::State::Outdated { version, relation } => outln!(
+                "{:<9} {} {} (need {})",
+                state.name(),
+                package,
+                version,
+                relation
+            )?,
+        }
+        if !matches!(state, installed::State::Present { .. }) {
+            failing += 1;
+        }
+    }
+    if failing > 0 {
+        bail!(
+            "{} of {} system packages missing or outdated",
+            failing,
+            system.len()
+        );
+    }
+    Ok(())
+}
+
+fn install_packages(
+    opt: &Opt,
+    dry_run: bool,
+    sudo: bool,
+    yes: bool,
+    packages: &[String],
+) -> Result<()> {
+    let (distribution, release) = os::detect(opt.os_name.clone(), opt.os_version.clone())?;
+    let (response, _) = resolve_sysreqs(
+        &mut Endpoints::new(opt),
+        &distribution,
+        &release,
+        packages,
+        client::CHUNK_SIZE,
+        false,
+    )?;
+    let (commands, skipped) = render::commands(&response);
+    for script in skipped.iter() {
+        warn!("not running {}", script);
+    }
+    if commands.is_empty() {
+        eprintln!("no system requirements to install");
+        return Ok(());
+    }
+    if dry_run {
+        for command in commands.iter() {
+            outln!("{}", command)?;
+        }
+        return Ok(());
+    }
+
+    let sudo = sudo && !userspace::is_root();
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            bail!("not running install commands without confirmation, pass --yes");
+        }
+        for command in commands.iter() {
+            eprintln!("  {}", command);
+        }
+        eprint!(
+            "Run these {} commands{}? [y/N] ",
+            commands.len(),
+            if sudo { " with sudo" } else { "" }
+        );
+        std::io::stderr().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            bail!("cancelled");
+        }
+    }
+    install::run(&commands, sudo)
+}
+
+/// Distribution and release of a base image: those given by the user, or
+/// else known for rocker images.
+fn image_target(opt: &Opt, image: &str) -> Result<(String, String)> {
+    if let (Some(name), Some(version)) = (&opt.os_name, &opt.os_version) {
+        return Ok((name.clone(), version.clone()));
+    }
+    let (name, tag) = image.split_once(':').unwrap_or((image, "latest"));
+    if rocker::IMAGES.iter().any(|rocker| rocker.name == name) {
+        return Ok((
+            String::from("ubuntu"),
+            rocker::ubuntu_release(tag)?.to_string(),
+        ));
+    }
+
+    bail!(
+        "unknown OS of image '{}', specify it with --os-name and --os-version",
+        image
+    )
+}
+
+fn list_or_none(items: &[String]) -> String {
+    if items.is_empty() {
+        String::from("none")
+    } else {
+        items.join(", ")
+    }
+}
+
+/// Fill the global options not given on the command line from the
+/// configuration file.
+fn apply_config(opt: &mut Opt, matches: &ArgMatches) -> Result<()> {
+    let mut entries = Vec::new();
+    if let Some(path) = config::path() {
+        let config = Config::load(&path).context("fix it with `r-sysdeps config edit`")?;
+        entries.extend(config.entries()?);
+    }
+    let config = Config::load(Path::new(config::PROJECT_FILE))
+        .context("fix it with `r-sysdeps config --project edit`")?;
+    entries.extend(config.entries()?);
+    entries.extend(config::env_entries()?);
+
+    for (key, value) in entries {
+        if matches.occurrences_of(key) > 0 {
+            continue;
+        }
+        match key {
+            "server" => opt.server = api::normalize_server(&value)?,
+            // a user given on the command line takes precedence
+            "token" if opt.user.is_some() => {}
+            "token" => opt.token = Some(value),
+            "repository" => opt.repository = Some(value),
+            "repository-type" => opt.repository_type = Some(value),
+            "os-name" => opt.os_name = Some(value),
+            "os-version" => opt.os_version = Some(value),
+            "arch" => opt.arch = Some(value),
+            "snapshot" => opt.snapshot = Some(value),
+            "r-version" => opt.r_version = Some(value),
+            "overrides" => opt.overrides = Some(PathBuf::from(value)),
+            "constraints" => opt.constraints = Some(PathBuf::from(value)),
+            "exclude" => {
+                opt.exclude = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(String::from)
+                    .collect()
+            }
+            "jobs" => opt.jobs = parse_jobs(&value)?,
+            "max-rps" => opt.max_rps = Some(parse_rate(&value)?),
+            "deadline" => opt.deadline = Some(humantime::parse_duration(&value)?),
+            "timeout" => opt.timeout = Some(humantime::parse_duration(&value)?),
+            "cacert" => opt.cacert = Some(PathBuf::from(value)),
+            "retries" => opt.retries = value.parse()?,
+            "retry-backoff" => opt.retry_backoff = humantime::parse_duration(&value)?,
+            "cache-ttl" => opt.cache_ttl = humantime::parse_duration(&value)?,
+            "pkg-manager" => opt.pkg_manager = Some(value.parse().map_err(|e: String| anyhow!(e))?),
+            "log-format" => opt.log_format = value.parse().map_err(|e: String| anyhow!(e))?,
+            _ => bail!("configuration key '{}' is not an option", key),
+        }
+    }
+
+    Ok(())
+}
+
+fn print_manpage() -> Result<()> {
+    fn help(command: &[&str]) -> Result<String> {
+        let mut args = vec!["r-sysdeps"];
+        args.extend_from_slice(command);
+        args.push("--help");
+        match Opt::clap().set_term_width(80).get_matches_from_safe(args) {
+            Err(err) if err.kind == ErrorKind::HelpDisplayed => Ok(err.message),
+            _ => bail!("no help for {}", command.join(" ")),
+        }
+    }
+    // subcommands are listed from the App, as the completions are generated,
+    // in the order of the help texts
+    fn commands(app: &App, command: &[&str], out: &mut Vec<(String, String)>) -> Result<()> {
+        let mut subs: Vec<&App> = app
+            .p
+            .subcommands
+            .iter()
+            .filter(|sub| !sub.p.is_set(AppSettings::Hidden))
+            .collect();
+        subs.sort_by_key(|sub| sub.get_name());
+        for sub in subs {
+            let mut names = command.to_vec();
+            names.push(sub.get_name());
+            let text = help(&names)?;
+            out.push((format!("r-sysdeps {}", names.join(" ")), text));
+            commands(sub, &names, out)?;
+        }
+        Ok(())
+    }
+
+    let mut sections = Vec::new();
+    commands(&Opt::clap(), &[], &mut sections)?;
+    out!(
+        "{}",
+        manpage::render(
+            "r-sysdeps",
+            env!("CARGO_PKG_VERSION"),
+            "resolve system dependencies of R packages using a Package Manager server",
+            &help(&[])?,
+            &sections
+        )
+    )?;
+    Ok(())
+}
+
+fn manage_config(project: bool, action: &ConfigAction) -> Result<()> {
+    let path = if project {
+        PathBuf::from(config::PROJECT_FILE)
+    } else {
+        config::path()
+            .ok_or_else(|| anyhow!("no configuration directory; set R_SYSDEPS_CONFIG or HOME"))?
+    };
+    match action {
+        ConfigAction::Get { key } => match Config::load(&path)?.get(key)? {
+            Some(value) => outln!("{}", value)?,
+            None => std::process::exit(1),
+        },
+        ConfigAction::Set { key, value } => {
+            let mut config = Config::load(&path)?;
+            config.set(key, value)?;
+            config.save(&path)?;
+        }
+        ConfigAction::Unset { key } => {
+            let mut config = Config::load(&path)?;
+            if config.unset(key)? {
+                config.save(&path)?;
+            }
+        }
+        ConfigAction::List { all: false } => {
+            for (key, value) in Config::load(&path)?.entries()? {
+                outln!("{} = {}", key, value)?;
+            }
+        }
+        ConfigAction::List { all: true } => {
+            let config = Config::load(&path)?;
+            for key in config::KEYS {
+                let value = config
+                    .get(key.name)?
+                    .unwrap_or_else(|| "(unset)".to_string());
+                outln!("{:<16} {:<40} # {}", key.name, value, key.description)?;
+            }
+        }
+        ConfigAction::Edit => edit_config(&path)?,
+        ConfigAction::Init { force } => {
+            if path.exists() && !force {
+                bail!(
+                    "{} already exists, pass --force to replace it",
+                    path.display()
+                );
+            }
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("failed to create {}", dir.display()))?;
+            }
+            files::write_atomic(&path, config::template().as_bytes())?;
+            outln!("{}", path.display())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Edit a copy of the configuration file, replacing it only when the edits
+/// are valid.
+fn edit_config(path: &Path) -> Result<()> {
+    let original = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => config::template(),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    let copy =
+        std::env::temp_dir().join(format!("r-sysdeps-{}-{}", std::process::id(), config::FILE));
+    std::fs::write(&copy, &original)
+        .with_context(|| format!("failed to write {}", copy.display()))?;
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&copy)
+        .status()
+        .with_context(|| format!("failed to run editor '{}'", editor))?;
+    if !status.success() {
+        bail!(
+            "editor '{}' failed ({}); edits are in {}",
+            editor,
+            status,
+            copy.display()
+        );
+    }
+
+    let edited = std::fs::read_to_string(&copy)
+        .with_context(|| format!("failed to read {}", copy.display()))?;
+    if edited != original {
+        Config::parse(&edited)
+            .with_context(|| format!("configuration not saved; edits are in {}", copy.display()))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        files::write_atomic(path, edited.as_bytes())?;
+    }
+    let _ = std::fs::remove_file(&copy);
+
+    Ok(())
+}
+
+fn manage_cache(action: &CacheAction) -> Result<()> {
+    let cache = Cache::open(REPOSITORIES_TTL)?;
+    match action {
+        CacheAction::Path => outln!("{}", cache.dir().display())?,
+        CacheAction::Stats => {
+            let usage = cache.usage()?;
+            let now = SystemTime::now();
+            let age = |time: Option<SystemTime>| {
+                time.and_then(|time| now.duration_since(time).ok())
+                    .map(|age| {
+                        humantime::format_duration(Duration::from_secs(age.as_secs())).to_string()
+                    })
+                    .unwrap_or_else(|| String::from("-"))
+            };
+            outln!(
+                "{:<32} {:>8} {:>10}  {:<16} NEWEST",
+                "SERVER",
+                "ENTRIES",
+                "SIZE",
+                "OLDEST"
+            )?;
+            for usage in usage.iter() {
+                let name = if usage.name == cache::PROGRESS_DIR {
+                    String::from("(resume progress)")
+                } else {
+                    usage.name.clone()
+                };
+                outln!(
+                    "{:<32} {:>8} {:>10}  {:<16} {}",
+                    name,
+                    usage.entries,
+                    format_size(usage.bytes),
+                    age(usage.oldest),
+                    age(usage.newest)
+                )?;
+            }
+            outln!(
+                "{:<32} {:>8} {:>10}",
+                "total",
+                usage.iter().map(|usage| usage.entries).sum::<usize>(),
+                format_size(usage.iter().map(|usage| usage.bytes).sum())
+            )?;
+        }
+        CacheAction::Clear => {
+            let (count, bytes) = cache.clear()?;
+            outln!("removed {} entries ({})", count, format_size(bytes))?;
+        }
+        CacheAction::Prune { older_than } => {
+            let (count, bytes) = cache.prune(*older_than)?;
+            outln!(
+                "removed {} entries older than {} ({})",
+                count,
+                humantime::format_duration(*older_than),
+                format_size(bytes)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A size in bytes in binary units, e.g. 1.5 MiB.
+fn format_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "KiB", "MiB", "GiB"] {
+        if size < 1024.0 || unit == "GiB" {
+            return if unit == "B" {
+                format!("{} {}", bytes, unit)
+            } else {
+                format!("{:.1} {}", size, unit)
+            };
+        }
+        size /= 1024.0;
+    }
+    unreachable!()
+}
+
+/// R packages listed per rule by `search` without --all.
+const SEARCH_PACKAGES: usize = 10;
+
+fn search(query: &str, all: bool) -> Result<()> {
+    let database = rules::Database::load()?;
+    let found = database.search(query)?;
+    if found.is_empty() {
+        bail!(
+            "no rule or system package matches '{}' in the rules of {}",
+            query,
+            database.updated
+        );
+    }
+
+    for (i, found) in found.iter().enumerate() {
+        if i > 0 {
+            outln!()?;
+        }
+        let rule = found.rule;
+        outln!("{}", rule.name)?;
+        outln!("  matches: {}", rule.patterns.join(", "))?;
+        for dep in rule.dependencies.iter() {
+            let targets: Vec<String> = dep.constraints.iter().map(|c| c.target()).collect();
+            outln!("  {}: {}", targets.join("; "), dep.packages.join(" "))?;
+            for command in dep.pre_install.iter() {
+                outln!("    pre-install: {}", shell::comment(&command.command))?;
+            }
+            for command in dep.post_install.iter() {
+                outln!("    post-install: {}", shell::comment(&command.command))?;
+            }
+        }
+        let shown = if all {
+            found.packages.len()
+        } else {
+            SEARCH_PACKAGES.min(found.packages.len())
+        };
+        let more = match found.packages.len() - shown {
+            0 => String::new(),
+            more => format!(" and {} more", more),
+        };
+        outln!(
+            "  R packages ({}): {}{}",
+            found.packages.len(),
+            found.packages[..shown].join(", "),
+            more
+        )?;
+    }
+
+    Ok(())
+}
+
+fn db_update(
+    opt: &Opt,
+    rules_url: &str,
+    packages_url: Option<&str>,
+    output: Option<&Path>,
+) -> Result<()> {
+    let packages_url = match packages_url {
+        Some(url) => url.to_string(),
+        None => {
+            let repository_name = Endpoints::new(opt).repository_name()?;
+            repository::packages_url(&opt.server, &repository_name)
+        }
+    };
+
+    let database = rules::download(rules_url, &packages_url)?;
+    let path = match output {
+        Some(path) => path.to_path_buf(),
+        None => rules::path().ok_or_else(|| anyhow!("failed to locate data directory"))?,
+    };
+    database.save(&path)?;
+    outln!(
+        "updated {} ({} rules, {} packages)",
+        path.display(),
+        database.rules.len(),
+        database.packages.len()
+    )?;
+
+    Ok(())
+}
+
+fn print_schema(name: Option<&str>) -> Result<()> {
+    let documents = schema::documents();
+    let output = match name {
+        Some(name) => {
+            let (_, schema) = documents
+                .into_iter()
+                .find(|(document, _)| *document == name)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "unknown document '{}', expected one of: {}",
+                        name,
+                        schema::documents()
+                            .iter()
+                            .map(|(document, _)| *document)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })?;
+            serde_json::to_string_pretty(&schema)?
+        }
+        None => serde_json::to_string_pretty(&documents.into_iter().collect::<BTreeMap<_, _>>())?,
+    };
+    outln!("{}", output)?;
+
+    Ok(())
 }
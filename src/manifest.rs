@@ -0,0 +1,154 @@
+//! Manifests of the system packages an R project resolves to, committed to
+//! detect drift.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::files;
+use crate::lockfile::Lockfile;
+use crate::models::APISysReqs;
+
+/// System packages by R package, for the R packages that have any.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct Manifest {
+    pub distribution: String,
+    pub release: String,
+    pub packages: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// A difference between two manifests or lockfiles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Setting {
+        name: &'static str,
+        old: String,
+        new: String,
+    },
+    Added {
+        package: String,
+        system_packages: BTreeSet<String>,
+    },
+    Removed {
+        package: String,
+        system_packages: BTreeSet<String>,
+    },
+    ScriptAdded {
+        package: String,
+        script: String,
+    },
+    ScriptRemoved {
+        package: String,
+        script: String,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join =
+            |packages: &BTreeSet<String>| packages.iter().cloned().collect::<Vec<_>>().join(", ");
+        match self {
+            Change::Setting { name, old, new } => write!(f, "~ {}: {} -> {}", name, old, new),
+            Change::Added {
+                package,
+                system_packages,
+            } => write!(f, "+ {}: {}", package, join(system_packages)),
+            Change::Removed {
+                package,
+                system_packages,
+            } => write!(f, "- {}: {}", package, join(system_packages)),
+            Change::ScriptAdded { package, script } => {
+                write!(f, "+ {} script: {}", package, script.escape_debug())
+            }
+            Change::ScriptRemoved { package, script } => {
+                write!(f, "- {} script: {}", package, script.escape_debug())
+            }
+        }
+    }
+}
+
+impl Manifest {
+    pub fn from_sysreqs(distribution: &str, release: &str, sysreqs: &APISysReqs) -> Manifest {
+        let mut packages: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for req in sysreqs.requirements.iter() {
+            if !req.requirements.packages.is_empty() {
+                packages
+                    .entry(req.name.clone())
+                    .or_default()
+                    .extend(req.requirements.packages.iter().cloned());
+            }
+        }
+
+        Manifest {
+            distribution: distribution.to_string(),
+            release: release.to_string(),
+            packages,
+        }
+    }
+
+    /// Load a manifest, or the manifest of a lockfile.
+    pub fn load(path: &Path) -> Result<Manifest> {
+        let contents =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        match serde_json::from_slice(&contents) {
+            Ok(manifest) => Ok(manifest),
+            Err(_) if is_lockfile(&contents) => Ok(Lockfile::load(path)?.manifest()),
+            Err(err) => Err(err).with_context(|| format!("failed to parse {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut contents = serde_json::to_vec_pretty(self)?;
+        contents.push(b'\n');
+        files::write_atomic(path, &contents)
+    }
+
+    /// Changes from `self` to `new`: system packages an R package gained
+    /// are added, those it lost removed.
+    pub fn diff(&self, new: &Manifest) -> Vec<Change> {
+        let mut changes = Vec::new();
+        let os = |manifest: &Manifest| format!("{}-{}", manifest.distribution, manifest.release);
+        if os(self) != os(new) {
+            changes.push(Change::Setting {
+                name: "OS",
+                old: os(self),
+                new: os(new),
+            });
+        }
+
+        let empty = BTreeSet::new();
+        let names: BTreeSet<&String> = self.packages.keys().chain(new.packages.keys()).collect();
+        for name in names {
+            let old_packages = self.packages.get(name).unwrap_or(&empty);
+            let new_packages = new.packages.get(name).unwrap_or(&empty);
+            let added: BTreeSet<String> = new_packages.difference(old_packages).cloned().collect();
+            let removed: BTreeSet<String> =
+                old_packages.difference(new_packages).cloned().collect();
+            if !added.is_empty() {
+                changes.push(Change::Added {
+                    package: name.clone(),
+                    system_packages: added,
+                });
+            }
+            if !removed.is_empty() {
+                changes.push(Change::Removed {
+                    package: name.clone(),
+                    system_packages: removed,
+                });
+            }
+        }
+
+        changes
+    }
+}
+
+/// Whether a JSON document looks like a [`Lockfile`] rather than a manifest.
+pub fn is_lockfile(contents: &[u8]) -> bool {
+    serde_json::from_slice::<serde_json::Value>(contents)
+        .map(|document| document.get("resolved_at").is_some())
+        .unwrap_or(false)
+}
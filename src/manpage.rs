@@ -0,0 +1,56 @@
+//! A man page of the command-line interface, built from its help texts in the
+//! manner of help2man, as clap 2 has no generator of its own.
+
+use std::fmt::Write;
+
+/// Render the man page of `name` at `version`, summarized by `about`, with
+/// its `help` and that of each of its `commands`, by their full name, e.g.
+/// `r-sysdeps config get`.
+pub fn render(
+    name: &str,
+    version: &str,
+    about: &str,
+    help: &str,
+    commands: &[(String, String)],
+) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        ".TH \"{}\" 1 \"\" \"{} {}\" \"User Commands\"",
+        name.to_uppercase(),
+        name,
+        version
+    )
+    .unwrap();
+    writeln!(out, ".SH NAME").unwrap();
+    writeln!(out, "{} \\- {}", escape(name), escape(about)).unwrap();
+    writeln!(out, ".SH DESCRIPTION").unwrap();
+    preformatted(&mut out, help);
+    if !commands.is_empty() {
+        writeln!(out, ".SH COMMANDS").unwrap();
+    }
+    for (command, help) in commands {
+        writeln!(out, ".SS \"{}\"", escape(command)).unwrap();
+        preformatted(&mut out, help);
+    }
+    out
+}
+
+/// `text` kept as laid out, the help texts being formatted already.
+fn preformatted(out: &mut String, text: &str) {
+    writeln!(out, ".nf").unwrap();
+    for line in text.trim_end().lines() {
+        writeln!(out, "{}", escape(line)).unwrap();
+    }
+    writeln!(out, ".fi").unwrap();
+}
+
+/// `text` with the characters roff interprets escaped.
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
@@ -0,0 +1,32 @@
+//! CI build matrices of the OS releases the server has system requirements for.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::models::APIStatusResponse;
+
+/// An OS release of a CI matrix, named as `--os-name` and `--os-version` take it.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct MatrixEntry {
+    pub os: String,
+    pub release: String,
+    pub display: String,
+    pub binaries: bool,
+}
+
+/// The releases with system requirements, only those with binary packages
+/// when `binaries` is set.
+pub fn entries(status: &APIStatusResponse, binaries: bool) -> Vec<MatrixEntry> {
+    status
+        .distros
+        .iter()
+        .filter(|distro| distro.sys_reqs)
+        .map(|distro| MatrixEntry {
+            os: distro.distribution.clone(),
+            release: distro.release.clone(),
+            display: distro.display.clone(),
+            binaries: status.binaries_enabled && distro.binaries,
+        })
+        .filter(|entry| entry.binaries || !binaries)
+        .collect()
+}
@@ -0,0 +1,120 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Name of the CRAN repository of servers not reporting theirs.
+pub const DEFAULT_CRAN_REPO: &str = "cran";
+
+/// Response of `/__api__/status`.
+///
+/// Fields missing from older or newer servers take their defaults, and
+/// unknown ones are ignored.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+pub struct APIStatusResponse {
+    pub version: String,
+    pub build_date: String,
+    pub r_configured: bool,
+    pub binaries_enabled: bool,
+    pub distros: Vec<APIDistribution>,
+    pub cran_repo: String,
+    pub bioc_versions: Vec<APIBioConductorVersion>,
+}
+
+impl APIStatusResponse {
+    /// The CRAN repository of the server, or [`DEFAULT_CRAN_REPO`] when it
+    /// does not report one.
+    pub fn cran_repo(&self) -> &str {
+        if self.cran_repo.is_empty() {
+            DEFAULT_CRAN_REPO
+        } else {
+            &self.cran_repo
+        }
+    }
+
+    /// Whether the server lists its distributions, so OSes can be checked
+    /// against them.
+    pub fn lists_distros(&self) -> bool {
+        !self.distros.is_empty()
+    }
+}
+
+/// A distribution supported by the server.
+///
+/// Distributions listed without `sysReqs` are assumed to have system
+/// requirements, as on servers predating the field.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+pub struct APIDistribution {
+    #[serde(rename = "binaryDisplay")]
+    pub binary_display: String,
+    #[serde(rename = "binaryURL")]
+    pub binary_url: String,
+    pub display: String,
+    pub distribution: String,
+    pub release: String,
+    #[serde(rename = "sysReqs", default = "listed")]
+    pub sys_reqs: bool,
+    pub binaries: bool,
+    /// Architectures with binaries, on servers reporting them.
+    #[serde(rename = "binaryArchitectures", skip_serializing_if = "Vec::is_empty")]
+    pub binary_architectures: Vec<String>,
+}
+
+fn listed() -> bool {
+    true
+}
+
+/// Mapping of a Bioconductor release to its R version and CRAN snapshot.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct APIBioConductorVersion {
+    pub bioc_version: String,
+    pub r_version: String,
+    pub cran_snapshot: String,
+}
+
+/// An entry of `/__api__/repos`.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct APIRepository {
+    pub id: u64,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Empty when the server does not report it.
+    #[serde(rename = "type", default)]
+    pub language: String,
+}
+
+/// Response of `/__api__/repos/{id}/sysreqs`.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct APISysReqs {
+    #[serde(default)]
+    pub requirements: Vec<APIRequirement>,
+}
+
+/// System requirements of a single R package.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct APIRequirement {
+    pub name: String,
+    #[serde(default)]
+    pub requirements: APIPackageRequirements,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct APIPackageRequirements {
+    #[serde(default)]
+    pub packages: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_install: Option<Vec<APIPrePost>>,
+    #[serde(default)]
+    pub install_scripts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_install: Option<Vec<APIPrePost>>,
+}
+
+/// A pre- or post-install step.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct APIPrePost {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    pub script: String,
+}
@@ -0,0 +1,59 @@
+//! Nix shells providing R and the system libraries of R packages.
+
+use std::fmt::Write;
+
+use crate::shell;
+
+/// The `packages` list of a `mkShell`, indented by `indent` spaces.
+fn package_list(packages: &[&str], untranslated: &[String], indent: usize) -> String {
+    let pad = " ".repeat(indent);
+    let mut out = String::new();
+    writeln!(out, "{}packages = with pkgs; [", pad).unwrap();
+    for package in ["R", "pkg-config"].iter().chain(packages.iter()) {
+        writeln!(out, "{}  {}", pad, package).unwrap();
+    }
+    if !untranslated.is_empty() {
+        writeln!(
+            out,
+            "{}  # no nixpkgs equivalent known for: {}",
+            pad,
+            shell::comment(&untranslated.join(", "))
+        )
+        .unwrap();
+    }
+    writeln!(out, "{}];", pad).unwrap();
+
+    out
+}
+
+/// A `shell.nix`.
+pub fn shell(packages: &[&str], untranslated: &[String]) -> String {
+    format!(
+        "{{ pkgs ? import <nixpkgs> {{}} }}:\n\npkgs.mkShell {{\n{}}}\n",
+        package_list(packages, untranslated, 2)
+    )
+}
+
+/// A `flake.nix` with a development shell for each default system.
+pub fn flake(packages: &[&str], untranslated: &[String]) -> String {
+    format!(
+        r#"{{
+  description = "R with the system libraries of R packages";
+
+  inputs.nixpkgs.url = "github:NixOS/nixpkgs/nixos-unstable";
+  inputs.flake-utils.url = "github:numtide/flake-utils";
+
+  outputs = {{ self, nixpkgs, flake-utils }}:
+    flake-utils.lib.eachDefaultSystem (system:
+      let
+        pkgs = nixpkgs.legacyPackages.${{system}};
+      in
+      {{
+        devShells.default = pkgs.mkShell {{
+{}        }};
+      }});
+}}
+"#,
+        package_list(packages, untranslated, 10)
+    )
+}
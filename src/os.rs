@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+
+/// The running distribution has no `VERSION_ID`, as is common for rolling releases.
+#[derive(Debug)]
+pub struct UnversionedDistribution {
+    pub distribution: String,
+}
+
+impl fmt::Display for UnversionedDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "distribution '{}' has no VERSION_ID in /etc/os-release (rolling release?); \
+             specify a supported target with --os-name and --os-version, e.g. --os-name ubuntu --os-version 22.04",
+            self.distribution
+        )
+    }
+}
+
+impl std::error::Error for UnversionedDistribution {}
+
+/// Name of macOS as an OS, e.g. for binary repository URLs.
+pub const MACOS: &str = "macos";
+
+/// Name of Windows as an OS, e.g. for binary repository URLs.
+pub const WINDOWS: &str = "windows";
+
+/// `arch` by the name R and the server use, e.g. aarch64 for arm64.
+pub fn arch(arch: &str) -> String {
+    match arch {
+        "amd64" | "x64" => String::from("x86_64"),
+        "arm64" => String::from("aarch64"),
+        other => other.to_string(),
+    }
+}
+
+/// Architecture of the running system.
+pub fn detect_arch() -> String {
+    arch(std::env::consts::ARCH)
+}
+
+/// Detect the distribution name and release of the running system.
+///
+/// Values given by the user take precedence over `/etc/os-release`. When it
+/// has no `VERSION_ID`, the release is derived from a known
+/// `VERSION_CODENAME`, or an [`UnversionedDistribution`] error is returned.
+/// On macOS and Windows, the name is [`MACOS`] or [`WINDOWS`] and the
+/// release that of the system, e.g. 14.2 or 10.0.22631.
+pub fn detect(os_name: Option<String>, os_version: Option<String>) -> Result<(String, String)> {
+    if let (Some(name), Some(version)) = (os_name.clone(), os_version.clone()) {
+        // user provided so just use it
+        return Ok((name, version));
+    }
+    let detected = match std::env::consts::OS {
+        "macos" => Some((MACOS, macos_version()?)),
+        "windows" => Some((WINDOWS, windows_version()?)),
+        _ => None,
+    };
+    if let Some((name, version)) = detected {
+        return Ok((
+            os_name.unwrap_or_else(|| name.to_string()),
+            os_version.unwrap_or(version),
+        ));
+    }
+
+    let mut os_rename = HashMap::new();
+    os_rename.insert("rhel", "redhat");
+
+    let os_release = std::fs::read_to_string("/etc/os-release")
+        .with_context(|| "failed to read /etc/os-release")?;
+    let os_attributes = parse_os_release(&os_release);
+
+    let name = match os_name {
+        Some(name) => name,
+        None => os_attributes
+            .get("ID")
+            .map(|id| {
+                os_rename
+                    .get(id.as_str())
+                    .unwrap_or(&id.as_str())
+                    .to_string()
+            })
+            .ok_or_else(|| anyhow!("failed to detect linux distribution and/or version"))?,
+    };
+    let version = os_version
+        .or_else(|| os_attributes.get("VERSION_ID").cloned())
+        .or_else(|| {
+            os_attributes
+                .get("VERSION_CODENAME")
+                .and_then(|codename| codename_release(&name, codename))
+                .map(String::from)
+        });
+
+    match version {
+        Some(version) => Ok((name, version)),
+        None => Err(UnversionedDistribution { distribution: name }.into()),
+    }
+}
+
+/// The macOS release, from `sw_vers`.
+fn macos_version() -> Result<String> {
+    let output = Command::new("sw_vers")
+        .arg("-productVersion")
+        .output()
+        .context("failed to run sw_vers to detect the macOS version")?;
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || version.is_empty() {
+        return Err(anyhow!("failed to detect the macOS version with sw_vers"));
+    }
+    Ok(version)
+}
+
+/// The Windows release, from the banner of `ver`, e.g. `Microsoft Windows
+/// [Version 10.0.22631.2861]`, without the revision.
+fn windows_version() -> Result<String> {
+    let output = Command::new("cmd")
+        .args(["/C", "ver"])
+        .output()
+        .context("failed to run ver to detect the Windows version")?;
+    let banner = String::from_utf8_lossy(&output.stdout);
+    banner
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .find(|word| word.contains('.'))
+        .map(|version| version.split('.').take(3).collect::<Vec<_>>().join("."))
+        .ok_or_else(|| {
+            anyhow!(
+                "failed to detect the Windows version from '{}'",
+                banner.trim()
+            )
+        })
+}
+
+/// Read the `KEY=value` pairs of an os-release file.
+fn parse_os_release(os_release: &str) -> HashMap<String, String> {
+    os_release
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().replace('"', "")))
+        .collect()
+}
+
+/// Codenames of Debian and Ubuntu releases.
+const CODENAMES: &[(&str, &str, &str)] = &[
+    ("debian", "buster", "10"),
+    ("debian", "bullseye", "11"),
+    ("debian", "bookworm", "12"),
+    ("debian", "trixie", "13"),
+    ("debian", "forky", "14"),
+    ("ubuntu", "bionic", "18.04"),
+    ("ubuntu", "focal", "20.04"),
+    ("ubuntu", "jammy", "22.04"),
+    ("ubuntu", "noble", "24.04"),
+];
+
+/// Release of a distribution codename, for os-release files without `VERSION_ID`
+/// such as Debian testing and unstable.
+fn codename_release(distribution: &str, codename: &str) -> Option<&'static str> {
+    CODENAMES
+        .iter()
+        .find(|(name, code, _)| *name == distribution && *code == codename)
+        .map(|(_, _, release)| *release)
+}
+
+/// Codename of a Debian or Ubuntu release, e.g. jammy for Ubuntu 22.04.
+pub fn codename(distribution: &str, release: &str) -> Option<&'static str> {
+    CODENAMES
+        .iter()
+        .find(|(name, _, version)| *name == distribution && release.starts_with(version))
+        .map(|(_, codename, _)| *codename)
+}
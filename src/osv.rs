@@ -0,0 +1,201 @@
+//! Known vulnerabilities of system packages, from the OSV database.
+//!
+//! OSV tracks distribution packages by source package, so binary packages are
+//! mapped to theirs through the local package database when installed, with
+//! their versions to only report advisories affecting them. Otherwise the
+//! development package suffix is dropped, which is only a guess, and all
+//! advisories of the package are reported.
+
+use std::fmt::Write;
+use std::process::Command;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{api, rules, shell};
+
+pub const OSV_URL: &str = "https://api.osv.dev";
+
+/// A source package to look up, with the binary packages built from it.
+#[derive(Debug, Clone)]
+pub struct Query {
+    pub source: String,
+    pub version: Option<String>,
+    pub binaries: Vec<String>,
+}
+
+/// Advisories of a source package.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub query: Query,
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResponse {
+    #[serde(default)]
+    results: Vec<BatchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResult {
+    #[serde(default)]
+    vulns: Vec<Vulnerability>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Vulnerability {
+    id: String,
+}
+
+/// The OSV ecosystem of a distribution release.
+pub fn ecosystem(distribution: &str, release: &str) -> Result<String> {
+    let major = release.split('.').next().unwrap_or(release);
+    Ok(match rules::canonical_distribution(distribution) {
+        "ubuntu" => {
+            let lts =
+                major.parse::<u32>().is_ok_and(|year| year % 2 == 0) && release.ends_with(".04");
+            if lts {
+                format!("Ubuntu:{}:LTS", release)
+            } else {
+                format!("Ubuntu:{}", release)
+            }
+        }
+        "debian" => format!("Debian:{}", major),
+        "rockylinux" => format!("Rocky Linux:{}", major),
+        "almalinux" => format!("AlmaLinux:{}", major),
+        "alpine" => format!(
+            "Alpine:v{}",
+            release.splitn(3, '.').take(2).collect::<Vec<_>>().join(".")
+        ),
+        _ => bail!("no OSV ecosystem known for {} {}", distribution, release),
+    })
+}
+
+/// Source package and version of an installed binary package.
+fn installed_source(distribution: &str, package: &str) -> Option<(String, String)> {
+    let output = match rules::query_command(distribution).ok()? {
+        "dpkg -s" => Command::new("dpkg-query")
+            .args(["-W", "-f=${source:Package}\t${source:Version}", package])
+            .output(),
+        "rpm -q" => Command::new("rpm")
+            .args(["-q", "--qf", "%{SOURCERPM}\t%{VERSION}-%{RELEASE}", package])
+            .output(),
+        _ => return None,
+    }
+    .ok()
+    .filter(|output| output.status.success())?;
+
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let (source, version) = stdout.trim().split_once('\t')?;
+    // source RPMs are named <name>-<version>-<release>.src.rpm
+    let source = match source.strip_suffix(".src.rpm") {
+        Some(rpm) => rpm.rsplitn(3, '-').last()?,
+        None => source,
+    };
+
+    Some((source.to_string(), version.to_string()))
+}
+
+/// Queries for system packages; with `local`, the packages installed on this
+/// machine are looked up with their versions.
+pub fn queries(distribution: &str, packages: &[String], local: bool) -> Vec<Query> {
+    let mut queries: Vec<Query> = Vec::new();
+    for package in packages {
+        let (source, version) = match installed_source(distribution, package).filter(|_| local) {
+            Some((source, version)) => (source, Some(version)),
+            None => {
+                let source = package
+                    .strip_suffix("-dev")
+                    .or_else(|| package.strip_suffix("-devel"))
+                    .unwrap_or(package);
+                (source.to_string(), None)
+            }
+        };
+        match queries
+            .iter_mut()
+            .find(|query| query.source == source && query.version == version)
+        {
+            Some(query) => query.binaries.push(package.clone()),
+            None => queries.push(Query {
+                source,
+                version,
+                binaries: vec![package.clone()],
+            }),
+        }
+    }
+
+    queries
+}
+
+/// Advisories of each query, in one batch request to `osv_url`.
+pub fn advisories(osv_url: &str, ecosystem: &str, queries: Vec<Query>) -> Result<Vec<Finding>> {
+    if queries.is_empty() {
+        return Ok(Vec::new());
+    }
+    let body = json!({
+        "queries": queries
+            .iter()
+            .map(|query| {
+                let mut body = json!({
+                    "package": { "name": query.source, "ecosystem": ecosystem }
+                });
+                if let Some(version) = &query.version {
+                    body["version"] = json!(version);
+                }
+                body
+            })
+            .collect::<Vec<_>>()
+    });
+    let url = format!("{}/v1/querybatch", osv_url.trim_end_matches('/'));
+    let response: BatchResponse = api::post_json(&url, &body, 60)?;
+    if response.results.len() != queries.len() {
+        bail!(
+            "{} returned {} results for {} queries",
+            url,
+            response.results.len(),
+            queries.len()
+        );
+    }
+
+    Ok(queries
+        .into_iter()
+        .zip(response.results)
+        .map(|(query, result)| Finding {
+            query,
+            ids: result.vulns.into_iter().map(|vuln| vuln.id).collect(),
+        })
+        .collect())
+}
+
+/// Findings as shell comments, one line per source package with advisories.
+pub fn report(ecosystem: &str, findings: &[Finding]) -> String {
+    let mut out = String::new();
+    let affected: Vec<&Finding> = findings.iter().filter(|f| !f.ids.is_empty()).collect();
+    writeln!(
+        out,
+        "# OSV advisories ({}): {} of {} source packages affected",
+        shell::comment(ecosystem),
+        affected.len(),
+        findings.len()
+    )
+    .unwrap();
+    for finding in affected {
+        let version = match &finding.query.version {
+            Some(version) => format!("{} installed", version),
+            None => String::from("any version"),
+        };
+        writeln!(
+            out,
+            "## {} ({}; for {}): {}",
+            shell::comment(&finding.query.source),
+            shell::comment(&version),
+            shell::comment(&finding.query.binaries.join(", ")),
+            finding.ids.join(", ")
+        )
+        .unwrap();
+    }
+
+    out
+}
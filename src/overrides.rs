@@ -0,0 +1,140 @@
+//! Overrides of the system packages the server returns, such as packages
+//! renamed on an internal mirror or needed in addition, from a TOML file:
+//!
+//! ```toml
+//! [rename]
+//! libcurl4-openssl-dev = "libcurl4-gnutls-dev"
+//!
+//! [extra]
+//! xml2 = ["libxslt1-dev"]
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::models::{APIPackageRequirements, APIRequirement, APISysReqs};
+use crate::{rules, shell};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Overrides {
+    /// Replacements of system packages, by system package.
+    #[serde(default)]
+    pub rename: BTreeMap<String, String>,
+    /// System packages to install in addition, by R package.
+    #[serde(default)]
+    pub extra: BTreeMap<String, Vec<String>>,
+}
+
+impl Overrides {
+    pub fn load(path: &Path) -> Result<Overrides> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Overrides::parse(&text).with_context(|| format!("invalid overrides {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Overrides> {
+        let overrides: Overrides = toml_edit::de::from_str(text)?;
+        let names = overrides
+            .rename
+            .iter()
+            .flat_map(|(old, new)| [old, new])
+            .chain(overrides.extra.values().flatten());
+        for name in names {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_graphic()) {
+                bail!("invalid system package name '{}'", name.escape_default());
+            }
+        }
+
+        Ok(overrides)
+    }
+
+    /// Rename the system packages of `sysreqs`, in their install scripts too,
+    /// and add the extra ones of the R packages of `packages`, installed as
+    /// on `distribution` `release`.
+    pub fn apply(
+        &self,
+        distribution: &str,
+        release: &str,
+        packages: &[String],
+        sysreqs: &mut APISysReqs,
+    ) -> Result<()> {
+        for req in sysreqs.requirements.iter_mut() {
+            let reqs = &mut req.requirements;
+            for script in reqs.install_scripts.iter_mut() {
+                let renamed = script.rsplit_once(' ').and_then(|(installer, package)| {
+                    let new = self.rename.get(package.trim_matches('\''))?;
+                    Some(format!("{} {}", installer, shell::quote(new)))
+                });
+                if let Some(renamed) = renamed {
+                    *script = renamed;
+                }
+            }
+            for package in reqs.packages.iter_mut() {
+                if let Some(new) = self.rename.get(package) {
+                    *package = new.clone();
+                }
+            }
+        }
+
+        let extra = self
+            .extra
+            .iter()
+            .filter(|(name, extra)| packages.contains(name) && !extra.is_empty());
+        for (name, extra) in extra {
+            let installer = rules::install_command(distribution, release)?;
+            let index = match sysreqs
+                .requirements
+                .iter()
+                .position(|req| &req.name == name)
+            {
+                Some(index) => index,
+                None => {
+                    sysreqs.requirements.push(APIRequirement {
+                        name: name.clone(),
+                        requirements: APIPackageRequirements::default(),
+                    });
+                    sysreqs.requirements.len() - 1
+                }
+            };
+            let reqs = &mut sysreqs.requirements[index].requirements;
+            for package in extra {
+                if !reqs.packages.contains(package) {
+                    reqs.packages.push(package.clone());
+                    reqs.install_scripts
+                        .push(format!("{} {}", installer, shell::quote(package)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Remove the `excluded` system packages from `sysreqs`, with their install
+/// scripts, and the pre- and post-install scripts of R packages left with no
+/// system packages; these are kept without requirements, as they do have
+/// metadata.
+pub fn exclude(sysreqs: &mut APISysReqs, excluded: &[String]) {
+    if excluded.is_empty() {
+        return;
+    }
+    for req in sysreqs.requirements.iter_mut() {
+        let reqs = &mut req.requirements;
+        reqs.install_scripts.retain(|script| {
+            !script.rsplit_once(' ').is_some_and(|(_, package)| {
+                excluded
+                    .iter()
+                    .any(|name| name == package.trim_matches('\''))
+            })
+        });
+        reqs.packages.retain(|package| !excluded.contains(package));
+        if reqs.packages.is_empty() {
+            reqs.pre_install = None;
+            reqs.post_install = None;
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Package manager front-ends the install commands can be run with.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+
+use crate::models::APISysReqs;
+
+/// A package manager front-end, replacing the one the server's scripts use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageManager {
+    Apt,
+    AptGet,
+    Nala,
+    Dnf,
+    Dnf5,
+    Yum,
+    Zypper,
+    Apk,
+}
+
+/// Package managers installing the same kind of packages, with the same
+/// command line syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Family {
+    Deb,
+    Rpm,
+    Zypper,
+    Apk,
+}
+
+impl PackageManager {
+    pub const ALL: [PackageManager; 8] = [
+        PackageManager::Apt,
+        PackageManager::AptGet,
+        PackageManager::Nala,
+        PackageManager::Dnf,
+        PackageManager::Dnf5,
+        PackageManager::Yum,
+        PackageManager::Zypper,
+        PackageManager::Apk,
+    ];
+
+    /// The command of the package manager.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PackageManager::Apt => "apt",
+            PackageManager::AptGet => "apt-get",
+            PackageManager::Nala => "nala",
+            PackageManager::Dnf => "dnf",
+            PackageManager::Dnf5 => "dnf5",
+            PackageManager::Yum => "yum",
+            PackageManager::Zypper => "zypper",
+            PackageManager::Apk => "apk",
+        }
+    }
+
+    /// The command installing packages, to be followed by their names.
+    pub fn install_command(&self) -> &'static str {
+        match self {
+            PackageManager::Apt => "apt install -y",
+            PackageManager::AptGet => "apt-get install -y",
+            PackageManager::Nala => "nala install -y",
+            PackageManager::Dnf => "dnf install -y",
+            PackageManager::Dnf5 => "dnf5 install -y",
+            PackageManager::Yum => "yum install -y",
+            PackageManager::Zypper => "zypper --non-interactive install",
+            PackageManager::Apk => "apk add --no-cache",
+        }
+    }
+
+    /// The command refreshing the package lists before installing, for the
+    /// apt front-ends, whose lists are not refreshed on their own.
+    pub fn update_command(&self) -> Option<&'static str> {
+        match self {
+            PackageManager::Apt => Some("apt update"),
+            PackageManager::AptGet => Some("apt-get update"),
+            PackageManager::Nala => Some("nala update"),
+            _ => None,
+        }
+    }
+
+    /// The command removing the package lists fetched by
+    /// [`PackageManager::update_command`] once installs are done.
+    pub fn remove_lists_command(&self) -> Option<&'static str> {
+        Some("rm -rf /var/lib/apt/lists/*").filter(|_| self.is_apt())
+    }
+
+    /// The command removing the package manager's caches, to keep them out of
+    /// image layers; apt's lists are removed by
+    /// [`PackageManager::remove_lists_command`] instead, and apk keeps none
+    /// with `--no-cache`.
+    pub fn clean_command(&self) -> Option<&'static str> {
+        match self {
+            PackageManager::Dnf => Some("dnf clean all"),
+            PackageManager::Dnf5 => Some("dnf5 clean all"),
+            PackageManager::Yum => Some("yum clean all"),
+            PackageManager::Zypper => Some("zypper clean --all"),
+            _ => None,
+        }
+    }
+
+    /// Whether the package manager installs Debian packages, with apt.
+    pub fn is_apt(&self) -> bool {
+        self.family() == Family::Deb
+    }
+
+    fn family(&self) -> Family {
+        match self {
+            PackageManager::Apt | PackageManager::AptGet | PackageManager::Nala => Family::Deb,
+            PackageManager::Dnf | PackageManager::Dnf5 | PackageManager::Yum => Family::Rpm,
+            PackageManager::Zypper => Family::Zypper,
+            PackageManager::Apk => Family::Apk,
+        }
+    }
+
+    /// The package manager `script` runs, if it starts with one.
+    pub fn of_script(script: &str) -> Option<PackageManager> {
+        let command = script.split_whitespace().next()?;
+        PackageManager::ALL
+            .iter()
+            .find(|manager| manager.name() == command)
+            .copied()
+    }
+
+    /// `script` run with this package manager instead of the one it starts
+    /// with, if any. Fails for package managers of another kind of packages.
+    pub fn rewrite(&self, script: &str) -> Result<String> {
+        let manager = match PackageManager::of_script(script) {
+            Some(manager) => manager,
+            None => return Ok(script.to_string()),
+        };
+        if manager.family() != self.family() {
+            bail!(
+                "--pkg-manager {} can't replace {} in '{}'",
+                self,
+                manager,
+                script
+            );
+        }
+
+        let start = script.find(manager.name()).unwrap_or_default();
+        Ok(format!(
+            "{}{}{}",
+            &script[..start],
+            self.name(),
+            &script[start + manager.name().len()..]
+        ))
+    }
+
+    /// Run all scripts of `sysreqs` with this package manager.
+    pub fn apply(&self, sysreqs: &mut APISysReqs) -> Result<()> {
+        for req in sysreqs.requirements.iter_mut() {
+            let reqs = &mut req.requirements;
+            for script in reqs.install_scripts.iter_mut() {
+                *script = self.rewrite(script)?;
+            }
+            let steps = reqs
+                .pre_install
+                .iter_mut()
+                .chain(reqs.post_install.iter_mut());
+            for step in steps.flatten() {
+                step.script = self.rewrite(&step.script)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for PackageManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PackageManager {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PackageManager::ALL
+            .iter()
+            .find(|manager| manager.name() == s)
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "unknown package manager '{}', expected {}",
+                    s,
+                    PackageManager::ALL
+                        .iter()
+                        .map(PackageManager::name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
@@ -0,0 +1,213 @@
+//! R package names.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+/// Packages shipped with R itself, which are not listed in any repository.
+pub const BASE_PACKAGES: &[&str] = &[
+    "base",
+    "compiler",
+    "datasets",
+    "grDevices",
+    "graphics",
+    "grid",
+    "methods",
+    "parallel",
+    "splines",
+    "stats",
+    "stats4",
+    "tcltk",
+    "tools",
+    "utils",
+];
+
+pub fn is_base(name: &str) -> bool {
+    BASE_PACKAGES.contains(&name)
+}
+
+/// Check that `name` can be sent to the server as a package name.
+pub fn validate(name: &str) -> Result<()> {
+    if name.is_empty() {
+        bail!("invalid package name: empty");
+    }
+    if let Some(c) = name.chars().find(|c| c.is_whitespace() || c.is_control()) {
+        bail!(
+            "invalid package name '{}': contains whitespace or control character {:?}",
+            name.escape_debug(),
+            c
+        );
+    }
+    if let Some(c) = name.chars().find(|c| !c.is_ascii_graphic()) {
+        bail!(
+            "invalid package name '{}': contains non-ASCII character {:?}",
+            name,
+            c
+        );
+    }
+
+    Ok(())
+}
+
+/// Check that `name` follows R's rules for package names: ASCII letters,
+/// digits and dots, at least two characters, starting with a letter and not
+/// ending with a dot.
+pub fn check_r_name(name: &str) -> Result<()> {
+    if name.starts_with('-') {
+        bail!(
+            "invalid package name '{}': looks like an option, which must come before the package names",
+            name
+        );
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '.')
+    {
+        bail!(
+            "invalid package name '{}': only letters, digits and '.' are allowed, not {:?}",
+            name,
+            c
+        );
+    }
+    if name.chars().count() < 2 {
+        bail!(
+            "invalid package name '{}': must be at least two characters",
+            name
+        );
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        bail!("invalid package name '{}': must start with a letter", name);
+    }
+    if name.ends_with('.') {
+        bail!("invalid package name '{}': must not end with '.'", name);
+    }
+
+    Ok(())
+}
+
+/// Trim surrounding whitespace from a package name given by the user, and
+/// check it before anything is sent to the server.
+pub fn normalize(name: &str) -> Result<String> {
+    let name = name.trim();
+    validate(name)?;
+    check_r_name(name)?;
+
+    Ok(name.to_string())
+}
+
+/// Package names listed in a file, one per line, skipping blank lines and
+/// `#` comments.
+pub fn load(path: &Path) -> Result<Vec<String>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read package list {}", path.display()))?;
+    Ok(text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Package names gathered from several inputs, each kept once.
+///
+/// Names are normalized, and names differing only in case, which R
+/// repositories don't allow side by side, are taken to be the same package,
+/// spelled as first given.
+#[derive(Debug, Default)]
+pub struct Merged {
+    names: Vec<String>,
+    duplicates: Vec<Duplicate>,
+}
+
+/// A package name given again, merged into an earlier one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    /// The name as given again.
+    pub given: String,
+    /// The name it was merged into.
+    pub name: String,
+    /// The input it was given again in.
+    pub source: String,
+}
+
+impl Merged {
+    /// Add the package `name` given in `source`, e.g. the command line.
+    pub fn add(&mut self, source: &str, name: &str) -> Result<()> {
+        let normalized = normalize(name)?;
+        match self
+            .names
+            .iter()
+            .find(|known| known.eq_ignore_ascii_case(&normalized))
+        {
+            Some(known) => self.duplicates.push(Duplicate {
+                given: name.to_string(),
+                name: known.clone(),
+                source: source.to_string(),
+            }),
+            None => self.names.push(normalized),
+        }
+
+        Ok(())
+    }
+
+    /// Add all of `names`, given in `source`.
+    pub fn extend<S: AsRef<str>>(&mut self, source: &str, names: &[S]) -> Result<()> {
+        names
+            .iter()
+            .try_for_each(|name| self.add(source, name.as_ref()))
+    }
+
+    /// The distinct names, in the order first given.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn duplicates(&self) -> &[Duplicate] {
+        &self.duplicates
+    }
+
+    /// The merged duplicates, one per line, or nothing without them.
+    pub fn report(&self) -> String {
+        self.duplicates
+            .iter()
+            .map(|duplicate| {
+                let spelling = if duplicate.given.trim() == duplicate.name {
+                    String::new()
+                } else {
+                    format!(" as '{}'", duplicate.given.escape_debug())
+                };
+                format!(
+                    "{} given again{} in {}\n",
+                    duplicate.name, spelling, duplicate.source
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_r_name_accepts_valid_names() {
+        for name in ["xml2", "data.table", "R6", "BiocManager"] {
+            assert!(check_r_name(name).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn check_r_name_rejects_invalid_names() {
+        for name in [
+            "--strict", "x", "2fast", ".hidden", "dot.", "my_pkg", "a-b", "",
+        ] {
+            assert!(check_r_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn normalize_trims() {
+        assert_eq!(normalize("  xml2\t").unwrap(), "xml2");
+    }
+}
@@ -0,0 +1,107 @@
+//! The result of `pak::pkg_sysreqs()` as serialized by jsonlite, so that
+//! tooling built around pak can consume our results.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{APIPrePost, APISysReqs};
+use crate::provenance::Provenance;
+
+/// System requirements of a set of R packages, with the scripts installing them.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct PakSysreqs {
+    pub os: String,
+    pub distribution: String,
+    pub version: String,
+    pub pre_install: Vec<String>,
+    pub install_scripts: Vec<String>,
+    pub post_install: Vec<String>,
+    /// Rows of the `packages` data frame.
+    pub packages: Vec<PakSysreq>,
+    /// Where the system requirements came from, with `--provenance`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+/// A system requirement and the R packages needing it.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct PakSysreq {
+    pub sysreq: String,
+    pub packages: Vec<String>,
+    pub pre_install: Vec<String>,
+    pub system_packages: Vec<String>,
+    pub post_install: Vec<String>,
+}
+
+/// Convert the server's per-package requirements to pak's per-requirement rows.
+///
+/// The server doesn't name requirements, so each system package is one, and
+/// pre- and post-install steps go with the system packages of the same R package.
+pub fn from_sysreqs(distribution: &str, release: &str, sysreqs: &APISysReqs) -> PakSysreqs {
+    let mut result = PakSysreqs {
+        os: String::from("linux"),
+        distribution: distribution.to_string(),
+        version: release.to_string(),
+        pre_install: Vec::new(),
+        install_scripts: Vec::new(),
+        post_install: Vec::new(),
+        packages: Vec::new(),
+        provenance: None,
+    };
+
+    for req in sysreqs.requirements.iter() {
+        let reqs = &req.requirements;
+        let pre_install = scripts(&reqs.pre_install);
+        let post_install = scripts(&reqs.post_install);
+        extend_unique(&mut result.pre_install, &pre_install);
+        extend_unique(&mut result.install_scripts, &reqs.install_scripts);
+        extend_unique(&mut result.post_install, &post_install);
+
+        // steps without any system package are a requirement of their own
+        let rows: Vec<(String, Vec<String>)> = if reqs.packages.is_empty() {
+            vec![(req.name.clone(), Vec::new())]
+        } else {
+            reqs.packages
+                .iter()
+                .map(|package| (package.clone(), vec![package.clone()]))
+                .collect()
+        };
+        for (sysreq, system_packages) in rows {
+            let index = match result.packages.iter().position(|row| row.sysreq == sysreq) {
+                Some(index) => index,
+                None => {
+                    result.packages.push(PakSysreq {
+                        sysreq,
+                        packages: Vec::new(),
+                        pre_install: Vec::new(),
+                        system_packages,
+                        post_install: Vec::new(),
+                    });
+                    result.packages.len() - 1
+                }
+            };
+            let row = &mut result.packages[index];
+            extend_unique(&mut row.packages, std::slice::from_ref(&req.name));
+            extend_unique(&mut row.pre_install, &pre_install);
+            extend_unique(&mut row.post_install, &post_install);
+        }
+    }
+
+    result
+}
+
+fn scripts(steps: &Option<Vec<APIPrePost>>) -> Vec<String> {
+    steps
+        .iter()
+        .flatten()
+        .map(|step| step.script.clone())
+        .collect()
+}
+
+fn extend_unique(values: &mut Vec<String>, more: &[String]) {
+    for value in more {
+        if !values.contains(value) {
+            values.push(value.clone());
+        }
+    }
+}
@@ -0,0 +1,31 @@
+//! Per-user locations, following the XDG base directory conventions.
+
+use std::env;
+use std::path::PathBuf;
+
+fn xdg(var: &str, default: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME")
+                .filter(|home| !home.is_empty())
+                .map(|home| PathBuf::from(home).join(default))
+        })
+        .map(|dir| dir.join("r-sysdeps"))
+}
+
+/// `$XDG_DATA_HOME/r-sysdeps`, defaulting to `~/.local/share/r-sysdeps`.
+pub fn data_dir() -> Option<PathBuf> {
+    xdg("XDG_DATA_HOME", ".local/share")
+}
+
+/// `$XDG_CACHE_HOME/r-sysdeps`, defaulting to `~/.cache/r-sysdeps`.
+pub fn cache_dir() -> Option<PathBuf> {
+    xdg("XDG_CACHE_HOME", ".cache")
+}
+
+/// `$XDG_CONFIG_HOME/r-sysdeps`, defaulting to `~/.config/r-sysdeps`.
+pub fn config_dir() -> Option<PathBuf> {
+    xdg("XDG_CONFIG_HOME", ".config")
+}
@@ -0,0 +1,324 @@
+//! R packages an R project depends on, from its `DESCRIPTION` or `renv.lock`,
+//! or from the code of a Quarto project.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::manifest::Manifest;
+use crate::{dcf, packages};
+
+/// Field of `renv.lock` package records holding their system packages by OS.
+pub const RENV_FIELD: &str = "SystemPackages";
+
+#[derive(Debug, Deserialize)]
+struct RenvLock {
+    #[serde(rename = "Packages", default)]
+    packages: BTreeMap<String, RenvPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RenvPackage {
+    #[serde(rename = "Package")]
+    package: String,
+    #[serde(rename = "Version", default)]
+    version: Option<String>,
+    #[serde(rename = "Source", default)]
+    source: Option<String>,
+}
+
+/// Packages of the `Depends`, `Imports` and `LinkingTo` fields of a
+/// `DESCRIPTION`, and of `Suggests` when `suggests` is set, without R itself
+/// and base packages.
+pub fn description_packages(text: &str, suggests: bool) -> Result<Vec<String>> {
+    let record = dcf::parse(text)
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("DESCRIPTION has no fields"))?;
+
+    let mut fields = vec!["Depends", "Imports", "LinkingTo"];
+    if suggests {
+        fields.push("Suggests");
+    }
+    let mut names = Vec::new();
+    for field in fields {
+        for entry in record.get(field).iter().flat_map(|value| value.split(',')) {
+            // drop version requirements such as `(>= 1.0)`
+            let name = entry.split('(').next().unwrap_or_default().trim();
+            if name.is_empty() || name == "R" || packages::is_base(name) {
+                continue;
+            }
+            let name = packages::normalize(name)?;
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+/// Packages of an `renv.lock` installed from CRAN-like repositories or
+/// Bioconductor; packages from other sources, such as GitHub, are skipped
+/// with a warning as the server cannot know them.
+pub fn renv_lock_packages(text: &str) -> Result<Vec<String>> {
+    let lock: RenvLock = serde_json::from_str(text).context("failed to parse renv.lock")?;
+
+    let mut names = Vec::new();
+    let mut skipped = Vec::new();
+    for package in lock.packages.into_values() {
+        if packages::is_base(&package.package) {
+            continue;
+        }
+        match package.source.as_deref() {
+            None | Some("Repository") | Some("Bioconductor") => {
+                names.push(packages::normalize(&package.package)?)
+            }
+            Some(_) => skipped.push(package.package),
+        }
+    }
+    if !skipped.is_empty() {
+        warn!(
+            "skipping renv.lock packages not from a repository: {}",
+            skipped.join(", ")
+        );
+    }
+
+    Ok(names)
+}
+
+/// Versions of the packages of an `renv.lock`, by package.
+pub fn renv_lock_versions(text: &str) -> Result<BTreeMap<String, String>> {
+    let lock: RenvLock = serde_json::from_str(text).context("failed to parse renv.lock")?;
+    lock.packages
+        .into_values()
+        .filter_map(|package| Some((package.package, package.version?)))
+        .map(|(name, version)| Ok((packages::normalize(&name)?, version)))
+        .collect()
+}
+
+/// Versions of the packages read by [`read`], known for an `renv.lock` only.
+pub fn versions(path: &Path) -> Result<BTreeMap<String, String>> {
+    if path.extension() != Some("lock".as_ref()) {
+        return Ok(BTreeMap::new());
+    }
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    renv_lock_versions(&text)
+        .with_context(|| format!("failed to read versions from {}", path.display()))
+}
+
+/// Kinds of project R packages are read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Renv,
+    Description,
+    Quarto,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Renv => "renv.lock",
+            Kind::Description => "DESCRIPTION",
+            Kind::Quarto => "Quarto project",
+        }
+    }
+}
+
+/// The kinds of project in `dir`, most precise first: an `renv.lock` pins
+/// the packages a `DESCRIPTION` declares.
+pub fn detect(dir: &Path) -> Vec<Kind> {
+    let mut kinds = Vec::new();
+    if dir.join("renv.lock").exists() {
+        kinds.push(Kind::Renv);
+    }
+    if dir.join("DESCRIPTION").exists() {
+        kinds.push(Kind::Description);
+    }
+    if dir.join("_quarto.yml").exists() || dir.join("_quarto.yaml").exists() {
+        kinds.push(Kind::Quarto);
+    }
+    kinds
+}
+
+/// The packages of the project of `kind` in `dir`.
+pub fn packages_of(dir: &Path, kind: Kind, suggests: bool) -> Result<Vec<String>> {
+    match kind {
+        Kind::Renv => read(&dir.join("renv.lock"), suggests),
+        Kind::Description => read(&dir.join("DESCRIPTION"), suggests),
+        Kind::Quarto => quarto_packages(dir),
+    }
+}
+
+/// The project file to read packages from: `renv.lock` if present, else
+/// `DESCRIPTION`.
+pub fn default_path() -> PathBuf {
+    if Path::new("renv.lock").exists() {
+        PathBuf::from("renv.lock")
+    } else {
+        PathBuf::from("DESCRIPTION")
+    }
+}
+
+/// Read the packages of a `DESCRIPTION` or `renv.lock` file.
+pub fn read(path: &Path, suggests: bool) -> Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let is_lock = path.extension() == Some("lock".as_ref());
+    if is_lock {
+        renv_lock_packages(&text)
+    } else {
+        description_packages(&text, suggests)
+    }
+    .with_context(|| format!("failed to read packages from {}", path.display()))
+}
+
+/// Annotate the package records of an `renv.lock` with their system
+/// packages on the OS of `manifest`, under [`RENV_FIELD`], replacing those of
+/// a previous annotation for the same OS. Other fields are left untouched.
+pub fn annotate_renv_lock(text: &str, manifest: &Manifest) -> Result<String> {
+    let mut lock: Value = serde_json::from_str(text).context("failed to parse renv.lock")?;
+    let os = format!("{}-{}", manifest.distribution, manifest.release);
+    let records = match lock.get_mut("Packages").and_then(Value::as_object_mut) {
+        Some(records) => records,
+        None => bail!("renv.lock has no Packages"),
+    };
+    for (name, record) in records.iter_mut() {
+        let record = match record.as_object_mut() {
+            Some(record) => record,
+            None => continue,
+        };
+        let field = record
+            .entry(RENV_FIELD)
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("{} of {} is not an object", RENV_FIELD, name))?;
+        match manifest.packages.get(name) {
+            Some(system_packages) => {
+                field.insert(os.clone(), json!(system_packages));
+            }
+            None => {
+                field.remove(&os);
+            }
+        }
+        if field.is_empty() {
+            record.remove(RENV_FIELD);
+        }
+    }
+
+    let mut out = serde_json::to_string_pretty(&lock)?;
+    out.push('\n');
+
+    Ok(out)
+}
+
+/// Directories of a Quarto project that hold no sources: output, caches and
+/// libraries.
+const QUARTO_SKIPPED_DIRS: &[&str] = &["_site", "_book", "_freeze", "renv", "node_modules"];
+
+/// Files of a Quarto project, including its extensions, with R code.
+fn quarto_sources(dir: &Path, sources: &mut Vec<PathBuf>) -> Result<()> {
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        if path.is_dir() {
+            if !name.starts_with('.') && !QUARTO_SKIPPED_DIRS.contains(&name) {
+                quarto_sources(&path, sources)?;
+            }
+        } else if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+            if ["qmd", "Rmd", "rmd", "R", "r"].contains(&extension) {
+                sources.push(path);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The R code of a document: the contents of its `{r}` chunks.
+fn r_chunks(text: &str) -> String {
+    let mut code = String::new();
+    let mut in_chunk = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if in_chunk {
+            if trimmed.starts_with("```") {
+                in_chunk = false;
+            } else {
+                code.push_str(line);
+                code.push('\n');
+            }
+        } else if trimmed.starts_with("```{r") || trimmed.starts_with("```{R") {
+            in_chunk = true;
+        }
+    }
+
+    code
+}
+
+/// R packages used by the documents and scripts of a Quarto project, from
+/// `library()` and similar calls and `pkg::` prefixes. Projects with R code
+/// also need knitr and rmarkdown, which Quarto renders R with.
+pub fn quarto_packages(dir: &Path) -> Result<Vec<String>> {
+    if !dir.join("_quarto.yml").exists() && !dir.join("_quarto.yaml").exists() {
+        bail!(
+            "{} is not a Quarto project, it has no _quarto.yml",
+            dir.display()
+        );
+    }
+    let mut sources = Vec::new();
+    quarto_sources(dir, &mut sources)?;
+    sources.sort();
+
+    let name = r"([A-Za-z][A-Za-z0-9.]*[A-Za-z0-9])";
+    let calls = Regex::new(&format!(
+        r#"\b(?:library|require|requireNamespace|loadNamespace)\(\s*["']?{}"#,
+        name
+    ))?;
+    let prefixes = Regex::new(&format!(r"(?:^|[^A-Za-z0-9._]){}:::?[A-Za-z._`]", name))?;
+
+    let mut names = Vec::new();
+    let mut has_r = false;
+    for path in sources.iter() {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let is_script = matches!(path.extension().and_then(|e| e.to_str()), Some("R" | "r"));
+        let code = if is_script { text } else { r_chunks(&text) };
+        if code.trim().is_empty() {
+            continue;
+        }
+        has_r = true;
+        for captures in calls
+            .captures_iter(&code)
+            .chain(prefixes.captures_iter(&code))
+        {
+            let name = &captures[1];
+            if packages::is_base(name) || names.iter().any(|known| known == name) {
+                continue;
+            }
+            if let Ok(name) = packages::normalize(name) {
+                names.push(name);
+            }
+        }
+    }
+    if has_r {
+        for engine in ["knitr", "rmarkdown"] {
+            if !names.iter().any(|known| known == engine) {
+                names.push(engine.to_string());
+            }
+        }
+    }
+
+    Ok(names)
+}
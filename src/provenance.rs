@@ -0,0 +1,211 @@
+//! Where system requirements were resolved, and which R packages need each
+//! system package, for annotating generated output.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::files;
+use crate::lockfile::Lockfile;
+use crate::models::APISysReqs;
+use crate::shell;
+
+/// Source of the requirements of output resolved without the server.
+pub const LOCAL_SOURCE: &str = "local rules database";
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct Provenance {
+    /// Server the requirements were resolved with, or the local rules
+    /// database.
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+    pub distribution: String,
+    pub release: String,
+    pub resolved_at: String,
+    /// R packages needing each system package.
+    pub required_by: BTreeMap<String, Vec<String>>,
+}
+
+impl Provenance {
+    /// The provenance of `sysreqs`, resolved now from `source`.
+    pub fn new(
+        source: &str,
+        repository: Option<&str>,
+        snapshot: Option<&str>,
+        distribution: &str,
+        release: &str,
+        sysreqs: &APISysReqs,
+    ) -> Provenance {
+        let mut required_by: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for req in sysreqs.requirements.iter() {
+            for package in req.requirements.packages.iter() {
+                let names = required_by.entry(package.clone()).or_default();
+                if !names.contains(&req.name) {
+                    names.push(req.name.clone());
+                }
+            }
+        }
+
+        Provenance {
+            source: source.to_string(),
+            repository: repository.map(String::from),
+            snapshot: snapshot.map(String::from),
+            distribution: distribution.to_string(),
+            release: release.to_string(),
+            resolved_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            required_by,
+        }
+    }
+
+    /// The provenance as `#` comments, one line per system package.
+    pub fn comments(&self) -> String {
+        let mut source = self.source.clone();
+        if let Some(repository) = &self.repository {
+            write!(source, ", repository {}", repository).unwrap();
+        }
+        if let Some(snapshot) = &self.snapshot {
+            write!(source, ", snapshot {}", snapshot).unwrap();
+        }
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "# System requirements for {}-{} resolved by r-sysdeps at {}",
+            shell::comment(&self.distribution),
+            shell::comment(&self.release),
+            self.resolved_at
+        )
+        .unwrap();
+        writeln!(out, "# from {}", shell::comment(&source)).unwrap();
+        for (package, names) in self.required_by.iter() {
+            writeln!(
+                out,
+                "#   {}: required by {}",
+                shell::comment(package),
+                shell::comment(&names.join(", "))
+            )
+            .unwrap();
+        }
+
+        out
+    }
+
+    /// `output` with the provenance comments at its top, after any `#!`
+    /// line.
+    pub fn annotate(&self, output: &str) -> String {
+        let (shebang, rest) = match output.strip_prefix("#!") {
+            Some(_) => output.split_at(output.find('\n').map_or(output.len(), |i| i + 1)),
+            None => ("", output),
+        };
+        format!("{}{}{}", shebang, self.comments(), rest)
+    }
+}
+
+/// Suffix of the metadata file written next to a generated file.
+pub const METADATA_SUFFIX: &str = ".meta.json";
+
+/// Metadata of a generated file, tracing it to the tool, server and inputs
+/// it was generated with.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct Metadata {
+    /// Name and version of the tool that generated the file.
+    pub tool: String,
+    pub server: String,
+    pub repository: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository_id: Option<u64>,
+    pub snapshot: String,
+    pub distribution: String,
+    pub release: String,
+    pub generated_at: String,
+    /// SHA-256 of the sorted R package names, one per line.
+    pub input_sha256: String,
+    /// SHA-256 of the generated file.
+    pub sha256: String,
+}
+
+impl Metadata {
+    /// The metadata of `artifact`, generated now from the resolution
+    /// `resolved`, with the id of its repository when known.
+    pub fn new(
+        artifact: &Path,
+        resolved: &Lockfile,
+        repository_id: Option<u64>,
+    ) -> Result<Metadata> {
+        let contents = std::fs::read(artifact)
+            .with_context(|| format!("failed to read {}", artifact.display()))?;
+        // the R packages are sorted, as keys
+        let input: String = resolved
+            .packages
+            .keys()
+            .map(|name| format!("{}\n", name))
+            .collect();
+
+        Ok(Metadata {
+            tool: concat!("r-sysdeps ", env!("CARGO_PKG_VERSION")).to_string(),
+            server: resolved.server.clone(),
+            repository: resolved.repository.clone(),
+            repository_id,
+            snapshot: resolved.snapshot.clone(),
+            distribution: resolved.distribution.clone(),
+            release: resolved.release.clone(),
+            generated_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            input_sha256: format!("{:x}", Sha256::digest(input.as_bytes())),
+            sha256: format!("{:x}", Sha256::digest(&contents)),
+        })
+    }
+
+    /// The metadata written next to `artifact`, if any.
+    pub fn load(artifact: &Path) -> Result<Option<Metadata>> {
+        let path = metadata_path(artifact);
+        let contents = match std::fs::read(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()))
+            }
+        };
+        serde_json::from_slice(&contents)
+            .map(Some)
+            .with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    /// Check that `artifact` is unchanged since the metadata was written.
+    pub fn check(&self, artifact: &Path) -> Result<()> {
+        let contents = std::fs::read(artifact)
+            .with_context(|| format!("failed to read {}", artifact.display()))?;
+        if format!("{:x}", Sha256::digest(&contents)) != self.sha256 {
+            bail!(
+                "{} was changed since it was generated, its SHA-256 differs from {}",
+                artifact.display(),
+                metadata_path(artifact).display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Write the metadata next to `artifact`, returning where.
+    pub fn save(&self, artifact: &Path) -> Result<PathBuf> {
+        let path = metadata_path(artifact);
+        let mut contents = serde_json::to_vec_pretty(self)?;
+        contents.push(b'\n');
+        files::write_atomic(&path, &contents)?;
+        Ok(path)
+    }
+}
+
+/// Where the metadata of `artifact` is written.
+pub fn metadata_path(artifact: &Path) -> PathBuf {
+    let mut path = artifact.as_os_str().to_os_string();
+    path.push(METADATA_SUFFIX);
+    PathBuf::from(path)
+}
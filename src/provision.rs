@@ -0,0 +1,200 @@
+//! Provisioning blocks for machine image and VM tools, running the install
+//! [`commands`](crate::render::commands) of system requirements.
+
+use std::fmt::Write;
+
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::models::APISysReqs;
+use crate::provenance::Provenance;
+use crate::{render, rules, shell};
+
+/// OS of Databricks runtimes from 13.0.
+pub const DATABRICKS_OS: (&str, &str) = ("ubuntu", "22.04");
+
+/// Packer runs provisioning scripts as root through sudo.
+const PACKER_EXECUTE_COMMAND: &str = "sudo -E sh -eux '{{ .Path }}'";
+
+/// `text` as an HCL string literal, with template sequences escaped.
+fn hcl_string(text: &str) -> String {
+    let escaped = serde_json::to_string(text).unwrap();
+    escaped.replace("${", "$${").replace("%{", "%%{")
+}
+
+/// A Packer shell provisioner block in HCL.
+pub fn packer_hcl(sysreqs: &APISysReqs) -> String {
+    let (steps, skipped) = render::commands(sysreqs);
+
+    let mut out = String::new();
+    for script in skipped.iter() {
+        writeln!(out, "{}", script).unwrap();
+    }
+    writeln!(out, "provisioner \"shell\" {{").unwrap();
+    writeln!(
+        out,
+        "  execute_command = {}",
+        hcl_string(PACKER_EXECUTE_COMMAND)
+    )
+    .unwrap();
+    if render::uses_apt(&steps) {
+        writeln!(
+            out,
+            "  environment_vars = [\"DEBIAN_FRONTEND=noninteractive\"]"
+        )
+        .unwrap();
+    }
+    writeln!(out, "  inline = [").unwrap();
+    for step in steps.iter() {
+        writeln!(out, "    {},", hcl_string(step)).unwrap();
+    }
+    writeln!(out, "  ]").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    out
+}
+
+/// A Packer template in the JSON format, holding provisioners only.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct PackerTemplate {
+    pub provisioners: Vec<PackerProvisioner>,
+    /// Where the system requirements came from, with `--provenance`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+/// A Packer shell provisioner.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct PackerProvisioner {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub execute_command: String,
+    pub inline: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub environment_vars: Vec<String>,
+}
+
+/// A Packer shell provisioner in the JSON template format. Unrecognized
+/// install scripts have no place for comments and are left out.
+pub fn packer_json(sysreqs: &APISysReqs) -> PackerTemplate {
+    let (steps, _) = render::commands(sysreqs);
+    let environment_vars = if render::uses_apt(&steps) {
+        vec![String::from("DEBIAN_FRONTEND=noninteractive")]
+    } else {
+        Vec::new()
+    };
+
+    PackerTemplate {
+        provisioners: vec![PackerProvisioner {
+            kind: String::from("shell"),
+            execute_command: String::from(PACKER_EXECUTE_COMMAND),
+            inline: steps,
+            environment_vars,
+        }],
+        provenance: None,
+    }
+}
+
+/// A Vagrant shell provisioner, to paste into the `Vagrant.configure` block.
+/// The heredoc is quoted so that Ruby leaves the script as is.
+pub fn vagrant(sysreqs: &APISysReqs) -> String {
+    let (steps, skipped) = render::commands(sysreqs);
+
+    let mut out = String::new();
+    writeln!(out, "config.vm.provision \"shell\", inline: <<-'SHELL'").unwrap();
+    writeln!(out, "  set -e").unwrap();
+    if render::uses_apt(&steps) {
+        writeln!(out, "  export DEBIAN_FRONTEND=noninteractive").unwrap();
+    }
+    for line in skipped.iter().chain(steps.iter()) {
+        writeln!(out, "  {}", line).unwrap();
+    }
+    writeln!(out, "SHELL").unwrap();
+
+    out
+}
+
+/// A Databricks cluster-scoped init script, run as root on every node when
+/// it starts. apt waits for the package lock, which other init scripts or
+/// unattended upgrades may hold.
+pub fn databricks(sysreqs: &APISysReqs, packages: &[String]) -> String {
+    let (steps, skipped) = render::commands(sysreqs);
+
+    let mut out = String::new();
+    writeln!(out, "#!/bin/bash").unwrap();
+    writeln!(
+        out,
+        "# Databricks cluster-scoped init script installing the system requirements of: {}",
+        shell::comment(&packages.join(", "))
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "# to only install on the driver, wrap the commands in: if [[ $DB_IS_DRIVER = \"TRUE\" ]]; then ... fi"
+    )
+    .unwrap();
+    writeln!(out, "set -euo pipefail").unwrap();
+    if render::uses_apt(&steps) {
+        writeln!(out, "export DEBIAN_FRONTEND=noninteractive").unwrap();
+        writeln!(
+            out,
+            "echo 'DPkg::Lock::Timeout \"600\";' > /etc/apt/apt.conf.d/90r-sysdeps-lock-timeout"
+        )
+        .unwrap();
+    }
+    for line in skipped.iter().chain(steps.iter()) {
+        writeln!(out, "{}", line).unwrap();
+    }
+
+    out
+}
+
+/// A Kubernetes `initContainers` entry that fails the pod when `image`, the
+/// image of its R container, lacks system requirements. Installing into the
+/// init container would not carry over to the R container, so the image is
+/// only checked.
+pub fn k8s_init_container(image: &str, distribution: &str, sysreqs: &APISysReqs) -> Result<String> {
+    let query = rules::query_command(distribution)?;
+    let mut packages: Vec<String> = Vec::new();
+    for req in sysreqs.requirements.iter() {
+        for package in req.requirements.packages.iter() {
+            let word = shell::quote(package).into_owned();
+            if shell::is_single_line(package) && !packages.contains(&word) {
+                packages.push(word);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "# add to the pod spec of the R workload, with the image of its R container"
+    )
+    .unwrap();
+    writeln!(out, "initContainers:").unwrap();
+    writeln!(out, "  - name: check-sysdeps").unwrap();
+    writeln!(out, "    image: {}", serde_json::to_string(image)?).unwrap();
+    writeln!(out, "    command: [\"sh\", \"-c\"]").unwrap();
+    writeln!(out, "    args:").unwrap();
+    writeln!(out, "      - |").unwrap();
+    writeln!(out, "        missing=\"\"").unwrap();
+    writeln!(out, "        for package in {}; do", packages.join(" ")).unwrap();
+    writeln!(
+        out,
+        "          {} \"$package\" >/dev/null 2>&1 || missing=\"$missing $package\"",
+        query
+    )
+    .unwrap();
+    writeln!(out, "        done").unwrap();
+    writeln!(out, "        if [ -n \"$missing\" ]; then").unwrap();
+    writeln!(
+        out,
+        "          echo \"missing system packages:$missing\" >&2"
+    )
+    .unwrap();
+    writeln!(out, "          exit 1").unwrap();
+    writeln!(out, "        fi").unwrap();
+
+    Ok(out)
+}
@@ -0,0 +1,97 @@
+//! r2u, which packages CRAN and Bioconductor as Ubuntu binaries, so that R
+//! packages can be installed with apt along with their system libraries.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::io::Read;
+
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+
+use crate::{api, dcf, packages};
+
+/// The r2u apt repository.
+pub const R2U_URL: &str = "https://r2u.stat.illinois.edu/ubuntu";
+
+/// URL of the apt package index of an Ubuntu release.
+pub fn packages_url(base: &str, codename: &str) -> String {
+    format!(
+        "{}/dists/{}/main/binary-amd64/Packages.gz",
+        base.trim_end_matches('/'),
+        codename
+    )
+}
+
+/// Names of the apt packages r2u provides for an Ubuntu release.
+pub fn available(base: &str, codename: &str) -> Result<BTreeSet<String>> {
+    let url = packages_url(base, codename);
+    let compressed = api::get_bytes(&url, 60)?;
+    let mut index = String::new();
+    GzDecoder::new(compressed.as_slice())
+        .read_to_string(&mut index)
+        .with_context(|| format!("failed to decompress {}", url))?;
+
+    Ok(dcf::parse(&index)
+        .into_iter()
+        .filter_map(|mut record| record.remove("Package"))
+        .collect())
+}
+
+/// The apt package of an R package, if r2u provides one.
+pub fn apt_package(name: &str, available: &BTreeSet<String>) -> Option<String> {
+    ["r-cran-", "r-bioc-"]
+        .iter()
+        .map(|prefix| format!("{}{}", prefix, name.to_lowercase()))
+        .find(|apt_package| available.contains(apt_package))
+}
+
+/// Split R packages into those r2u provides, along with their apt packages,
+/// and those to install from source. Base packages are left out.
+pub fn split(
+    names: &[String],
+    available: &BTreeSet<String>,
+) -> (Vec<(String, String)>, Vec<String>) {
+    let mut binaries = Vec::new();
+    let mut sources = Vec::new();
+    for name in names.iter().filter(|name| !packages::is_base(name)) {
+        match apt_package(name, available) {
+            Some(apt_package) => binaries.push((name.clone(), apt_package)),
+            None => sources.push(name.clone()),
+        }
+    }
+
+    (binaries, sources)
+}
+
+/// Comment block telling which of `names` r2u provides, with the apt command
+/// installing them.
+pub fn report(codename: &str, names: &[String], available: &BTreeSet<String>) -> String {
+    let (binaries, sources) = split(names, available);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "## r2u ({}): {} of {} R packages available as apt packages",
+        codename,
+        binaries.len(),
+        binaries.len() + sources.len()
+    )
+    .unwrap();
+    if !binaries.is_empty() {
+        writeln!(
+            out,
+            "# apt-get install -y {}",
+            binaries
+                .iter()
+                .map(|(_, apt_package)| apt_package.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        )
+        .unwrap();
+    }
+    if !sources.is_empty() {
+        writeln!(out, "## Not in r2u: {}", sources.join(", ")).unwrap();
+    }
+
+    out
+}
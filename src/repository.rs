@@ -0,0 +1,315 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::models::{APIBioConductorVersion, APIDistribution, APIRepository, APIStatusResponse};
+use crate::{os, suggest};
+
+/// Repository types holding R packages.
+pub const R_TYPES: &[&str] = &["R", "Bioconductor"];
+
+/// Find a repository by its (case-sensitive) name and type.
+///
+/// Without a type, repositories of any of [`R_TYPES`] or of no reported type
+/// match.
+pub fn find<'a>(
+    repositories: &'a [APIRepository],
+    name: &str,
+    kind: Option<&str>,
+) -> Result<&'a APIRepository> {
+    let named: Vec<&APIRepository> = repositories
+        .iter()
+        .filter(|repo| repo.name == name)
+        .collect();
+    if named.is_empty() {
+        bail!(
+            "Specified repository '{}' does not exist on the server",
+            name
+        );
+    }
+
+    let matches: Vec<&APIRepository> = named
+        .iter()
+        .copied()
+        .filter(|repo| match kind {
+            Some(kind) => repo.language.eq_ignore_ascii_case(kind),
+            None => {
+                repo.language.is_empty()
+                    || R_TYPES
+                        .iter()
+                        .any(|kind| repo.language.eq_ignore_ascii_case(kind))
+            }
+        })
+        .collect();
+    let describe = |repos: &[&APIRepository]| {
+        repos
+            .iter()
+            .map(|repo| format!("id {} of type {}", repo.id, repo.language))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    match matches.as_slice() {
+        [repository] => Ok(repository),
+        [] => Err(anyhow!(
+            "repository '{}' is not of type {} ({}); select its type with --repository-type",
+            name,
+            kind.map_or_else(|| R_TYPES.join(" or "), String::from),
+            describe(&named)
+        )),
+        _ => Err(anyhow!(
+            "repository name '{}' is ambiguous ({}); select one with --repository-type",
+            name,
+            describe(&matches)
+        )),
+    }
+}
+
+/// Snapshot of repositories always serving their newest packages.
+pub const LATEST: &str = "latest";
+
+/// URL of the source package repository.
+pub fn source_url(server: &str, repository: &str) -> String {
+    snapshot_url(server, repository, LATEST)
+}
+
+/// URL of a frozen snapshot of the source package repository, by date or transaction ID.
+pub fn snapshot_url(server: &str, repository: &str, snapshot: &str) -> String {
+    format!("{}/{}/{}", server, repository, snapshot)
+}
+
+/// URL of the `PACKAGES` index of the source package repository.
+pub fn packages_url(server: &str, repository: &str) -> String {
+    format!("{}/src/contrib/PACKAGES", source_url(server, repository))
+}
+
+/// Check that a snapshot is [`LATEST`], a date as YYYY-MM-DD that has
+/// passed, or a transaction ID. Transaction IDs reading as a date without
+/// its dashes, e.g. 20240115, are likely mistyped dates and warned about.
+pub fn check_snapshot(snapshot: &str) -> Result<()> {
+    if snapshot == LATEST {
+        return Ok(());
+    }
+    if !snapshot.is_empty() && snapshot.bytes().all(|b| b.is_ascii_digit()) {
+        if let Some(date) = undashed_date(snapshot) {
+            tracing::warn!(
+                "snapshot {} is taken as a transaction ID; write {} for the date",
+                snapshot,
+                date
+            );
+        }
+        return Ok(());
+    }
+    let date = humantime::parse_rfc3339(&format!("{}T00:00:00Z", snapshot))
+        .ok()
+        .filter(|_| snapshot.len() == "YYYY-MM-DD".len())
+        .ok_or_else(|| {
+            anyhow!(
+                "invalid snapshot '{}', expected a date as YYYY-MM-DD or a transaction ID",
+                snapshot
+            )
+        })?;
+    if date > std::time::SystemTime::now() {
+        bail!("snapshot {} is in the future", snapshot);
+    }
+    Ok(())
+}
+
+/// `digits` as a YYYY-MM-DD date, if they read as one without its dashes.
+fn undashed_date(digits: &str) -> Option<String> {
+    if digits.len() != "YYYYMMDD".len() {
+        return None;
+    }
+    let date = format!("{}-{}-{}", &digits[..4], &digits[4..6], &digits[6..]);
+    humantime::parse_rfc3339(&format!("{}T00:00:00Z", date))
+        .ok()
+        .map(|_| date)
+}
+
+/// The distribution of the server with system requirements for an OS.
+///
+/// Fails listing those it supports, suggesting the closest, so that typos in
+/// the OS are caught before querying.
+pub fn supported_distro<'a>(
+    status: &'a APIStatusResponse,
+    distribution: &str,
+    release: &str,
+) -> Result<&'a APIDistribution> {
+    let supported: Vec<&APIDistribution> = status
+        .distros
+        .iter()
+        .filter(|distro| distro.sys_reqs)
+        .collect();
+    if let Some(distro) = supported
+        .iter()
+        .find(|distro| distro.distribution == distribution && release.starts_with(&distro.release))
+    {
+        return Ok(distro);
+    }
+
+    let names: Vec<String> = supported
+        .iter()
+        .map(|distro| format!("{}-{}", distro.distribution, distro.release))
+        .collect();
+    let target = format!("{}-{}", distribution, release);
+    let suggestions = suggest::closest(&target, names.iter().map(String::as_str));
+    let hint = if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!("; did you mean {}?", suggestions.join(" or "))
+    };
+    bail!(
+        "server has no system requirements for OS {}{} (supported: {})",
+        target,
+        hint,
+        names.join(", ")
+    )
+}
+
+/// URL of the binary package repository for a distribution release and
+/// architecture, as named by [`os::arch`], frozen at `snapshot`.
+///
+/// On macOS and Windows, the server serves binaries from the source package
+/// repository, where R asks for them under `bin/`. On Linux, binaries of
+/// every architecture are served from the same repository, told apart by the
+/// platform R announces in its user agent (see [`crate::render::rprofile`]).
+pub fn binary_url(
+    server: &str,
+    repository: &str,
+    status: &APIStatusResponse,
+    distribution: &str,
+    release: &str,
+    arch: &str,
+    snapshot: &str,
+) -> Result<String> {
+    if [os::MACOS, os::WINDOWS].contains(&distribution) {
+        if !status.binaries_enabled {
+            bail!("binary repositories not enabled on server")
+        }
+        return Ok(snapshot_url(server, repository, snapshot));
+    }
+
+    let distro = status
+        .distros
+        .iter()
+        .find(|distro| distro.distribution == distribution && release.starts_with(&distro.release))
+        .ok_or_else(|| anyhow!("server does not support OS {}-{}", distribution, release))?;
+
+    if !status.binaries_enabled {
+        bail!("binary repositories not enabled on server")
+    } else if !distro.binaries {
+        bail!(
+            "binary repositories not enabled for {}-{}",
+            distribution,
+            release
+        )
+    }
+
+    let archs = &distro.binary_architectures;
+    if archs.is_empty() {
+        tracing::warn!(
+            "server does not list the architectures of its {}-{} binaries; {} binaries may not be available",
+            distribution,
+            release,
+            arch
+        );
+    } else if !archs.iter().any(|supported| os::arch(supported) == arch) {
+        bail!(
+            "no {} binaries for {}-{} (available: {}); select another with --arch",
+            arch,
+            distribution,
+            release,
+            archs.join(", ")
+        );
+    }
+
+    Ok(format!(
+        "{}/{}/__linux__/{}/{}",
+        server, repository, distro.binary_url, snapshot
+    ))
+}
+
+/// The newest Bioconductor release for an R version, matched on its minor
+/// version, e.g. 4.3.2 on 4.3, or of all when the version is `latest`.
+pub fn bioc_version<'a>(
+    status: &'a APIStatusResponse,
+    r_version: &str,
+) -> Result<&'a APIBioConductorVersion> {
+    let minor = |version: &str| version.split('.').take(2).collect::<Vec<_>>().join(".");
+    status
+        .bioc_versions
+        .iter()
+        .filter(|bioc| r_version == "latest" || minor(&bioc.r_version) == minor(r_version))
+        .max_by_key(|bioc| {
+            bioc.bioc_version
+                .split('.')
+                .map(|part| part.parse::<u32>().unwrap_or(0))
+                .collect::<Vec<_>>()
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "server knows no Bioconductor release for R {} (known: {})",
+                r_version,
+                status
+                    .bioc_versions
+                    .iter()
+                    .map(|bioc| format!("{} for R {}", bioc.bioc_version, bioc.r_version))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+/// A Bioconductor release by its version, e.g. 3.18.
+pub fn bioc_release<'a>(
+    status: &'a APIStatusResponse,
+    bioc_version: &str,
+) -> Result<&'a APIBioConductorVersion> {
+    status
+        .bioc_versions
+        .iter()
+        .find(|bioc| bioc.bioc_version == bioc_version)
+        .ok_or_else(|| {
+            anyhow!(
+                "server knows no Bioconductor release {} (known: {})",
+                bioc_version,
+                status
+                    .bioc_versions
+                    .iter()
+                    .map(|bioc| bioc.bioc_version.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_snapshot_accepts_latest_ids_and_past_dates() {
+        for snapshot in [LATEST, "12345", "20240115", "2024-01-15"] {
+            assert!(check_snapshot(snapshot).is_ok(), "{}", snapshot);
+        }
+    }
+
+    #[test]
+    fn check_snapshot_rejects_other_strings() {
+        for snapshot in [
+            "",
+            "2024-1-15",
+            "2024-13-01",
+            "15/01/2024",
+            "9999-01-01",
+            "12a",
+        ] {
+            assert!(check_snapshot(snapshot).is_err(), "{}", snapshot);
+        }
+    }
+
+    #[test]
+    fn undashed_date_recognizes_dates_only() {
+        assert_eq!(undashed_date("20240115").as_deref(), Some("2024-01-15"));
+        assert_eq!(undashed_date("20241315"), None);
+        assert_eq!(undashed_date("1234567"), None);
+    }
+}
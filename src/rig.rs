@@ -0,0 +1,52 @@
+//! R versions installed with rig, the R installation manager.
+
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// An R installation listed by `rig list --json`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Installation {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub default: bool,
+}
+
+impl Installation {
+    /// The R version, falling back to the name for older rig releases.
+    pub fn version(&self) -> &str {
+        self.version.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// R installations, or `None` when rig is not installed.
+pub fn installations() -> Result<Option<Vec<Installation>>> {
+    let output = match Command::new("rig").args(["list", "--json"]).output() {
+        Ok(output) => output,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).context("failed to run rig"),
+    };
+    if !output.status.success() {
+        anyhow::bail!(
+            "rig list failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let installations =
+        serde_json::from_slice(&output.stdout).context("failed to parse the output of rig list")?;
+    Ok(Some(installations))
+}
+
+/// The version of rig's default R installation.
+pub fn default_version() -> Result<Option<String>> {
+    Ok(installations()?.and_then(|installations| {
+        installations
+            .iter()
+            .find(|installation| installation.default)
+            .map(|installation| installation.version().to_string())
+    }))
+}
@@ -0,0 +1,182 @@
+//! Rocker images from rocker-versioned2, to pick a base image for a set of R
+//! packages: the one leaving the fewest system requirements to install.
+
+use anyhow::{bail, Result};
+
+use crate::models::APISysReqs;
+
+/// An image, and the system packages its install scripts add to its base.
+pub struct Image {
+    pub name: &'static str,
+    pub base: Option<&'static str>,
+    pub system_packages: &'static [&'static str],
+}
+
+/// Images from lightest to heaviest, each built on the one before.
+pub const IMAGES: &[Image] = &[
+    Image {
+        name: "rocker/r-ver",
+        base: None,
+        system_packages: &[
+            "g++",
+            "gcc",
+            "gfortran",
+            "libblas-dev",
+            "libbz2-dev",
+            "libicu-dev",
+            "liblapack-dev",
+            "liblzma-dev",
+            "libpcre2-dev",
+            "make",
+            "zlib1g-dev",
+        ],
+    },
+    Image {
+        name: "rocker/rstudio",
+        base: Some("rocker/r-ver"),
+        system_packages: &["git", "libclang-dev", "libcurl4-openssl-dev", "libssl-dev"],
+    },
+    Image {
+        name: "rocker/tidyverse",
+        base: Some("rocker/rstudio"),
+        system_packages: &[
+            "default-libmysqlclient-dev",
+            "libcairo2-dev",
+            "libfreetype6-dev",
+            "libfribidi-dev",
+            "libgit2-dev",
+            "libharfbuzz-dev",
+            "libjpeg-dev",
+            "libpng-dev",
+            "libpq-dev",
+            "libsasl2-dev",
+            "libsqlite3-dev",
+            "libssh2-1-dev",
+            "libtiff5-dev",
+            "libxml2-dev",
+            "libxtst6",
+            "unixodbc-dev",
+        ],
+    },
+    Image {
+        name: "rocker/verse",
+        base: Some("rocker/tidyverse"),
+        system_packages: &[
+            "cmake",
+            "default-jdk",
+            "ghostscript",
+            "libglpk-dev",
+            "libgmp3-dev",
+            "libhunspell-dev",
+            "libmagick++-dev",
+            "libopenmpi-dev",
+            "libv8-dev",
+            "libxslt1-dev",
+            "libzmq3-dev",
+            "pandoc",
+            "qpdf",
+        ],
+    },
+    Image {
+        name: "rocker/geospatial",
+        base: Some("rocker/verse"),
+        system_packages: &[
+            "gdal-bin",
+            "libfftw3-dev",
+            "libgdal-dev",
+            "libgeos-dev",
+            "libgl1-mesa-dev",
+            "libglu1-mesa-dev",
+            "libgsl0-dev",
+            "libhdf4-alt-dev",
+            "libhdf5-dev",
+            "libjq-dev",
+            "libnetcdf-dev",
+            "libproj-dev",
+            "libprotobuf-dev",
+            "libsqlite3-dev",
+            "libudunits2-dev",
+            "netcdf-bin",
+            "protobuf-compiler",
+            "sqlite3",
+            "tk-dev",
+        ],
+    },
+];
+
+impl Image {
+    /// System packages of the image, including those of the images below it.
+    pub fn all_system_packages(&self) -> Vec<&'static str> {
+        let mut packages = self.system_packages.to_vec();
+        if let Some(base) = self
+            .base
+            .and_then(|base| IMAGES.iter().find(|image| image.name == base))
+        {
+            packages.extend(base.all_system_packages());
+        }
+        packages
+    }
+}
+
+/// Ubuntu release the images of an R version (X.Y[.Z] or latest) are built on.
+pub fn ubuntu_release(r_version: &str) -> Result<&'static str> {
+    if r_version == "latest" {
+        return Ok("24.04");
+    }
+    let mut parts = r_version.split('.').map(str::parse::<u32>);
+    let (major, minor) = match (parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor))) => (major, minor),
+        _ => bail!(
+            "invalid R version '{}', expected e.g. 4.3.2 or latest",
+            r_version
+        ),
+    };
+
+    Ok(match (major, minor) {
+        (4, 0..=1) => "20.04",
+        (4, 2..=3) => "22.04",
+        (4, _) => "24.04",
+        _ => bail!("rocker images are available for R 4.x, not {}", r_version),
+    })
+}
+
+/// How well an image fits a set of system requirements.
+pub struct Advice {
+    pub image: &'static Image,
+    /// Required system packages the image already has.
+    pub satisfied: Vec<String>,
+    /// Required system packages left to install.
+    pub missing: Vec<String>,
+}
+
+/// All images, the best fit for `sysreqs` first: the fewest system packages
+/// left to install, then the lightest.
+pub fn advise(sysreqs: &APISysReqs) -> Vec<Advice> {
+    let mut required: Vec<&String> = sysreqs
+        .requirements
+        .iter()
+        .flat_map(|req| req.requirements.packages.iter())
+        .collect();
+    required.sort();
+    required.dedup();
+
+    let mut advice: Vec<Advice> = IMAGES
+        .iter()
+        .map(|image| {
+            let preinstalled = image.all_system_packages();
+            let (satisfied, missing) = required
+                .iter()
+                .map(|package| package.to_string())
+                .partition(|package| preinstalled.contains(&package.as_str()));
+            Advice {
+                image,
+                satisfied,
+                missing,
+            }
+        })
+        .collect();
+    // stable, so equally good images stay lightest first
+    advice.sort_by_key(|advice| advice.missing.len());
+
+    advice
+}
@@ -0,0 +1,577 @@
+//! Local resolution of system requirements, using the rules of
+//! [rstudio/r-system-requirements](https://github.com/rstudio/r-system-requirements)
+//! matched against the `SystemRequirements` field of R packages.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{APIPackageRequirements, APIPrePost, APIRequirement, APISysReqs};
+use crate::{cache, files, paths, shell};
+
+/// Snapshot of the database bundled into the binary.
+static EMBEDDED: &[u8] = include_bytes!("../data/sysreqs.json.gz");
+
+/// Archive of the upstream rules repository.
+pub const RULES_URL: &str =
+    "https://github.com/rstudio/r-system-requirements/archive/refs/heads/main.tar.gz";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Database {
+    /// When the snapshot was taken.
+    pub updated: String,
+    pub rules: Vec<Rule>,
+    /// `SystemRequirements` of the R packages of the repository, by package
+    /// name, empty for those without any.
+    pub packages: BTreeMap<String, String>,
+    /// The R packages each package needs installed, from its `Depends`,
+    /// `Imports` and `LinkingTo`, without R itself and base packages.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Rule {
+    #[serde(default)]
+    pub name: String,
+    pub patterns: Vec<String>,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Dependency {
+    pub packages: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_install: Vec<Command>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_install: Vec<Command>,
+    pub constraints: Vec<Constraint>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Command {
+    pub command: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Constraint {
+    pub os: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distribution: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub versions: Vec<String>,
+}
+
+impl Constraint {
+    /// The distribution and releases, e.g. `centos 7, 8`.
+    pub fn target(&self) -> String {
+        let distribution = self.distribution.as_deref().unwrap_or(&self.os);
+        if self.versions.is_empty() {
+            distribution.to_string()
+        } else {
+            format!("{} {}", distribution, self.versions.join(", "))
+        }
+    }
+
+    fn matches(&self, distribution: &str, release: &str) -> bool {
+        self.os == "linux"
+            && self.distribution.as_deref() == Some(distribution)
+            && (self.versions.is_empty()
+                || self
+                    .versions
+                    .iter()
+                    .any(|v| release == v || release.starts_with(&format!("{}.", v))))
+    }
+}
+
+impl Rule {
+    fn regexes(&self) -> Result<Vec<Regex>> {
+        self.patterns
+            .iter()
+            .map(|pattern| {
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .with_context(|| format!("invalid pattern '{}' in rule {}", pattern, self.name))
+            })
+            .collect()
+    }
+}
+
+/// A rule found by [`Database::search`], with the R packages it applies to.
+#[derive(Debug)]
+pub struct Found<'a> {
+    pub rule: &'a Rule,
+    pub packages: Vec<&'a str>,
+}
+
+/// Location of the database written by `db update`.
+pub fn path() -> Option<PathBuf> {
+    paths::data_dir().map(|dir| dir.join("sysreqs.json.gz"))
+}
+
+/// Where the checksum of the database written to `path` is kept.
+fn checksum_path(path: &Path) -> PathBuf {
+    let mut checksum = path.as_os_str().to_os_string();
+    checksum.push(".sha256");
+    PathBuf::from(checksum)
+}
+
+impl Database {
+    /// The snapshot bundled into the binary.
+    pub fn embedded() -> Result<Database> {
+        Database::from_gzip(EMBEDDED).with_context(|| "failed to read bundled database")
+    }
+
+    /// The database written by `db update`, or the bundled snapshot.
+    ///
+    /// A written database that is damaged, or fails its checksum, is
+    /// reported and the bundled snapshot used instead.
+    pub fn load() -> Result<Database> {
+        match path() {
+            Some(path) if path.exists() => Database::read(&path).or_else(|err| {
+                tracing::warn!(
+                    "{:#}; using the bundled database, run `r-sysdeps db update` to fetch it again",
+                    err
+                );
+                Database::embedded()
+            }),
+            _ => Database::embedded(),
+        }
+    }
+
+    /// The database written to `path`, checked against its checksum when
+    /// one was written along.
+    fn read(path: &Path) -> Result<Database> {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let checksum_path = checksum_path(path);
+        if let Ok(expected) = std::fs::read_to_string(&checksum_path) {
+            if expected.trim() != cache::checksum(&bytes) {
+                bail!(
+                    "database {} does not match its checksum {}",
+                    path.display(),
+                    checksum_path.display()
+                );
+            }
+        }
+        Database::from_gzip(&bytes)
+            .with_context(|| format!("failed to read database {}", path.display()))
+    }
+
+    pub fn from_gzip(bytes: &[u8]) -> Result<Database> {
+        let mut json = String::new();
+        GzDecoder::new(bytes).read_to_string(&mut json)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(serde_json::to_string(self)?.as_bytes())?;
+        let bytes = encoder.finish()?;
+        files::write_atomic(path, &bytes)?;
+        files::write_atomic(
+            &checksum_path(path),
+            format!("{}\n", cache::checksum(&bytes)).as_bytes(),
+        )
+    }
+
+    /// Rules whose name or system packages contain `query`, ignoring case.
+    pub fn search(&self, query: &str) -> Result<Vec<Found<'_>>> {
+        let query = query.to_lowercase();
+        let contains = |name: &str| name.to_lowercase().contains(&query);
+        self.rules
+            .iter()
+            .filter(|rule| {
+                contains(&rule.name)
+                    || rule
+                        .dependencies
+                        .iter()
+                        .any(|dep| dep.packages.iter().any(|package| contains(package)))
+            })
+            .map(|rule| {
+                let regexes = rule.regexes()?;
+                let packages = self
+                    .packages
+                    .iter()
+                    .filter(|(_, sysreqs)| regexes.iter().any(|re| re.is_match(sysreqs)))
+                    .map(|(name, _)| name.as_str())
+                    .collect();
+                Ok(Found { rule, packages })
+            })
+            .collect()
+    }
+
+    /// Packages of `packages` the database does not know, which
+    /// [`Database::resolve`] takes to have no system requirements.
+    pub fn unknown<'a>(&self, packages: &'a [String]) -> Vec<&'a str> {
+        packages
+            .iter()
+            .filter(|name| !crate::packages::is_base(name) && !self.packages.contains_key(*name))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// `packages` followed by the packages they depend on, recursively, each
+    /// once.
+    fn with_dependencies(&self, packages: &[String]) -> Vec<String> {
+        let mut all: Vec<String> = Vec::new();
+        let mut pending: Vec<&str> = packages.iter().rev().map(String::as_str).collect();
+        while let Some(name) = pending.pop() {
+            if all.iter().any(|seen| seen == name) {
+                continue;
+            }
+            all.push(name.to_string());
+            if let Some(dependencies) = self.dependencies.get(name) {
+                pending.extend(dependencies.iter().rev().map(String::as_str));
+            }
+        }
+        all
+    }
+
+    /// Resolve the system requirements of `packages` and the packages they
+    /// depend on, in the shape returned by the server.
+    pub fn resolve(
+        &self,
+        distribution: &str,
+        release: &str,
+        packages: &[String],
+    ) -> Result<APISysReqs> {
+        let distribution = canonical_distribution(distribution);
+        let installer = installer(distribution, release)?;
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| Ok((rule, rule.regexes()?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut requirements = Vec::new();
+        for name in self.with_dependencies(packages) {
+            let sysreqs = match self.packages.get(&name) {
+                Some(sysreqs) => sysreqs,
+                None => continue,
+            };
+
+            let mut system_packages: Vec<String> = Vec::new();
+            let mut pre_install: Vec<APIPrePost> = Vec::new();
+            let mut post_install: Vec<APIPrePost> = Vec::new();
+            for (rule, regexes) in rules.iter() {
+                if !regexes.iter().any(|re| re.is_match(sysreqs)) {
+                    continue;
+                }
+                let dependencies = rule.dependencies.iter().filter(|dep| {
+                    dep.constraints
+                        .iter()
+                        .any(|constraint| constraint.matches(distribution, release))
+                });
+                for dep in dependencies {
+                    for package in dep.packages.iter() {
+                        if !system_packages.contains(package) {
+                            system_packages.push(package.clone());
+                        }
+                    }
+                    for (commands, steps) in [
+                        (&dep.pre_install, &mut pre_install),
+                        (&dep.post_install, &mut post_install),
+                    ] {
+                        for command in commands.iter() {
+                            if !steps.iter().any(|step| step.script == command.command) {
+                                steps.push(APIPrePost {
+                                    command: None,
+                                    script: command.command.clone(),
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+
+            if system_packages.is_empty() && pre_install.is_empty() && post_install.is_empty() {
+                continue;
+            }
+            requirements.push(APIRequirement {
+                name,
+                requirements: APIPackageRequirements {
+                    install_scripts: system_packages
+                        .iter()
+                        .map(|package| format!("{} {}", installer, shell::quote(package)))
+                        .collect(),
+                    packages: system_packages,
+                    pre_install: Some(pre_install).filter(|steps| !steps.is_empty()),
+                    post_install: Some(post_install).filter(|steps| !steps.is_empty()),
+                },
+            });
+        }
+
+        Ok(APISysReqs { requirements })
+    }
+}
+
+/// Map `/etc/os-release` IDs to the distribution names used by the rules.
+pub fn canonical_distribution(distribution: &str) -> &str {
+    match distribution {
+        "rhel" => "redhat",
+        "rocky" => "rockylinux",
+        "opensuse-leap" | "opensuse-tumbleweed" => "opensuse",
+        "sles" => "sle",
+        other => other,
+    }
+}
+
+/// Whether the rules cover `distribution` at all, regardless of release.
+pub fn supports(distribution: &str) -> bool {
+    installer(canonical_distribution(distribution), "").is_ok()
+}
+
+/// The command installing system packages on `distribution` `release`, to
+/// which the packages are appended.
+pub fn install_command(distribution: &str, release: &str) -> Result<&'static str> {
+    installer(canonical_distribution(distribution), release)
+}
+
+fn installer(distribution: &str, release: &str) -> Result<&'static str> {
+    let major = release.split('.').next().unwrap_or(release);
+    Ok(match distribution {
+        "ubuntu" | "debian" => "apt-get install -y",
+        "centos" | "redhat" | "rockylinux" | "almalinux" if major == "7" => "yum install -y",
+        "centos" | "redhat" | "rockylinux" | "almalinux" | "fedora" => "dnf install -y",
+        "opensuse" | "sle" => "zypper --non-interactive install",
+        "alpine" => "apk add --no-cache",
+        _ => bail!(
+            "no system requirements rules for distribution '{}'",
+            distribution
+        ),
+    })
+}
+
+/// The command exiting successfully when a system package is installed on
+/// `distribution`.
+pub fn query_command(distribution: &str) -> Result<&'static str> {
+    Ok(match canonical_distribution(distribution) {
+        "ubuntu" | "debian" => "dpkg -s",
+        "centos" | "redhat" | "rockylinux" | "almalinux" | "fedora" | "opensuse" | "sle" => {
+            "rpm -q"
+        }
+        "alpine" => "apk info -e",
+        _ => bail!("no package query command known for '{}'", distribution),
+    })
+}
+
+/// Build a database from the upstream rules archive and a `PACKAGES` file
+/// carrying the `SystemRequirements` field, such as CRAN's.
+#[cfg(feature = "http")]
+pub fn download(rules_url: &str, packages_url: &str) -> Result<Database> {
+    let archive = crate::api::get_bytes(rules_url, 60)?;
+
+    let mut rules = Vec::new();
+    let mut tarball = tar::Archive::new(GzDecoder::new(archive.as_slice()));
+    for entry in tarball
+        .entries()
+        .with_context(|| format!("failed to read archive {}", rules_url))?
+    {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let is_rule = path.extension() == Some("json".as_ref())
+            && path.parent().and_then(|dir| dir.file_name()) == Some("rules".as_ref());
+        if !is_rule {
+            continue;
+        }
+
+        let mut json = String::new();
+        entry.read_to_string(&mut json)?;
+        let mut rule: Rule = serde_json::from_str(&json)
+            .with_context(|| format!("failed to parse rule {}", path.display()))?;
+        rule.name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        rules.push(rule);
+    }
+    if rules.is_empty() {
+        bail!("no rules found in {}", rules_url);
+    }
+    rules.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let index = crate::api::get_bytes(packages_url, 60)?;
+    let mut packages = BTreeMap::new();
+    let mut dependencies = BTreeMap::new();
+    for mut record in crate::dcf::parse(&String::from_utf8_lossy(&index)) {
+        let name = match record.remove("Package") {
+            Some(name) => name,
+            None => continue,
+        };
+        let requires = package_dependencies(&record);
+        if !requires.is_empty() {
+            dependencies.insert(name.clone(), requires);
+        }
+        let sysreqs = record.remove("SystemRequirements").unwrap_or_default();
+        packages.insert(name, sysreqs);
+    }
+    if packages.is_empty() {
+        bail!("no packages found in {}", packages_url);
+    }
+
+    Ok(Database {
+        updated: humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string(),
+        rules,
+        packages,
+        dependencies,
+    })
+}
+
+/// Packages of the `Depends`, `Imports` and `LinkingTo` fields of a
+/// `PACKAGES` record, without R itself and base packages.
+#[cfg(feature = "http")]
+fn package_dependencies(record: &BTreeMap<String, String>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for field in ["Depends", "Imports", "LinkingTo"] {
+        for entry in record.get(field).iter().flat_map(|value| value.split(',')) {
+            // drop version requirements such as `(>= 1.0)`
+            let name = entry.split('(').next().unwrap_or_default().trim();
+            if name.is_empty() || name == "R" || crate::packages::is_base(name) {
+                continue;
+            }
+            if !names.iter().any(|seen| seen == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linux(distribution: &str, versions: &[&str]) -> Constraint {
+        Constraint {
+            os: String::from("linux"),
+            distribution: Some(distribution.to_string()),
+            versions: versions.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    fn database() -> Database {
+        let rule = |name: &str, pattern: &str, dependencies| Rule {
+            name: name.to_string(),
+            patterns: vec![pattern.to_string()],
+            dependencies,
+        };
+        Database {
+            updated: String::from("2024-01-15"),
+            rules: vec![
+                rule(
+                    "libxml2",
+                    r"\blibxml2\b",
+                    vec![
+                        Dependency {
+                            packages: vec![String::from("libxml2-dev")],
+                            pre_install: Vec::new(),
+                            post_install: Vec::new(),
+                            constraints: vec![linux("ubuntu", &[]), linux("debian", &[])],
+                        },
+                        Dependency {
+                            packages: vec![String::from("libxml2-devel")],
+                            pre_install: Vec::new(),
+                            post_install: Vec::new(),
+                            constraints: vec![linux("redhat", &["8", "9"])],
+                        },
+                    ],
+                ),
+                rule(
+                    "java",
+                    r"\bjava\b",
+                    vec![Dependency {
+                        packages: vec![String::from("default-jdk")],
+                        pre_install: Vec::new(),
+                        post_install: vec![Command {
+                            command: String::from("R CMD javareconf"),
+                        }],
+                        constraints: vec![linux("ubuntu", &["22.04"])],
+                    }],
+                ),
+            ],
+            packages: [
+                ("xml2", "libxml2: libxml2-dev (deb)"),
+                ("rJava", "Java JDK 1.2 or higher"),
+                ("dplyr", ""),
+            ]
+            .iter()
+            .map(|(name, sysreqs)| (name.to_string(), sysreqs.to_string()))
+            .collect(),
+            dependencies: std::iter::once((String::from("dplyr"), vec![String::from("xml2")]))
+                .collect(),
+        }
+    }
+
+    fn names(packages: &[&str]) -> Vec<String> {
+        packages.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn constraint_matches_release_prefix() {
+        let constraint = linux("redhat", &["8"]);
+        assert!(constraint.matches("redhat", "8"));
+        assert!(constraint.matches("redhat", "8.9"));
+        assert!(!constraint.matches("redhat", "80"));
+        assert!(!constraint.matches("centos", "8"));
+        assert!(linux("ubuntu", &[]).matches("ubuntu", "24.04"));
+    }
+
+    #[test]
+    fn resolve_matches_rules_for_the_os() {
+        let database = database();
+        let resolved = database
+            .resolve("ubuntu", "22.04", &names(&["xml2", "rJava"]))
+            .unwrap();
+        assert_eq!(resolved.requirements.len(), 2);
+        let xml2 = &resolved.requirements[0].requirements;
+        assert_eq!(xml2.packages, ["libxml2-dev"]);
+        assert_eq!(xml2.install_scripts, ["apt-get install -y libxml2-dev"]);
+        let java = &resolved.requirements[1].requirements;
+        assert_eq!(java.packages, ["default-jdk"]);
+        assert_eq!(
+            java.post_install.as_ref().unwrap()[0].script,
+            "R CMD javareconf"
+        );
+
+        let resolved = database
+            .resolve("rhel", "9.3", &names(&["xml2", "rJava"]))
+            .unwrap();
+        assert_eq!(resolved.requirements.len(), 1);
+        assert_eq!(
+            resolved.requirements[0].requirements.install_scripts,
+            ["dnf install -y libxml2-devel"]
+        );
+    }
+
+    #[test]
+    fn resolve_follows_dependencies() {
+        let resolved = database()
+            .resolve("ubuntu", "22.04", &names(&["dplyr"]))
+            .unwrap();
+        let names: Vec<_> = resolved.requirements.iter().map(|req| &req.name).collect();
+        assert_eq!(names, ["xml2"]);
+    }
+
+    #[test]
+    fn resolve_fails_for_unknown_distributions() {
+        assert!(database().resolve("plan9", "4", &names(&["xml2"])).is_err());
+    }
+
+    #[test]
+    fn unknown_skips_known_and_base_packages() {
+        let packages = names(&["xml2", "dplyr", "stats", "nonexistent"]);
+        assert_eq!(database().unknown(&packages), ["nonexistent"]);
+    }
+}
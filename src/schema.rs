@@ -0,0 +1,32 @@
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+
+use crate::containerit::ContaineritSysreqs;
+use crate::document::SysreqsDocument;
+use crate::lockfile::Lockfile;
+use crate::manifest::Manifest;
+use crate::matrix::MatrixEntry;
+use crate::models::{APIRepository, APIStatusResponse, APISysReqs};
+use crate::pak::PakSysreqs;
+use crate::provenance::{Metadata, Provenance};
+use crate::provision::PackerTemplate;
+use crate::timings::Report;
+
+/// JSON Schemas of the machine-readable documents, by name.
+pub fn documents() -> Vec<(&'static str, RootSchema)> {
+    vec![
+        ("sysreqs", schema_for!(APISysReqs)),
+        ("status", schema_for!(APIStatusResponse)),
+        ("repositories", schema_for!(Vec<APIRepository>)),
+        ("pak", schema_for!(PakSysreqs)),
+        ("containerit", schema_for!(ContaineritSysreqs)),
+        ("document", schema_for!(SysreqsDocument)),
+        ("packer-json", schema_for!(PackerTemplate)),
+        ("matrix", schema_for!(Vec<MatrixEntry>)),
+        ("manifest", schema_for!(Manifest)),
+        ("lockfile", schema_for!(Lockfile)),
+        ("metadata", schema_for!(Metadata)),
+        ("provenance", schema_for!(Provenance)),
+        ("timings", schema_for!(Report)),
+    ]
+}
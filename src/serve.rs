@@ -0,0 +1,120 @@
+//! Local caching proxy of the Package Manager API, so that many clients can
+//! share one warm cache instead of each querying the upstream server.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+use anyhow::{anyhow, Result};
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{error, info};
+
+use crate::api;
+use crate::cache::Cache;
+
+/// Serve `upstream`'s `/__api__/` endpoints on `listen` until the process exits.
+pub fn run(upstream: &str, listen: &str, cache: Cache, workers: usize) -> Result<()> {
+    let server = Arc::new(
+        Server::http(listen).map_err(|err| anyhow!("failed to listen on {}: {}", listen, err))?,
+    );
+    let upstream = Arc::new(upstream.to_string());
+    let cache = Arc::new(cache);
+    let flights = Arc::new(Flights::default());
+    info!("serving {} on http://{}", upstream, listen);
+
+    let handles: Vec<_> = (0..workers.max(1))
+        .map(|_| {
+            let server = server.clone();
+            let upstream = upstream.clone();
+            let cache = cache.clone();
+            let flights = flights.clone();
+            thread::spawn(move || loop {
+                match server.recv() {
+                    Ok(request) => handle(&upstream, &cache, &flights, request),
+                    Err(err) => error!("failed to receive request: {}", err),
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle
+            .join()
+            .map_err(|_| anyhow!("request handler panicked"))?;
+    }
+
+    Ok(())
+}
+
+fn handle(upstream: &str, cache: &Cache, flights: &Flights, request: Request) {
+    let path = request.url().to_string();
+    let (status, body) = if *request.method() != Method::Get {
+        (405, error_body("method not allowed"))
+    } else if !path.starts_with("/__api__/") {
+        (404, error_body("not found"))
+    } else {
+        match fetch(&format!("{}{}", upstream, path), cache, flights) {
+            Ok(body) => (200, body),
+            Err(err) => {
+                // client errors are the caller's to see, anything else is a bad gateway
+                let status = match err.downcast_ref::<api::HttpError>() {
+                    Some(http_error) if (400..500).contains(&http_error.status) => {
+                        http_error.status
+                    }
+                    _ => 502,
+                };
+                (status, error_body(&format!("{:#}", err)))
+            }
+        }
+    };
+    info!(method = %request.method(), path = %path, status, "handled request");
+
+    let content_type =
+        Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).expect("valid header");
+    let response = Response::from_data(body)
+        .with_status_code(status)
+        .with_header(content_type);
+    if let Err(err) = request.respond(response) {
+        error!("failed to respond to {}: {}", path, err);
+    }
+}
+
+/// The response to a request, or why there is none, shared by the requests
+/// for its URL made while it was fetched.
+type Fetched = Result<Vec<u8>, Arc<anyhow::Error>>;
+
+/// Fetches in progress, by URL.
+#[derive(Default)]
+struct Flights(Mutex<HashMap<String, Arc<OnceLock<Fetched>>>>);
+
+/// The response to `url` from `cache`, or from upstream through it. Requests
+/// for a URL already being fetched wait for that fetch and share its result,
+/// an error included, rather than each asking upstream.
+fn fetch(url: &str, cache: &Cache, flights: &Flights) -> Fetched {
+    let flight = flights
+        .0
+        .lock()
+        .expect("flights lock poisoned")
+        .entry(url.to_string())
+        .or_default()
+        .clone();
+    let fetched = flight
+        .get_or_init(|| api::get_cached(cache, url, 60).map_err(Arc::new))
+        .clone();
+
+    // later requests go to the cache again, or retry a failed fetch
+    let mut flights = flights.0.lock().expect("flights lock poisoned");
+    if flights
+        .get(url)
+        .is_some_and(|current| Arc::ptr_eq(current, &flight))
+    {
+        flights.remove(url);
+    }
+
+    fetched
+}
+
+fn error_body(message: &str) -> Vec<u8> {
+    serde_json::json!({ "error": message })
+        .to_string()
+        .into_bytes()
+}
@@ -0,0 +1,84 @@
+//! Quoting for the POSIX shell scripts we generate, so that names and scripts
+//! coming from the server can't run anything they don't claim to.
+
+use std::borrow::Cow;
+
+/// Quote `word` as a single shell argument, leaving plain words as they are.
+pub fn quote(word: &str) -> Cow<'_, str> {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        Cow::Borrowed(word)
+    } else {
+        Cow::Owned(format!("'{}'", word.replace('\'', r"'\''")))
+    }
+}
+
+/// Whether `script` is a single command line, without newlines or other
+/// control characters that could smuggle in further commands.
+pub fn is_single_line(script: &str) -> bool {
+    !script.chars().any(|c| c.is_control() && c != '\t')
+}
+
+/// `text` made safe to follow a `#`, with control characters escaped.
+pub fn comment(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_control() {
+                c.escape_default().collect()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// `script` as a single line of shell, to chain with `&&` or follow a
+/// Dockerfile `RUN`. Scripts spanning several lines are run with `sh -c`,
+/// their lines quoted one by one and joined back by `printf`.
+pub fn script_line(script: &str) -> String {
+    if is_single_line(script) {
+        return script.to_string();
+    }
+
+    let lines: Vec<_> = script.lines().map(quote).collect();
+    format!("sh -c \"$(printf '%s\\n' {})\"", lines.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_leaves_plain_words() {
+        assert_eq!(quote("libxml2-dev"), "libxml2-dev");
+        assert_eq!(quote("gcc-c++"), "gcc-c++");
+    }
+
+    #[test]
+    fn quote_wraps_everything_else() {
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("libfoo;touch /tmp/x"), "'libfoo;touch /tmp/x'");
+        assert_eq!(quote("$(id)"), "'$(id)'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn script_line_keeps_single_lines() {
+        assert_eq!(script_line("R CMD javareconf"), "R CMD javareconf");
+    }
+
+    #[test]
+    fn script_line_runs_several_lines_with_sh() {
+        assert_eq!(
+            script_line("echo 'a'\nif true; then echo b; fi"),
+            r#"sh -c "$(printf '%s\n' 'echo '\''a'\''' 'if true; then echo b; fi')""#
+        );
+    }
+
+    #[test]
+    fn comment_escapes_control_characters() {
+        assert_eq!(comment("a\nrm -rf /"), r"a\nrm -rf /");
+        assert!(is_single_line(&comment("a\r\nb")));
+    }
+}
@@ -0,0 +1,155 @@
+//! Signatures of generated files, made and checked with minisign or cosign.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+/// A signing tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Minisign,
+    /// Sigstore's cosign, keyless unless given a key.
+    Cosign,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 2] = [Tool::Minisign, Tool::Cosign];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Minisign => "minisign",
+            Tool::Cosign => "cosign",
+        }
+    }
+
+    /// Suffix of the signature written next to a signed file.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Tool::Minisign => ".minisig",
+            Tool::Cosign => ".sigstore.json",
+        }
+    }
+
+    /// Where the signature of `file` is written.
+    pub fn signature_path(&self, file: &Path) -> PathBuf {
+        let mut path = OsString::from(file.as_os_str());
+        path.push(self.suffix());
+        PathBuf::from(path)
+    }
+
+    /// The tool whose signature of `file` exists.
+    pub fn of_signed(file: &Path) -> Option<Tool> {
+        Tool::ALL
+            .iter()
+            .find(|tool| tool.signature_path(file).exists())
+            .copied()
+    }
+
+    /// Sign `file` with the secret key at `key`, or the tool's default,
+    /// returning where the signature was written.
+    pub fn sign(&self, file: &Path, key: Option<&Path>) -> Result<PathBuf> {
+        let signature = self.signature_path(file);
+        let mut command = Command::new(self.name());
+        match self {
+            Tool::Minisign => {
+                command.arg("-S");
+                if let Some(key) = key {
+                    command.arg("-s").arg(key);
+                }
+                command.arg("-m").arg(file).arg("-x").arg(&signature);
+            }
+            Tool::Cosign => {
+                command.args(["sign-blob", "--yes"]);
+                if let Some(key) = key {
+                    command.arg("--key").arg(key);
+                }
+                command.arg("--bundle").arg(&signature).arg(file);
+            }
+        }
+        self.run(command, "sign", file)?;
+
+        Ok(signature)
+    }
+
+    /// Check the signature of `file` with the public key at `key`, or for
+    /// keyless cosign signatures, the certificate `identity` and its OIDC
+    /// `issuer`.
+    pub fn verify(
+        &self,
+        file: &Path,
+        key: Option<&Path>,
+        identity: Option<&str>,
+        issuer: Option<&str>,
+    ) -> Result<()> {
+        let signature = self.signature_path(file);
+        if !signature.exists() {
+            bail!("no signature {}", signature.display());
+        }
+        let mut command = Command::new(self.name());
+        match self {
+            Tool::Minisign => {
+                let key = key
+                    .ok_or_else(|| anyhow!("minisign needs the public key, give it with --key"))?;
+                command
+                    .arg("-V")
+                    .arg("-p")
+                    .arg(key)
+                    .arg("-m")
+                    .arg(file)
+                    .arg("-x")
+                    .arg(&signature);
+            }
+            Tool::Cosign => {
+                command.arg("verify-blob");
+                match (key, identity, issuer) {
+                    (Some(key), _, _) => {
+                        command.arg("--key").arg(key);
+                    }
+                    (None, Some(identity), Some(issuer)) => {
+                        command
+                            .args(["--certificate-identity", identity])
+                            .args(["--certificate-oidc-issuer", issuer]);
+                    }
+                    _ => bail!(
+                        "cosign needs the public key, or the certificate identity and OIDC issuer of keyless signatures"
+                    ),
+                }
+                command.arg("--bundle").arg(&signature).arg(file);
+            }
+        }
+        self.run(command, "verify", file)
+    }
+
+    fn run(&self, mut command: Command, action: &str, file: &Path) -> Result<()> {
+        let status = command
+            .status()
+            .with_context(|| format!("failed to run {}, is it installed?", self.name()))?;
+        if !status.success() {
+            bail!("{} failed to {} {}", self.name(), action, file.display());
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Tool {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Tool::ALL
+            .iter()
+            .find(|tool| tool.name() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown signing tool '{}', expected minisign or cosign", s))
+    }
+}
@@ -0,0 +1,28 @@
+//! Spack environments providing R and the system libraries of R packages.
+
+use std::fmt::Write;
+
+use crate::shell;
+
+/// A `spack.yaml` environment with a view of all of its packages.
+pub fn environment(packages: &[&str], untranslated: &[String]) -> String {
+    let mut out = String::new();
+    writeln!(out, "spack:").unwrap();
+    writeln!(out, "  specs:").unwrap();
+    for package in ["r", "pkgconf"].iter().chain(packages.iter()) {
+        writeln!(out, "  - {}", package).unwrap();
+    }
+    if !untranslated.is_empty() {
+        writeln!(
+            out,
+            "  # no Spack package known for: {}",
+            shell::comment(&untranslated.join(", "))
+        )
+        .unwrap();
+    }
+    writeln!(out, "  view: true").unwrap();
+    writeln!(out, "  concretizer:").unwrap();
+    writeln!(out, "    unify: true").unwrap();
+
+    out
+}
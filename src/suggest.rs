@@ -0,0 +1,60 @@
+//! "Did you mean" suggestions for mistyped names.
+
+/// Levenshtein distance between `a` and `b`, in characters.
+pub fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Candidates within a few edits of `name`, closest first.
+pub fn closest<'a, I>(name: &str, candidates: I) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = (name.chars().count() / 3).max(2);
+    let mut matches: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    matches.sort();
+    matches.dedup();
+
+    matches
+        .into_iter()
+        .take(3)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_counts_edits() {
+        assert_eq!(distance("", ""), 0);
+        assert_eq!(distance("xml2", "xml2"), 0);
+        assert_eq!(distance("xml", "xml2"), 1);
+        assert_eq!(distance("dplyr", "dplry"), 2);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn closest_orders_by_distance() {
+        let candidates = ["dplyr", "dbplyr", "tidyr", "ggplot2"];
+        assert_eq!(closest("dplyrr", candidates), vec!["dplyr", "dbplyr"]);
+        assert!(closest("zzzzzz", candidates).is_empty());
+    }
+}
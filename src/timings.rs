@@ -0,0 +1,86 @@
+//! Durations of the phases of an invocation, such as OS detection, each
+//! request and rendering, collected for `--timings`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static STARTED: OnceLock<Instant> = OnceLock::new();
+static TIMINGS: Mutex<Vec<Timing>> = Mutex::new(Vec::new());
+
+/// A timed phase.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Timing {
+    /// Kind of phase, e.g. `request` or `render`.
+    pub phase: &'static str,
+    /// What the phase worked on, such as the URL of a request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Milliseconds since timing was enabled.
+    pub start_ms: f64,
+    pub duration_ms: f64,
+}
+
+/// The timings of an invocation, in the order the phases ended.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Report {
+    pub total_ms: f64,
+    pub timings: Vec<Timing>,
+}
+
+/// Start collecting timings, from now.
+pub fn enable() {
+    STARTED.get_or_init(Instant::now);
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Times a phase until dropped.
+#[must_use]
+pub struct Guard {
+    phase: &'static str,
+    detail: Option<String>,
+    start: Instant,
+}
+
+/// Time `phase` until the returned guard is dropped; `detail` is only
+/// evaluated when timings are collected.
+pub fn start<F: FnOnce() -> String>(phase: &'static str, detail: F) -> Option<Guard> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    Some(Guard {
+        phase,
+        detail: Some(detail()).filter(|detail| !detail.is_empty()),
+        start: Instant::now(),
+    })
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let started = *STARTED.get_or_init(Instant::now);
+        let timing = Timing {
+            phase: self.phase,
+            detail: self.detail.take(),
+            start_ms: millis(self.start.saturating_duration_since(started)),
+            duration_ms: millis(self.start.elapsed()),
+        };
+        TIMINGS.lock().expect("timings lock poisoned").push(timing);
+    }
+}
+
+/// The timings collected so far.
+pub fn report() -> Report {
+    let started = *STARTED.get_or_init(Instant::now);
+    Report {
+        total_ms: millis(started.elapsed()),
+        timings: TIMINGS.lock().expect("timings lock poisoned").clone(),
+    }
+}
+
+fn millis(duration: std::time::Duration) -> f64 {
+    (duration.as_secs_f64() * 1e6).round() / 1e3
+}
@@ -0,0 +1,364 @@
+//! Translation of Debian system package names to the package names of other
+//! ecosystems, for targets the server has no system requirements for.
+
+/// Distribution and release whose system packages are translated.
+pub const REFERENCE: (&str, &str) = ("ubuntu", "22.04");
+
+/// An ecosystem to translate Debian packages to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Nix,
+    Spack,
+    Winget,
+    Choco,
+    Conda,
+    Brew,
+}
+
+impl Target {
+    /// The name of the ecosystem's package collection.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Target::Nix => "nixpkgs",
+            Target::Spack => "Spack",
+            Target::Winget => "winget",
+            Target::Choco => "Chocolatey",
+            Target::Conda => "conda-forge",
+            Target::Brew => "Homebrew",
+        }
+    }
+}
+
+/// Debian packages and their nixpkgs attributes.
+const NIX: &[(&str, &str)] = &[
+    ("cmake", "cmake"),
+    ("default-jdk", "jdk"),
+    ("default-libmysqlclient-dev", "libmysqlclient"),
+    ("gdal-bin", "gdal"),
+    ("git", "git"),
+    ("libarchive-dev", "libarchive"),
+    ("libavfilter-dev", "ffmpeg"),
+    ("libbz2-dev", "bzip2"),
+    ("libcairo2-dev", "cairo"),
+    ("libcurl4-openssl-dev", "curl"),
+    ("libfftw3-dev", "fftw"),
+    ("libfontconfig1-dev", "fontconfig"),
+    ("libfreetype6-dev", "freetype"),
+    ("libfribidi-dev", "fribidi"),
+    ("libgdal-dev", "gdal"),
+    ("libgeos-dev", "geos"),
+    ("libgit2-dev", "libgit2"),
+    ("libgl1-mesa-dev", "libGL"),
+    ("libglpk-dev", "glpk"),
+    ("libglu1-mesa-dev", "libGLU"),
+    ("libgmp3-dev", "gmp"),
+    ("libgsl0-dev", "gsl"),
+    ("libharfbuzz-dev", "harfbuzz"),
+    ("libhdf5-dev", "hdf5"),
+    ("libhunspell-dev", "hunspell"),
+    ("libicu-dev", "icu"),
+    ("libjpeg-dev", "libjpeg"),
+    ("libjq-dev", "jq"),
+    ("libleptonica-dev", "leptonica"),
+    ("liblzma-dev", "xz"),
+    ("libmagick++-dev", "imagemagick"),
+    ("libmpfr-dev", "mpfr"),
+    ("libnetcdf-dev", "netcdf"),
+    ("libnode-dev", "nodejs"),
+    ("libopenmpi-dev", "openmpi"),
+    ("libpcre2-dev", "pcre2"),
+    ("libpng-dev", "libpng"),
+    ("libpoppler-cpp-dev", "poppler"),
+    ("libpq-dev", "postgresql"),
+    ("libproj-dev", "proj"),
+    ("libprotobuf-dev", "protobuf"),
+    ("libsasl2-dev", "cyrus_sasl"),
+    ("libsecret-1-dev", "libsecret"),
+    ("libsodium-dev", "libsodium"),
+    ("libsqlite3-dev", "sqlite"),
+    ("libssh2-1-dev", "libssh2"),
+    ("libssl-dev", "openssl"),
+    ("libtesseract-dev", "tesseract"),
+    ("libtiff-dev", "libtiff"),
+    ("libtiff5-dev", "libtiff"),
+    ("libudunits2-dev", "udunits"),
+    ("libuv1-dev", "libuv"),
+    ("libv8-dev", "v8"),
+    ("libwebp-dev", "libwebp"),
+    ("libxml2-dev", "libxml2"),
+    ("libxslt1-dev", "libxslt"),
+    ("libxt-dev", "xorg.libXt"),
+    ("libzmq3-dev", "zeromq"),
+    ("make", "gnumake"),
+    ("pandoc", "pandoc"),
+    ("protobuf-compiler", "protobuf"),
+    ("python3", "python3"),
+    ("tk-dev", "tk"),
+    ("unixodbc-dev", "unixODBC"),
+    ("zlib1g-dev", "zlib"),
+];
+
+/// Debian packages and their Spack packages.
+const SPACK: &[(&str, &str)] = &[
+    ("cmake", "cmake"),
+    ("default-jdk", "openjdk"),
+    ("default-libmysqlclient-dev", "mariadb-c-client"),
+    ("gdal-bin", "gdal"),
+    ("git", "git"),
+    ("libarchive-dev", "libarchive"),
+    ("libavfilter-dev", "ffmpeg"),
+    ("libbz2-dev", "bzip2"),
+    ("libcairo2-dev", "cairo"),
+    ("libcurl4-openssl-dev", "curl"),
+    ("libfftw3-dev", "fftw"),
+    ("libfontconfig1-dev", "fontconfig"),
+    ("libfreetype6-dev", "freetype"),
+    ("libfribidi-dev", "fribidi"),
+    ("libgdal-dev", "gdal"),
+    ("libgeos-dev", "geos"),
+    ("libgit2-dev", "libgit2"),
+    ("libgl1-mesa-dev", "mesa"),
+    ("libglpk-dev", "glpk"),
+    ("libglu1-mesa-dev", "mesa-glu"),
+    ("libgmp3-dev", "gmp"),
+    ("libgsl0-dev", "gsl"),
+    ("libharfbuzz-dev", "harfbuzz"),
+    ("libhdf5-dev", "hdf5"),
+    ("libicu-dev", "icu4c"),
+    ("libjpeg-dev", "libjpeg-turbo"),
+    ("libjq-dev", "jq"),
+    ("libleptonica-dev", "leptonica"),
+    ("liblzma-dev", "xz"),
+    ("libmagick++-dev", "imagemagick"),
+    ("libmpfr-dev", "mpfr"),
+    ("libnetcdf-dev", "netcdf-c"),
+    ("libnode-dev", "node-js"),
+    ("libopenmpi-dev", "openmpi"),
+    ("libpcre2-dev", "pcre2"),
+    ("libpng-dev", "libpng"),
+    ("libpoppler-cpp-dev", "poppler"),
+    ("libpq-dev", "postgresql"),
+    ("libproj-dev", "proj"),
+    ("libprotobuf-dev", "protobuf"),
+    ("libsasl2-dev", "cyrus-sasl"),
+    ("libsodium-dev", "libsodium"),
+    ("libsqlite3-dev", "sqlite"),
+    ("libssh2-1-dev", "libssh2"),
+    ("libssl-dev", "openssl"),
+    ("libtesseract-dev", "tesseract"),
+    ("libtiff-dev", "libtiff"),
+    ("libtiff5-dev", "libtiff"),
+    ("libudunits2-dev", "udunits"),
+    ("libuv1-dev", "libuv"),
+    ("libwebp-dev", "libwebp"),
+    ("libxml2-dev", "libxml2"),
+    ("libxslt1-dev", "libxslt"),
+    ("libxt-dev", "libxt"),
+    ("libzmq3-dev", "libzmq"),
+    ("make", "gmake"),
+    ("protobuf-compiler", "protobuf"),
+    ("python3", "python"),
+    ("tk-dev", "tk"),
+    ("unixodbc-dev", "unixodbc"),
+    ("zlib1g-dev", "zlib"),
+];
+
+/// Debian packages and their winget package identifiers. Most libraries need
+/// no translation, as Rtools provides them.
+const WINGET: &[(&str, &str)] = &[
+    ("cmake", "Kitware.CMake"),
+    ("default-jdk", "EclipseAdoptium.Temurin.21.JDK"),
+    ("ghostscript", "ArtifexSoftware.GhostScript"),
+    ("git", "Git.Git"),
+    ("libnode-dev", "OpenJS.NodeJS.LTS"),
+    ("pandoc", "JohnMacFarlane.Pandoc"),
+    ("python3", "Python.Python.3.12"),
+    ("texlive", "MiKTeX.MiKTeX"),
+];
+
+/// Debian packages and their Chocolatey packages.
+const CHOCO: &[(&str, &str)] = &[
+    ("cmake", "cmake"),
+    ("default-jdk", "temurin21"),
+    ("ghostscript", "ghostscript"),
+    ("git", "git"),
+    ("libnode-dev", "nodejs-lts"),
+    ("pandoc", "pandoc"),
+    ("python3", "python3"),
+    ("texlive", "miktex"),
+];
+
+/// Debian packages and their conda-forge packages.
+const CONDA: &[(&str, &str)] = &[
+    ("cmake", "cmake"),
+    ("default-jdk", "openjdk"),
+    ("default-libmysqlclient-dev", "mariadb-connector-c"),
+    ("gdal-bin", "gdal"),
+    ("ghostscript", "ghostscript"),
+    ("git", "git"),
+    ("libarchive-dev", "libarchive"),
+    ("libavfilter-dev", "ffmpeg"),
+    ("libbz2-dev", "bzip2"),
+    ("libcairo2-dev", "cairo"),
+    ("libcurl4-openssl-dev", "libcurl"),
+    ("libfftw3-dev", "fftw"),
+    ("libfontconfig1-dev", "fontconfig"),
+    ("libfreetype6-dev", "freetype"),
+    ("libfribidi-dev", "fribidi"),
+    ("libgdal-dev", "libgdal"),
+    ("libgeos-dev", "geos"),
+    ("libgit2-dev", "libgit2"),
+    ("libglpk-dev", "glpk"),
+    ("libglu1-mesa-dev", "libglu"),
+    ("libgmp3-dev", "gmp"),
+    ("libgsl0-dev", "gsl"),
+    ("libharfbuzz-dev", "harfbuzz"),
+    ("libhdf5-dev", "hdf5"),
+    ("libicu-dev", "icu"),
+    ("libjpeg-dev", "libjpeg-turbo"),
+    ("libjq-dev", "jq"),
+    ("libleptonica-dev", "leptonica"),
+    ("liblzma-dev", "xz"),
+    ("libmagick++-dev", "imagemagick"),
+    ("libmpfr-dev", "mpfr"),
+    ("libnetcdf-dev", "libnetcdf"),
+    ("libnode-dev", "nodejs"),
+    ("libopenmpi-dev", "openmpi"),
+    ("libpcre2-dev", "pcre2"),
+    ("libpng-dev", "libpng"),
+    ("libpoppler-cpp-dev", "poppler"),
+    ("libpq-dev", "libpq"),
+    ("libproj-dev", "proj"),
+    ("libprotobuf-dev", "libprotobuf"),
+    ("libsasl2-dev", "cyrus-sasl"),
+    ("libsecret-1-dev", "libsecret"),
+    ("libsodium-dev", "libsodium"),
+    ("libsqlite3-dev", "sqlite"),
+    ("libssh2-1-dev", "libssh2"),
+    ("libssl-dev", "openssl"),
+    ("libtesseract-dev", "tesseract"),
+    ("libtiff-dev", "libtiff"),
+    ("libtiff5-dev", "libtiff"),
+    ("libudunits2-dev", "udunits2"),
+    ("libuv1-dev", "libuv"),
+    ("libwebp-dev", "libwebp"),
+    ("libxml2-dev", "libxml2"),
+    ("libxslt1-dev", "libxslt"),
+    ("libxt-dev", "xorg-libxt"),
+    ("libzmq3-dev", "zeromq"),
+    ("make", "make"),
+    ("pandoc", "pandoc"),
+    ("protobuf-compiler", "protobuf"),
+    ("python3", "python"),
+    ("tk-dev", "tk"),
+    ("unixodbc-dev", "unixodbc"),
+    ("zlib1g-dev", "zlib"),
+];
+
+/// Debian packages and their Homebrew formulae.
+const BREW: &[(&str, &str)] = &[
+    ("cmake", "cmake"),
+    ("default-jdk", "openjdk"),
+    ("default-libmysqlclient-dev", "mariadb-connector-c"),
+    ("gdal-bin", "gdal"),
+    ("ghostscript", "ghostscript"),
+    ("git", "git"),
+    ("libarchive-dev", "libarchive"),
+    ("libavfilter-dev", "ffmpeg"),
+    ("libbz2-dev", "bzip2"),
+    ("libcairo2-dev", "cairo"),
+    ("libcurl4-openssl-dev", "curl"),
+    ("libfftw3-dev", "fftw"),
+    ("libfontconfig1-dev", "fontconfig"),
+    ("libfreetype6-dev", "freetype"),
+    ("libfribidi-dev", "fribidi"),
+    ("libgdal-dev", "gdal"),
+    ("libgeos-dev", "geos"),
+    ("libgit2-dev", "libgit2"),
+    ("libgl1-mesa-dev", "mesa"),
+    ("libglpk-dev", "glpk"),
+    ("libglu1-mesa-dev", "mesa-glu"),
+    ("libgmp3-dev", "gmp"),
+    ("libgsl0-dev", "gsl"),
+    ("libharfbuzz-dev", "harfbuzz"),
+    ("libhdf5-dev", "hdf5"),
+    ("libhunspell-dev", "hunspell"),
+    ("libicu-dev", "icu4c"),
+    ("libjpeg-dev", "jpeg-turbo"),
+    ("libjq-dev", "jq"),
+    ("libleptonica-dev", "leptonica"),
+    ("liblzma-dev", "xz"),
+    ("libmagick++-dev", "imagemagick"),
+    ("libmpfr-dev", "mpfr"),
+    ("libnetcdf-dev", "netcdf"),
+    ("libnode-dev", "node"),
+    ("libopenmpi-dev", "open-mpi"),
+    ("libpcre2-dev", "pcre2"),
+    ("libpng-dev", "libpng"),
+    ("libpoppler-cpp-dev", "poppler"),
+    ("libpq-dev", "libpq"),
+    ("libproj-dev", "proj"),
+    ("libprotobuf-dev", "protobuf"),
+    ("libsasl2-dev", "cyrus-sasl"),
+    ("libsecret-1-dev", "libsecret"),
+    ("libsodium-dev", "libsodium"),
+    ("libsqlite3-dev", "sqlite"),
+    ("libssh2-1-dev", "libssh2"),
+    ("libssl-dev", "openssl@3"),
+    ("libtesseract-dev", "tesseract"),
+    ("libtiff-dev", "libtiff"),
+    ("libtiff5-dev", "libtiff"),
+    ("libudunits2-dev", "udunits"),
+    ("libuv1-dev", "libuv"),
+    ("libwebp-dev", "webp"),
+    ("libxml2-dev", "libxml2"),
+    ("libxslt1-dev", "libxslt"),
+    ("libxt-dev", "libxt"),
+    ("libzmq3-dev", "zeromq"),
+    ("make", "make"),
+    ("pandoc", "pandoc"),
+    ("protobuf-compiler", "protobuf"),
+    ("python3", "python"),
+    ("tk-dev", "tcl-tk"),
+    ("unixodbc-dev", "unixodbc"),
+    ("zlib1g-dev", "zlib"),
+];
+
+fn table(target: Target) -> &'static [(&'static str, &'static str)] {
+    match target {
+        Target::Nix => NIX,
+        Target::Spack => SPACK,
+        Target::Winget => WINGET,
+        Target::Choco => CHOCO,
+        Target::Conda => CONDA,
+        Target::Brew => BREW,
+    }
+}
+
+/// The package of `target` providing Debian package `deb`, if known.
+pub fn package(target: Target, deb: &str) -> Option<&'static str> {
+    table(target)
+        .iter()
+        .find(|(name, _)| *name == deb)
+        .map(|(_, translated)| *translated)
+}
+
+/// Translate Debian packages, returning the packages of `target` without
+/// duplicates, and the Debian packages without a translation.
+pub fn packages<'a, I>(target: Target, debs: I) -> (Vec<&'static str>, Vec<String>)
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let mut translated = Vec::new();
+    let mut untranslated = Vec::new();
+    for deb in debs {
+        match package(target, deb) {
+            Some(name) if !translated.contains(&name) => translated.push(name),
+            Some(_) => {}
+            None if !untranslated.contains(deb) => untranslated.push(deb.clone()),
+            None => {}
+        }
+    }
+
+    (translated, untranslated)
+}
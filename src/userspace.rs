@@ -0,0 +1,102 @@
+//! Installs of system libraries without root, into a conda environment or
+//! Homebrew prefix, with the environment R needs to build packages against
+//! them.
+
+use std::fmt::Write;
+use std::process::Command;
+
+use crate::shell;
+
+/// Conda environment the libraries are installed into.
+const CONDA_PREFIX: &str = "${R_SYSDEPS_PREFIX:-$HOME/.local/share/r-sysdeps/conda}";
+
+/// Export the variables compilers, pkg-config and the dynamic linker need to
+/// find the libraries of `$PREFIX`.
+fn environment(out: &mut String) {
+    writeln!(
+        out,
+        "# before installing R packages from source, e.g. in ~/.bashrc:"
+    )
+    .unwrap();
+    for (name, value) in [
+        ("PATH", "$PREFIX/bin"),
+        (
+            "PKG_CONFIG_PATH",
+            "$PREFIX/lib/pkgconfig:$PREFIX/share/pkgconfig",
+        ),
+        ("LD_LIBRARY_PATH", "$PREFIX/lib"),
+        ("LIBRARY_PATH", "$PREFIX/lib"),
+        ("CPATH", "$PREFIX/include"),
+    ] {
+        writeln!(
+            out,
+            "export {}=\"{}${{{}:+:${}}}\"",
+            name, value, name, name
+        )
+        .unwrap();
+    }
+}
+
+fn untranslated_note(out: &mut String, target: &str, untranslated: &[String]) {
+    if !untranslated.is_empty() {
+        writeln!(
+            out,
+            "# no {} package known for: {}",
+            target,
+            shell::comment(&untranslated.join(", "))
+        )
+        .unwrap();
+    }
+}
+
+/// Install into a conda environment from conda-forge.
+pub fn conda(packages: &[&str], untranslated: &[String]) -> String {
+    let mut out = String::new();
+    writeln!(out, "PREFIX=\"{}\"", CONDA_PREFIX).unwrap();
+    let all: Vec<&str> = ["pkg-config"].iter().chain(packages).copied().collect();
+    writeln!(
+        out,
+        "conda create -y -p \"$PREFIX\" -c conda-forge --override-channels {}",
+        all.join(" ")
+    )
+    .unwrap();
+    untranslated_note(&mut out, "conda-forge", untranslated);
+    writeln!(out).unwrap();
+    environment(&mut out);
+
+    out
+}
+
+/// Install with Homebrew, which on Linux lives in a user-owned prefix.
+pub fn brew(packages: &[&str], untranslated: &[String]) -> String {
+    let mut out = String::new();
+    let all: Vec<&str> = ["pkg-config"].iter().chain(packages).copied().collect();
+    writeln!(out, "brew install {}", all.join(" ")).unwrap();
+    untranslated_note(&mut out, "Homebrew", untranslated);
+    writeln!(out).unwrap();
+    writeln!(out, "PREFIX=\"$(brew --prefix)\"").unwrap();
+    environment(&mut out);
+
+    out
+}
+
+/// Whether running as root, assumed when it can't be told.
+pub fn is_root() -> bool {
+    Command::new("id")
+        .arg("-u")
+        .output()
+        .map(|output| output.stdout.trim_ascii() == b"0")
+        .unwrap_or(true)
+}
+
+/// Whether system packages can be installed: as root, or with sudo without
+/// a password prompt.
+pub fn can_install_system_packages() -> bool {
+    let sudo = Command::new("sudo")
+        .args(["-n", "true"])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+
+    is_root() || sudo
+}
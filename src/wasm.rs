@@ -0,0 +1,94 @@
+//! Bindings for JavaScript hosts, using `fetch` for HTTP.
+
+use serde::de::DeserializeOwned;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::models::{APIRepository, APIStatusResponse, APISysReqs};
+use crate::{api, render, repository};
+
+#[wasm_bindgen]
+extern "C" {
+    // the global fetch, available in windows, workers and node
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch(input: &str) -> js_sys::Promise;
+}
+
+fn error<E: std::fmt::Display>(err: E) -> JsValue {
+    JsValue::from_str(&format!("{:#}", err))
+}
+
+async fn get<T: DeserializeOwned>(url: &str) -> Result<T, JsValue> {
+    let response: web_sys::Response = JsFuture::from(fetch(url)).await?.dyn_into()?;
+    let body = JsFuture::from(response.text()?)
+        .await?
+        .as_string()
+        .unwrap_or_default();
+    if !response.ok() {
+        return Err(error(api::HttpError::new(
+            url,
+            response.status(),
+            &response.status_text(),
+            body.as_bytes(),
+        )));
+    }
+    api::read_json(url, body.as_bytes()).map_err(error)
+}
+
+async fn fetch_sysreqs(
+    server: &str,
+    repository: Option<String>,
+    distribution: &str,
+    release: &str,
+    packages: &[String],
+) -> Result<APISysReqs, JsValue> {
+    let server = api::normalize_server(server).map_err(error)?;
+    let server = server.as_str();
+    let repository_name = match repository {
+        Some(name) => name,
+        None => get::<APIStatusResponse>(&api::status_url(server))
+            .await?
+            .cran_repo()
+            .to_string(),
+    };
+    let repositories: Vec<APIRepository> = get(&api::repositories_url(server)).await?;
+    let repository = repository::find(&repositories, &repository_name, None).map_err(error)?;
+
+    let u =
+        api::sysreqs_url(server, distribution, release, repository.id, packages).map_err(error)?;
+    get(u.as_str()).await
+}
+
+/// System requirements of R packages as a JSON document.
+#[wasm_bindgen]
+pub async fn sysreqs(
+    server: String,
+    repository: Option<String>,
+    distribution: String,
+    release: String,
+    packages: Vec<String>,
+) -> Result<String, JsValue> {
+    let response = fetch_sysreqs(&server, repository, &distribution, &release, &packages).await?;
+    serde_json::to_string(&response).map_err(error)
+}
+
+/// System requirements of R packages rendered as a shell script.
+#[wasm_bindgen]
+pub async fn sysdeps(
+    server: String,
+    repository: Option<String>,
+    distribution: String,
+    release: String,
+    packages: Vec<String>,
+) -> Result<String, JsValue> {
+    let response = fetch_sysreqs(&server, repository, &distribution, &release, &packages).await?;
+    Ok(render::text(&response))
+}
+
+/// Render a JSON system requirements document as a shell script.
+#[wasm_bindgen(js_name = renderText)]
+pub fn render_text(sysreqs: &str) -> Result<String, JsValue> {
+    let response: APISysReqs = serde_json::from_str(sysreqs).map_err(error)?;
+    Ok(render::text(&response))
+}
@@ -0,0 +1,50 @@
+//! Windows install commands for R, Rtools and the tools R packages need.
+//!
+//! Rtools provides the libraries R packages build against, so only tools
+//! outside of it, such as Java or pandoc, are installed separately.
+
+use std::fmt::Write;
+
+use crate::shell;
+
+/// Comment on the system packages Rtools is expected to provide.
+fn rtools_note(out: &mut String, untranslated: &[String]) {
+    if !untranslated.is_empty() {
+        writeln!(
+            out,
+            "# provided by Rtools or not needed on Windows: {}",
+            shell::comment(&untranslated.join(", "))
+        )
+        .unwrap();
+    }
+}
+
+/// `winget install` commands, one per package.
+pub fn winget(packages: &[&str], untranslated: &[String]) -> String {
+    let mut out = String::new();
+    for package in ["RProject.R", "RProject.Rtools"].iter().chain(packages) {
+        writeln!(
+            out,
+            "winget install --exact --id {} --accept-package-agreements --accept-source-agreements",
+            package
+        )
+        .unwrap();
+    }
+    rtools_note(&mut out, untranslated);
+
+    out
+}
+
+/// A `choco install` command.
+pub fn choco(packages: &[&str], untranslated: &[String]) -> String {
+    let mut out = String::new();
+    let all: Vec<&str> = ["r.project", "rtools"]
+        .iter()
+        .chain(packages)
+        .copied()
+        .collect();
+    writeln!(out, "choco install -y {}", all.join(" ")).unwrap();
+    rtools_note(&mut out, untranslated);
+
+    out
+}